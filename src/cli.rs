@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use serde_json::json;
+
+use crate::desktop_entry::{is_desktop_entry, is_desktop_entry_offloaded};
+use crate::internals::{anti_cheat_match, backup_path, execute, is_bind_mounted, is_launcher_of, is_offload_masked, launcher_path, migrate_wrapper_names, parse_env_lines, remove_launcher, revert, set_offload, system_path_match, unmount_wrapper, wrapped_executables, WrapOptions, WrapStrategy, PROTON_ENV};
+use crate::config::Config;
+use crate::self_test;
+use crate::settings::Settings;
+use crate::{add_config, config_entry, remove_config, validate_config, wrapped_with_settings, AppPaths, BrokenEntry};
+
+const USAGE: &str = "Usage: NvidiaManager [COMMAND]
+
+Runs the GUI when no command is given.
+
+Commands:
+    list [--format plain|json]    List the added executables
+    add [--force] [--dry-run] <path>
+                                  Make an executable (or every executable in a folder) use the NVIDIA GPU,
+                                  --force wraps it even if it looks protected by anti-cheat
+    enable [--force] [--dry-run] <path>
+                                  Same as add
+    remove [--dry-run] <path>     Revert an added executable (or folder) and remove it from the config
+    disable [--dry-run] <path>    Same as remove
+    self-test                     Wrap and revert a throwaway executable to check that it works on this system,
+                                  also available as --self-test
+
+--dry-run prints what would be changed without changing anything";
+
+/// Run a command from the command line instead of the GUI
+pub fn run(args: &[String]) -> ExitCode {
+    let (paths, settings) = match AppPaths::load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Nobody is around to ask about entries with missing backups, so the reverted ones are dropped (unless nothing may be changed).
+    // The dangling symlinks stay, so the GUI can still offer to clean them up
+    let dry_run = args.iter().any(|arg| return arg == "--dry-run");
+    if !dry_run {
+        migrate_wrapper_names(&paths.wrapper_dir, &wrapped_with_settings(&Config::read(&paths.config_path)), &parse_env_lines(PROTON_ENV));
+    }
+    for (entry, state) in validate_config(&paths.config_path, &paths.wrapper_dir, !dry_run) {
+        match state {
+            BrokenEntry::MissingWrapper => eprintln!("The wrapper script of {entry} is gone, it can't launch. Repair it in the GUI"),
+            BrokenEntry::OrphanedSymlink => eprintln!("{entry} is still a symlink to its wrapper script but its backup is gone, it can't launch. Clean it up in the GUI and reinstall it"),
+            BrokenEntry::Reverted if !dry_run => eprintln!("Removed {entry} from the config, its backup is missing"),
+            BrokenEntry::Reverted => eprintln!("The backup of {entry} is missing"),
+        }
+    }
+
+    return match args.first().map(String::as_str) {
+        Some("list") => list(&paths, &args[1..]),
+        Some("add" | "enable") => add(&paths, &settings, &args[1..]),
+        Some("remove" | "disable") => remove(&paths, &args[1..]),
+        Some("self-test" | "--self-test") => self_test(&paths),
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Wrap an executable and add it to the config
+fn add(paths: &AppPaths, settings: &Settings, args: &[String]) -> ExitCode {
+    let (flags, path) = split_flags(args);
+    let (Some(path), true) = (path, flags.iter().all(|flag| return matches!(*flag, "--force" | "--dry-run"))) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let force = flags.contains(&"--force");
+    let dry_run = flags.contains(&"--dry-run");
+
+    // Relative paths are resolved here, the config must not depend on where we were run from
+    let entry = match config_entry(path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            eprintln!("Can't resolve {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = Config::read(&paths.config_path);
+    if config.entry(&entry).is_some() {
+        eprintln!("{entry} is already added");
+        return ExitCode::FAILURE;
+    }
+    if let (Some(prefix), false) = (system_path_match(&PathBuf::from(&entry), &settings.scan.system_paths), is_desktop_entry(&PathBuf::from(&entry))) {
+        eprintln!("{entry} is in {prefix}, which is on the system path blocklist (system_paths in settings.toml)");
+        return ExitCode::FAILURE;
+    }
+    if let (false, Some(pattern)) = (force, anti_cheat_match(&PathBuf::from(&entry), &settings.anti_cheat_patterns)) {
+        eprintln!("{entry} looks protected by anti-cheat (matched \"{pattern}\"), modifying it may get you banned. Use --force to wrap it anyway");
+        return ExitCode::FAILURE;
+    }
+
+    // Wrapping an already wrapped executable (or a folder with anything wrapped in it) would revert it instead
+    let is_folder = PathBuf::from(&entry).is_dir();
+    if (is_folder && !wrapped_executables(&PathBuf::from(&entry)).is_empty()) || (!is_folder && backup_path(&PathBuf::from(&entry)).exists()) || is_desktop_entry_offloaded(&PathBuf::from(&entry)) {
+        eprintln!("{entry} is already wrapped");
+        return ExitCode::FAILURE;
+    }
+    let options = WrapOptions { proton_env: parse_env_lines(PROTON_ENV), dry_run, ..settings.wrap_options() };
+    let summary = match execute(&paths.wrapper_dir, &PathBuf::from(&entry), config.backend_of(&entry), &config.env_of(&entry), &options) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("Failed to wrap {entry}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if is_folder {
+        println!("{entry}: {}", summary.describe());
+        for (path, reason) in &summary.skipped {
+            println!("  Skipped {}: {reason}", path.display());
+        }
+    }
+    // A symlink gets its real file wrapped, which is what the config has to keep track of
+    let entry = summary.resolved.as_ref().map_or(entry, |real_path| return real_path.display().to_string());
+    if dry_run { return ExitCode::SUCCESS; }
+    if summary.succeeded == 0 {
+        eprintln!("Nothing in {entry} could be wrapped");
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = add_config(&entry, &paths.config_path, |_| {}) {
+        eprintln!("Failed to add {entry} to the config: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    // Respect the master switch, which is off if anything added before is masked
+    let offload_enabled = !config.entries.iter()
+        .flat_map(|added| return wrapped_executables(&PathBuf::from(&added.path)))
+        .any(|path| return is_offload_masked(&path));
+    if !offload_enabled {
+        for path in wrapped_executables(&PathBuf::from(&entry)) {
+            if let Err(e) = set_offload(&paths.wrapper_dir, &path, false) {
+                eprintln!("Failed to switch the NVIDIA offload off for {}: {e}", path.display());
+            }
+        }
+    }
+
+    // It's added either way so the wrapped part can be removed again, but a script should know not everything worked
+    if !summary.failed.is_empty() {
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS
+}
+
+/// Revert an added executable and remove it from the config
+fn remove(paths: &AppPaths, args: &[String]) -> ExitCode {
+    let (flags, path) = split_flags(args);
+    let (Some(path), true) = (path, flags.iter().all(|flag| return *flag == "--dry-run")) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let dry_run = !flags.is_empty();
+
+    let entry = match config_entry(path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            eprintln!("Can't resolve {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = Config::read(&paths.config_path);
+    let Some(recorded) = config.entry(&entry) else {
+        eprintln!("{entry} isn't added");
+        return ExitCode::FAILURE;
+    };
+
+    let (bind_mounted, has_launcher) = (recorded.bind_mount, recorded.launcher);
+    if dry_run {
+        if bind_mounted {
+            println!("[dry run] Would unmount the wrapper from {entry}");
+        } else if has_launcher {
+            println!("[dry run] Would remove the launcher of {entry}");
+        } else {
+            for path in wrapped_executables(&PathBuf::from(&entry)) {
+                if let Err(e) = execute(&paths.wrapper_dir, &path, recorded.backend, &recorded.env, &WrapOptions { dry_run: true, ..WrapOptions::default() }) {
+                    eprintln!("Failed to revert {}: {e}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        println!("[dry run] Would remove {entry} from the config");
+        return ExitCode::SUCCESS;
+    }
+
+    let result = if bind_mounted {
+        unmount_wrapper(&PathBuf::from(&entry))
+    } else if has_launcher {
+        remove_launcher(&PathBuf::from(&entry))
+    } else {
+        revert(&paths.wrapper_dir, &PathBuf::from(&entry))
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to revert {entry}: {e}");
+        return ExitCode::FAILURE;
+    }
+    remove_config(&entry, &paths.config_path);
+    println!("Reverted {entry}");
+    return ExitCode::SUCCESS
+}
+
+/// Split the arguments of a command into its `--` flags and its one path, `None` if there isn't exactly one
+fn split_flags(args: &[String]) -> (Vec<&str>, Option<&String>) {
+    let (flags, rest): (Vec<&String>, Vec<&String>) = args.iter().partition(|arg| return arg.starts_with("--"));
+    let path = match rest.as_slice() {
+        [path] => Some(*path),
+        _ => None,
+    };
+    return (flags.into_iter().map(String::as_str).collect(), path)
+}
+
+/// Print the added executables, one path per line or as JSON
+fn list(paths: &AppPaths, args: &[String]) -> ExitCode {
+    let json = match args {
+        [] => false,
+        [flag, format] if flag == "--format" => match format.as_str() {
+            "plain" => false,
+            "json" => true,
+            _ => {
+                eprintln!("Unknown format {format}, expected plain or json");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let entries = Config::read(&paths.config_path).entries;
+
+    if !json {
+        for entry in entries {
+            println!("{}", entry.path);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let objects: Vec<serde_json::Value> = entries.iter()
+        .map(|entry| {
+            // An entry is enabled when it's still mounted, its launcher is still there, or everything behind it is wrapped and not masked by the master switch
+            let path = PathBuf::from(&entry.path);
+            let (strategy, enabled) = if entry.bind_mount {
+                (WrapStrategy::BindMount, is_bind_mounted(&path))
+            } else if entry.launcher {
+                (WrapStrategy::SeparateLauncher, launcher_path(&path).is_some_and(|launcher| return is_launcher_of(&launcher, &path)))
+            } else {
+                let executables = wrapped_executables(&path);
+                (WrapStrategy::InPlaceSymlink, !executables.is_empty() && executables.iter().all(|path| return backup_path(path).exists() && !is_offload_masked(path)))
+            };
+            let env: serde_json::Map<String, serde_json::Value> = entry.backend.env(&entry.env).vars.into_iter()
+                .map(|(key, value)| return (key, json!(value)))
+                .collect();
+            return json!({
+                "path": entry.path,
+                "enabled": enabled,
+                "strategy": strategy.name(),
+                "vendor": entry.backend.name(),
+                "env": env,
+            })
+        })
+        .collect();
+    println!("{}", serde_json::Value::Array(objects));
+    return ExitCode::SUCCESS
+}
+
+fn self_test(paths: &AppPaths) -> ExitCode {
+    let checks = self_test::run(&paths.wrapper_dir);
+    for check in &checks {
+        match &check.result {
+            Ok(()) => println!("PASS  {}", check.name),
+            Err(e) => println!("FAIL  {}: {e}", check.name),
+        }
+    }
+    if checks.iter().all(|check| return check.result.is_ok()) {
+        println!("Self-test passed");
+        return ExitCode::SUCCESS;
+    }
+    println!("Self-test failed, see the log for details");
+    return ExitCode::FAILURE
+}
@@ -0,0 +1,591 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+use log::{error, warn};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::flatpak;
+use crate::internals::{is_env_name, temp_sibling, NvidiaExtra, OffloadBackend, OriginalPermissions, WrapperEnv, WrapStrategy};
+
+/// Version of the config format, only bumped when older versions can't make sense of it anymore (new fields alone don't need a bump).
+/// Version 1 kept the entries as a list of paths with their metadata in tables keyed by path next to it, since 2 the metadata is on the entries
+pub const CONFIG_VERSION: i64 = 2;
+
+/// Everything that's added, loaded and saved as a whole
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Config {
+    pub version: i64,
+    /// The added paths with everything recorded about them, sorted by path
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+    /// Paths that aren't added (anymore) but had their backend or environment customized, adding them again picks those up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remembered: Vec<Entry>,
+    /// Offloaded Flatpak apps by their ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub flatpaks: BTreeMap<String, FlatpakOffload>,
+    /// Whatever a newer version added without bumping the version, kept as is
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+/// An added path along with everything recorded about it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub path: String,
+    /// NVIDIA for entries from before there was a choice
+    #[serde(default = "nvidia", with = "backend_name", skip_serializing_if = "is_nvidia")]
+    pub backend: OffloadBackend,
+    /// Bind mounted rather than symlinked
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bind_mount: bool,
+    /// Has a separate launcher instead of being wrapped
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub launcher: bool,
+    #[serde(flatten)]
+    pub env: WrapperEnv,
+    /// What's needed to undo the wrapping of the executables it wrapped, by their original path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub wrapped: BTreeMap<String, Wrapped>,
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+/// What's recorded about a wrapped executable so reverting can put it back as it was
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Wrapped {
+    /// Where its backup went if it's kept in the central directory instead of next to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    /// SHA-256 of the original when it was wrapped, to tell if its backup got swapped out in between
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Its mode and ownership, a backup copied across filesystems doesn't keep them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<OriginalPermissions>,
+}
+
+/// A Flatpak app offloaded through its overrides
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlatpakOffload {
+    #[serde(default = "nvidia", with = "backend_name")]
+    pub backend: OffloadBackend,
+    /// What was changed in its overrides, nothing in an exported config since that's only meaningful on the machine it was exported from
+    #[serde(flatten)]
+    pub changed: flatpak::Override,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        return Config { version: CONFIG_VERSION, entries: Vec::new(), remembered: Vec::new(), flatpaks: BTreeMap::new(), unknown: toml::Table::new() }
+    }
+}
+
+impl Entry {
+    pub fn new(path: &str) -> Self {
+        return Entry { path: path.to_string(), backend: OffloadBackend::Nvidia, bind_mount: false, launcher: false, env: WrapperEnv::default(), wrapped: BTreeMap::new(), unknown: toml::Table::new() }
+    }
+
+    /// Whether there's anything worth remembering once it's removed, executables still wrapped included
+    fn is_customized(&self) -> bool {
+        return self.backend != OffloadBackend::Nvidia || self.env != WrapperEnv::default() || !self.wrapped.is_empty()
+    }
+
+    /// How it was added
+    pub fn strategy(&self) -> WrapStrategy {
+        if self.bind_mount { return WrapStrategy::BindMount }
+        if self.launcher { return WrapStrategy::SeparateLauncher }
+        return WrapStrategy::InPlaceSymlink
+    }
+
+    /// Whether wrapping this entry may have wrapped the original: it's the entry itself, in its folder or what the entry is a symlink to
+    fn covers(&self, original: &Path) -> bool {
+        let path = Path::new(&self.path);
+        return original.starts_with(path) || path.canonicalize().is_ok_and(|real_path| return real_path == original)
+    }
+}
+
+impl Config {
+    /// Read a config, failing if it doesn't parse or is from a newer version (we'd drop whatever it changed). Ones from version 1 are brought up to date
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let document = fs::read_to_string(path)?.parse::<toml::Table>()
+            .map_err(|e| return format!("Failed to parse {}: {e}", path.display()))?;
+        let version = document.get("version").and_then(toml::Value::as_integer).unwrap_or(0);
+        if version > CONFIG_VERSION {
+            return Err(format!("{} is from a newer version of Nvidia Manager (config version {version}, this one supports up to {CONFIG_VERSION}), please update", path.display()).into());
+        }
+        let mut config = if version < 2 {
+            Config::from_v1(document)?
+        } else {
+            toml::Value::Table(document).try_into::<Config>().map_err(|e| return format!("Failed to read {}: {e}", path.display()))?
+        };
+        config.tidy();
+        return Ok(config)
+    }
+
+    /// Read the config just to look at it, empty if there's none yet or it can't be read (which is logged)
+    pub fn read(path: &Path) -> Config {
+        if !path.exists() { return Config::default() }
+        return Config::load(path).unwrap_or_else(|e| {
+            error!("Failed to read the config: {e}");
+            return Config::default()
+        })
+    }
+
+    /// Load the config, change it and save it again. Nothing is saved if it can't be loaded, that would lose everything in it
+    /// Updates are one at a time, wrapping the executables of a folder in parallel records each of them
+    pub fn update<T>(path: &Path, change: impl FnOnce(&mut Config) -> T) -> Result<T, Box<dyn Error>> {
+        let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut config = if path.exists() { Config::load(path)? } else { Config::default() };
+        let changed = change(&mut config);
+        config.save(path)?;
+        *cache = Some(Cached { path: path.to_path_buf(), stamp: stamp(path), config });
+        return Ok(changed)
+    }
+
+    /// Write the config, sorted by path so the file stays diff friendly.
+    /// It's written next to it and renamed over it, so it's never seen half written
+    pub fn save(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.version = CONFIG_VERSION;
+        self.remembered.retain(Entry::is_customized);
+        self.entries.sort_by(|a, b| return a.path.cmp(&b.path));
+        self.remembered.sort_by(|a, b| return a.path.cmp(&b.path));
+
+        let temp_path = temp_sibling(path);
+        fs::write(&temp_path, toml::to_string(self)?)?;
+        if let Err(e) = fs::rename(&temp_path, path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into())
+        }
+        return Ok(())
+    }
+
+    /// The added paths
+    pub fn paths(&self) -> HashSet<String> {
+        return self.entries.iter().map(|entry| return entry.path.clone()).collect()
+    }
+
+    /// The entry of an added path
+    pub fn entry(&self, path: &str) -> Option<&Entry> {
+        return self.entries.iter().find(|entry| return entry.path == path)
+    }
+
+    /// The backend a path is added with, or was the last time. NVIDIA if it never was
+    pub fn backend_of(&self, path: &str) -> OffloadBackend {
+        return self.recorded(path).map_or(OffloadBackend::Nvidia, |entry| return entry.backend)
+    }
+
+    /// The wrapper environment of a path, added or not, the default offload variables if it was never customized
+    pub fn env_of(&self, path: &str) -> WrapperEnv {
+        return self.recorded(path).map(|entry| return entry.env.clone()).unwrap_or_default()
+    }
+
+    fn recorded(&self, path: &str) -> Option<&Entry> {
+        return self.entries.iter().chain(&self.remembered).find(|entry| return entry.path == path)
+    }
+
+    /// Add a path, with whatever was remembered of it. One that's already added just gets its entry returned.
+    /// It takes over the records of what it wrapped, those are made before it's added
+    pub fn add(&mut self, path: &str) -> &mut Entry {
+        let index = if let Some(index) = self.entries.iter().position(|entry| return entry.path == path) {
+            warn!("{path} is already in the config file");
+            index
+        } else {
+            let mut entry = match self.remembered.iter().position(|entry| return entry.path == path) {
+                Some(index) => self.remembered.remove(index),
+                None => Entry::new(path),
+            };
+            for remembered in &mut self.remembered {
+                let (covered, kept) = std::mem::take(&mut remembered.wrapped).into_iter().partition(|(original, _)| return entry.covers(Path::new(original)));
+                entry.wrapped.extend::<BTreeMap<_, _>>(covered);
+                remembered.wrapped = kept;
+            }
+            self.remembered.retain(Entry::is_customized);
+            self.entries.push(entry);
+            self.entries.len() - 1
+        };
+        return &mut self.entries[index]
+    }
+
+    /// Remove a path, returning its entry. Its backend and environment are remembered if they were customized
+    pub fn remove(&mut self, path: &str) -> Option<Entry> {
+        let Some(index) = self.entries.iter().position(|entry| return entry.path == path) else {
+            warn!("{path} is not in the config file");
+            return None
+        };
+        let entry = self.entries.remove(index);
+        if entry.is_customized() {
+            self.remembered.push(Entry { bind_mount: false, launcher: false, ..entry.clone() });
+        }
+        return Some(entry)
+    }
+
+    /// The entry of a path to customize, added or not
+    pub fn customize(&mut self, path: &str) -> &mut Entry {
+        if let Some(index) = self.entries.iter().position(|entry| return entry.path == path) {
+            return &mut self.entries[index]
+        }
+        let index = self.remembered.iter().position(|entry| return entry.path == path).unwrap_or_else(|| {
+            self.remembered.push(Entry::new(path));
+            return self.remembered.len() - 1
+        });
+        return &mut self.remembered[index]
+    }
+
+    /// What's recorded about a wrapped executable
+    pub fn wrapped(&self, original: &str) -> Option<&Wrapped> {
+        return self.entries.iter().chain(&self.remembered).find_map(|entry| return entry.wrapped.get(original))
+    }
+
+    /// Change what's recorded about a wrapped executable, dropping the record once there's nothing left in it.
+    /// It goes on the entry that wrapped it, or one of its own until that's added
+    pub fn record_wrapped(&mut self, original: &str, change: impl FnOnce(&mut Wrapped)) {
+        let holds = |entry: &Entry| return entry.wrapped.contains_key(original);
+        let recorded = if let Some(index) = self.entries.iter().position(holds) {
+            &mut self.entries[index]
+        } else if let Some(index) = self.remembered.iter().position(holds) {
+            &mut self.remembered[index]
+        } else if let Some(index) = self.entries.iter().position(|entry| return entry.covers(Path::new(original))) {
+            &mut self.entries[index]
+        } else {
+            self.customize(original)
+        };
+        let wrapped = recorded.wrapped.entry(original.to_string()).or_default();
+        change(wrapped);
+        if *wrapped == Wrapped::default() {
+            recorded.wrapped.remove(original);
+        }
+    }
+
+    /// What gets exported to set up the same entries on another machine.
+    /// What was changed in the overrides of the Flatpak apps and what was wrapped are only meaningful on this one, the import records them anew
+    pub fn exported(&self) -> Config {
+        let flatpaks = self.flatpaks.iter()
+            .map(|(app_id, offload)| return (app_id.clone(), FlatpakOffload { backend: offload.backend, changed: flatpak::Override::default() }))
+            .collect();
+        let entries = self.entries.iter().map(|entry| return Entry { wrapped: BTreeMap::new(), ..entry.clone() }).collect();
+        return Config { entries, flatpaks, ..Config::default() }
+    }
+
+    /// Clean up after hand editing: stray whitespace around the paths, empty and duplicate ones, and variable names that would end up in the wrapper script as is
+    fn tidy(&mut self) {
+        for entries in [&mut self.entries, &mut self.remembered] {
+            let mut seen = HashSet::new();
+            entries.retain_mut(|entry| {
+                entry.path = entry.path.trim().to_string();
+                let path = &entry.path;
+                entry.env.vars.retain(|(key, _)| {
+                    if is_env_name(key) { return true }
+                    warn!("Ignoring the invalid environment variable name {key:?} of {path} in the config");
+                    return false
+                });
+                return !entry.path.is_empty() && seen.insert(entry.path.clone())
+            });
+            entries.sort_by(|a, b| return a.path.cmp(&b.path));
+        }
+        let added = self.paths();
+        self.remembered.retain(|entry| return !added.contains(&entry.path));
+    }
+
+    /// Bring a version 1 config up to date. It kept the added paths as a list, with their metadata in tables keyed by path next to it.
+    /// Configs exported by version 1 already had their entries as tables, and their Flatpak apps as a list
+    fn from_v1(mut document: toml::Table) -> Result<Config, Box<dyn Error>> {
+        let bind_mounts = strings(document.remove("bind_mounts"));
+        let launchers = strings(document.remove("launchers"));
+        let mut metadata: Vec<(&str, toml::Table)> = [("backends", "backend"), ("env", "env"), ("nvidia_extras", "nvidia_extras"), ("nvidia_gpu", "nvidia_gpu"), ("command_prefix", "command_prefix")]
+            .into_iter()
+            .map(|(table, field)| {
+                return match document.remove(table) {
+                    Some(toml::Value::Table(table)) => (field, table),
+                    _ => (field, toml::Table::new()),
+                }
+            })
+            .collect();
+
+        // The metadata is looked up by the path as it was written, it's only trimmed afterwards
+        let entry_of = |path: String, metadata: &mut [(&str, toml::Table)]| -> Result<Entry, toml::de::Error> {
+            let mut fields = toml::Table::new();
+            for (field, table) in metadata {
+                if let Some(value) = table.remove(&path) {
+                    fields.insert((*field).to_string(), value);
+                }
+            }
+            fields.insert("bind_mount".to_string(), bind_mounts.contains(&path).into());
+            fields.insert("launcher".to_string(), launchers.contains(&path).into());
+            fields.insert("path".to_string(), path.into());
+            return toml::Value::Table(fields).try_into()
+        };
+        let mut config = Config::default();
+        if let Some(toml::Value::Array(entries)) = document.remove("entries") {
+            for entry in entries {
+                config.entries.push(match entry {
+                    toml::Value::String(path) => entry_of(path, &mut metadata)?,
+                    exported => exported.try_into()?,
+                });
+            }
+        }
+        // What's left was customized for paths that aren't added
+        let customized: BTreeSet<String> = metadata.iter().flat_map(|(_, table)| return table.keys().cloned()).collect();
+        for path in customized {
+            config.remembered.push(entry_of(path, &mut metadata)?);
+        }
+
+        match document.remove("flatpaks") {
+            // What was changed for each app, the oldest versions only kept the variables that were set (those were always unset again)
+            Some(toml::Value::Table(flatpaks)) => {
+                for (app_id, offload) in flatpaks {
+                    let offload = match offload {
+                        toml::Value::Array(keys) => FlatpakOffload { backend: OffloadBackend::Nvidia, changed: flatpak::Override { keys: strings(Some(toml::Value::Array(keys))).into_iter().collect(), previous: Vec::new() } },
+                        offload => offload.try_into()?,
+                    };
+                    config.flatpaks.insert(app_id, offload);
+                }
+            },
+            // Exported ones were a list of the apps with their backend
+            Some(toml::Value::Array(flatpaks)) => {
+                for flatpak in flatpaks {
+                    let toml::Value::Table(mut flatpak) = flatpak else { continue };
+                    let Some(toml::Value::String(app_id)) = flatpak.remove("app_id") else { continue };
+                    config.flatpaks.insert(app_id, toml::Value::Table(flatpak).try_into()?);
+                }
+            },
+            _ => {},
+        }
+
+        document.remove("version");
+        config.unknown = document;
+        return Ok(config)
+    }
+}
+
+/// Where the config is kept
+pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+    return Ok(xdg::BaseDirectories::with_prefix("NvidiaManager")?.place_data_file("config/config.toml")?)
+}
+
+
+/// Held while the config is updated, with the config as it was last read or written.
+/// Reverting looks up what was recorded about every executable of a folder and would parse it again each time otherwise
+static CACHE: Mutex<Option<Cached>> = Mutex::new(None);
+
+/// A config as it was read, with what its file looked like then so a change by another Nvidia Manager (like the CLI) is noticed
+struct Cached {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    config: Config,
+}
+
+/// When a file was last modified and how big it is, `None` if it's not there
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    return Some((metadata.modified().ok()?, metadata.len()))
+}
+
+
+/// Look up what's recorded about a wrapped executable in the config, only reading it again once its file changed
+pub fn wrapped(original: &Path) -> Option<Wrapped> {
+    let path = path().ok()?;
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    let stamp = stamp(&path);
+    if cache.as_ref().is_none_or(|cached| return cached.path != path || cached.stamp != stamp) {
+        *cache = Some(Cached { config: Config::read(&path), path, stamp });
+    }
+    return cache.as_ref()?.config.wrapped(original.to_str()?).cloned()
+}
+
+
+/// Change what's recorded about a wrapped executable in the config
+pub fn record_wrapped(original: &Path, change: impl FnOnce(&mut Wrapped)) -> Result<(), Box<dyn Error>> {
+    let original = original.to_str().ok_or_else(|| return format!("{} is not valid UTF-8", original.display()))?;
+    return Config::update(&path()?, |config| config.record_wrapped(original, change))
+}
+
+
+/// The strings of a list, in order
+fn strings(list: Option<toml::Value>) -> Vec<String> {
+    let Some(toml::Value::Array(list)) = list else { return Vec::new() };
+    return list.into_iter().filter_map(|item| return item.as_str().map(ToString::to_string)).collect()
+}
+
+fn nvidia() -> OffloadBackend {
+    return OffloadBackend::Nvidia
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // Serde hands it over by reference
+fn is_nvidia(backend: &OffloadBackend) -> bool {
+    return *backend == OffloadBackend::Nvidia
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(value: &bool) -> bool {
+    return !value
+}
+
+/// Backends are stored by name, one this version doesn't know is NVIDIA
+mod backend_name {
+    use super::{warn, Deserialize, Deserializer, OffloadBackend, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(backend: &OffloadBackend, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(backend.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffloadBackend, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        return Ok(OffloadBackend::from_name(&name).unwrap_or_else(|| {
+            warn!("Unknown offload backend {name:?} in the config, using NVIDIA");
+            return OffloadBackend::Nvidia
+        }))
+    }
+}
+
+/// The NVIDIA extras are stored by name, the ones this version doesn't know are left out
+pub(crate) mod nvidia_extras {
+    use super::{Deserialize, Deserializer, NvidiaExtra, Serializer};
+
+    pub fn serialize<S: Serializer>(extras: &[NvidiaExtra], serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.collect_seq(extras.iter().map(|extra| return extra.name()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<NvidiaExtra>, D::Error> {
+        return Ok(Vec::<String>::deserialize(deserializer)?.iter().filter_map(|name| return NvidiaExtra::from_name(name)).collect())
+    }
+}
+
+/// Environment variables are stored as a table, in the order they're exported
+pub(crate) mod env_vars {
+    use std::fmt;
+
+    use serde::de::{MapAccess, Visitor};
+
+    use super::{Deserializer, Serializer, WrapperEnv};
+
+    pub fn serialize<S: Serializer>(vars: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.collect_map(vars.iter().map(|(key, value)| return (key, value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+        struct VarsVisitor;
+
+        impl<'de> Visitor<'de> for VarsVisitor {
+            type Value = Vec<(String, String)>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                return formatter.write_str("a table of environment variables")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut vars = Vec::new();
+                while let Some(var) = map.next_entry()? {
+                    vars.push(var);
+                }
+                return Ok(vars)
+            }
+        }
+
+        return deserializer.deserialize_map(VarsVisitor)
+    }
+
+    /// Whether they're the default offload variables, which aren't worth writing down
+    pub fn is_default(vars: &[(String, String)]) -> bool {
+        return vars == WrapperEnv::default().vars.as_slice()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder for a test to work in
+    fn test_folder(name: &str) -> std::path::PathBuf {
+        let folder = std::env::temp_dir().join(format!("nvidia_manager_config_test_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        return folder
+    }
+
+    #[test]
+    fn version_1_configs_are_brought_up_to_date() {
+        let folder = test_folder("from_v1");
+        let config_path = folder.join("config.toml");
+        fs::write(&config_path, r#"version = 1
+entries = ["/games/a", "/games/b", "/games/c"]
+bind_mounts = ["/games/b"]
+launchers = ["/games/c"]
+
+[backends]
+"/games/a" = "dri_prime"
+"/games/removed" = "custom"
+
+[env]
+"/games/a" = { DXVK_HUD = "fps", __NV_PRIME_RENDER_OFFLOAD = "1" }
+
+[nvidia_extras]
+"/games/a" = ["video_decode", "from_the_future"]
+
+[command_prefix]
+"/games/a" = "mangohud"
+"#).unwrap();
+
+        let mut config = Config::load(&config_path).unwrap();
+        let a = config.entry("/games/a").unwrap();
+        assert_eq!(a.backend, OffloadBackend::DriPrime);
+        assert_eq!(a.env.vars, [("DXVK_HUD".to_string(), "fps".to_string()), ("__NV_PRIME_RENDER_OFFLOAD".to_string(), "1".to_string())]);
+        assert_eq!(a.env.extras, [NvidiaExtra::VideoDecode]);
+        assert_eq!(a.env.command_prefix.as_deref(), Some("mangohud"));
+        assert!(config.entry("/games/b").unwrap().bind_mount && config.entry("/games/c").unwrap().launcher);
+        // Customizations of paths that were removed are still there for when they're added again
+        assert!(config.entry("/games/removed").is_none());
+        assert_eq!(config.backend_of("/games/removed"), OffloadBackend::Custom);
+
+        config.save(&config_path).unwrap();
+        let saved = Config::load(&config_path).unwrap();
+        assert_eq!(saved.version, CONFIG_VERSION);
+        assert_eq!(saved.entries, config.entries);
+        assert_eq!(saved.backend_of("/games/removed"), OffloadBackend::Custom);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn removed_entries_keep_their_customizations() {
+        let mut config = Config::default();
+        config.add("/games/a").backend = OffloadBackend::DriPrime;
+        config.add("/games/b");
+        config.customize("/games/a").env.set("DXVK_HUD", "fps");
+
+        assert_eq!(config.remove("/games/a").map(|entry| return entry.backend), Some(OffloadBackend::DriPrime));
+        assert!(config.remove("/games/b").is_some() && config.remove("/games/b").is_none());
+        assert_eq!(config.paths(), HashSet::new());
+        assert_eq!(config.remembered.len(), 1);
+
+        let added = config.add("/games/a");
+        assert_eq!(added.backend, OffloadBackend::DriPrime);
+        assert!(added.env.vars.contains(&("DXVK_HUD".to_string(), "fps".to_string())));
+        assert!(config.remembered.is_empty());
+    }
+
+    #[test]
+    fn records_made_before_adding_move_onto_the_entry() {
+        let mut config = Config::default();
+        config.add("/games/b");
+        // Wrapping a folder records its executables before the folder is added
+        config.record_wrapped("/games/a/game", |wrapped| wrapped.sha256 = Some("1".to_string()));
+        config.record_wrapped("/games/b", |wrapped| wrapped.sha256 = Some("2".to_string()));
+        assert_eq!(config.remembered.len(), 1);
+        assert_eq!(config.entry("/games/b").map(|entry| return entry.wrapped.len()), Some(1));
+
+        config.add("/games/a");
+        assert!(config.remembered.is_empty());
+        assert_eq!(config.entry("/games/a").and_then(|entry| return entry.wrapped.get("/games/a/game")), Some(&Wrapped { sha256: Some("1".to_string()), ..Wrapped::default() }));
+        assert!(config.exported().entries.iter().all(|entry| return entry.wrapped.is_empty()));
+
+        // Reverting forgets them again
+        config.record_wrapped("/games/a/game", |wrapped| wrapped.sha256 = None);
+        assert_eq!(config.wrapped("/games/a/game"), None);
+    }
+}
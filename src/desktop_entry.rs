@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use crate::error::WrapError;
+use crate::internals::{backup_path, launch_prefix, temp_sibling, LaunchHelper, WrapperEnv};
+
+/// Added to the copy overriding a desktop entry, with the path of the entry it overrides, so we know it's ours
+const ORIGINAL_KEY: &str = "X-NvidiaManager-Original";
+
+/// Characters that make an argument of an `Exec=` line need quoting
+const RESERVED_CHARACTERS: &str = " \t\n\"'\\><~|&;$*?#()`";
+
+/// Check if a path is a desktop entry (a `.desktop` launcher)
+pub fn is_desktop_entry(path: &Path) -> bool {
+    return path.extension().is_some_and(|ext| return ext == "desktop")
+}
+
+/// Where the copy overriding a desktop entry we can't change goes. The user's applications folder comes before the system ones,
+/// so a copy with the same name there takes its place in the menus
+fn override_path(path: &Path) -> Option<PathBuf> {
+    let applications = xdg::BaseDirectories::new().ok()?.get_data_home().join("applications");
+    if path.parent() == Some(applications.as_path()) { return None } // That's the user's own, it gets changed in place
+    return Some(applications.join(path.file_name()?))
+}
+
+/// Where the offloaded version of a desktop entry is, if it's offloaded: the entry itself when it was changed in place, or the copy overriding it
+fn offloaded_path(path: &Path) -> Option<PathBuf> {
+    if backup_path(path).exists() { return Some(path.to_path_buf()) }
+    let override_path = override_path(path)?;
+    let marker = format!("{ORIGINAL_KEY}={}", path.display());
+    return fs::read_to_string(&override_path).ok()?
+        .lines()
+        .any(|line| return line.trim() == marker)
+        .then_some(override_path)
+}
+
+/// Check if a desktop entry launches on the GPU we set it up for
+pub fn is_desktop_entry_offloaded(path: &Path) -> bool {
+    return offloaded_path(path).is_some()
+}
+
+/// Make a desktop entry launch with the offload environment by prefixing its `Exec=` lines, leaving the executable alone.
+/// Entries we can write to are changed in place with a backup next to them, the others (like the ones in `/usr/share/applications`) get overridden by a copy in the user's applications folder
+pub fn offload_desktop_entry(path: &Path, env: &WrapperEnv, helpers: &[LaunchHelper], dry_run: bool) -> Result<(), WrapError> {
+    if is_desktop_entry_offloaded(path) {
+        return Err(WrapError::AlreadyWrapped(path.to_path_buf()));
+    }
+    let content = fs::read_to_string(path)?;
+
+    let in_place = fs::OpenOptions::new().write(true).open(path).is_ok();
+    let destination = if in_place { path.to_path_buf() } else { override_path(path).ok_or_else(|| return WrapError::NotWritable(path.to_path_buf()))? };
+    if !in_place && destination.exists() {
+        return Err(WrapError::AlreadyExists(destination)); // The user's own override, which isn't ours to replace
+    }
+    let rewritten = rewrite_exec_lines(&content, &exec_prefix(env, helpers), (!in_place).then_some(path));
+
+    if dry_run {
+        if in_place {
+            info!("[dry run] Would copy {} to {}", path.display(), backup_path(path).display());
+        }
+        info!("[dry run] Would write {}:\n{rewritten}", destination.display());
+        return Ok(())
+    }
+
+    if in_place {
+        fs::copy(path, backup_path(path))?; // fs::copy keeps the permissions
+    } else if let Some(applications) = destination.parent() {
+        fs::create_dir_all(applications)?;
+    }
+    // Renamed into place, so a launcher is never half written
+    let temp_path = temp_sibling(&destination);
+    if let Err(e) = fs::write(&temp_path, rewritten).and_then(|()| return fs::rename(&temp_path, &destination)) {
+        let _ = fs::remove_file(&temp_path);
+        if in_place {
+            let _ = fs::remove_file(backup_path(path));
+        }
+        return Err(e.into());
+    }
+
+    info!("Desktop entry {} now launches on the NVIDIA GPU", path.display());
+    return Ok(())
+}
+
+/// Undo `offload_desktop_entry`, restoring the backup or removing the overriding copy
+pub fn restore_desktop_entry(path: &Path, dry_run: bool) -> Result<(), WrapError> {
+    let offloaded = offloaded_path(path).ok_or_else(|| return WrapError::BackupMissing(path.to_path_buf()))?;
+    let in_place = offloaded == path;
+
+    if dry_run {
+        if in_place {
+            info!("[dry run] Would move {} back to {}", backup_path(path).display(), path.display());
+        } else {
+            info!("[dry run] Would remove {}", offloaded.display());
+        }
+        return Ok(())
+    }
+
+    if in_place {
+        fs::rename(backup_path(path), path)?;
+    } else {
+        fs::remove_file(&offloaded)?;
+    }
+    info!("Restored the desktop entry {}", path.display());
+    return Ok(())
+}
+
+/// The command put in front of every `Exec=` line, like `env __NV_PRIME_RENDER_OFFLOAD=1 ... mangohud `
+fn exec_prefix(env: &WrapperEnv, helpers: &[LaunchHelper]) -> String {
+    let assignments: Vec<String> = env.vars.iter().map(|(key, value)| return exec_quote(&format!("{key}={value}"))).collect();
+    let prefix: String = env.prefix_words().into_iter().map(|word| return format!("{} ", exec_quote(word))).collect();
+    return format!("env {} {}{prefix}", assignments.join(" "), launch_prefix(helpers))
+}
+
+/// Quote an argument of an `Exec=` line the way the desktop entry spec wants it, also escaping it for the string value it's in
+fn exec_quote(argument: &str) -> String {
+    let argument = argument.replace('%', "%%"); // Field codes like %f start with a percent sign
+    if !argument.is_empty() && !argument.chars().any(|c| return RESERVED_CHARACTERS.contains(c)) {
+        return argument
+    }
+    let mut quoted = String::from("\"");
+    for c in argument.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    return quoted.replace('\\', "\\\\")
+}
+
+/// Put the prefix in front of every `Exec=` line (the main one and those of the actions), marking the copy with the entry it overrides if it's one
+fn rewrite_exec_lines(content: &str, prefix: &str, overrides: Option<&Path>) -> String {
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        match line.split_once('=') {
+            Some((key, command)) if key.trim() == "Exec" => lines.push(format!("Exec={prefix}{}", command.trim_start())),
+            _ => lines.push(line.to_string()),
+        }
+        if let (Some(original), "[Desktop Entry]") = (overrides, line.trim()) {
+            lines.push(format!("{ORIGINAL_KEY}={}", original.display()));
+        }
+    }
+    return lines.join("\n") + "\n"
+}
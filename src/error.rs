@@ -0,0 +1,56 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Everything that can go wrong while wrapping, reverting or moving wrapped executables
+#[derive(Debug, Error)]
+pub enum WrapError {
+    #[error("Path {0} does not exist")]
+    PathMissing(PathBuf),
+    #[error("{0} is not writable")]
+    NotWritable(PathBuf),
+    #[error("{0} is already wrapped")]
+    AlreadyWrapped(PathBuf),
+    #[error("{0} is a symlink to {1}, which can't be wrapped")]
+    UnwrappableSymlink(PathBuf, PathBuf),
+    #[error("{0} can't be wrapped, {1}")]
+    OwnFile(PathBuf, &'static str),
+    #[error("{0} already exists")]
+    AlreadyExists(PathBuf),
+    #[error("No backup found for {0}")]
+    BackupMissing(PathBuf),
+    #[error("The backup of {0} isn't the file that was wrapped anymore")]
+    BackupMismatch(PathBuf),
+    #[error("Failed to create the symlink at {0}: {1}")]
+    SymlinkFailed(PathBuf, #[source] io::Error),
+    #[error("{0} is on a filesystem that can't hold symlinks: {1}")]
+    SymlinksUnsupported(PathBuf, #[source] io::Error),
+    #[error("{0} is a mount point of its own, it can't be swapped for a symlink")]
+    MountPoint(PathBuf),
+    #[error("{0} didn't end up wrapped as expected, the changes were rolled back")]
+    UnexpectedState(PathBuf),
+    #[error("Generated wrapper script has a syntax error: {0}")]
+    ScriptSyntax(String),
+    #[error("Wrapper scripts in {0} can't be run: {1}")]
+    ScriptWontRun(PathBuf, String),
+    #[error("Running `{0}` as root failed or was cancelled")]
+    Privileged(String),
+    #[error("Failed to record the wrapped executable in the config: {0}")]
+    Record(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl WrapError {
+    /// An icon to show next to the error, telling the kinds apart at a glance
+    pub fn icon(&self) -> &'static str {
+        return match self {
+            WrapError::PathMissing(_) => "❓",
+            WrapError::Privileged(_) | WrapError::NotWritable(_) | WrapError::UnwrappableSymlink(..) => "🔒",
+            WrapError::AlreadyWrapped(_) | WrapError::AlreadyExists(_) => "ℹ",
+            WrapError::OwnFile(..) | WrapError::MountPoint(_) | WrapError::BackupMissing(_) | WrapError::BackupMismatch(_) => "⚠",
+            WrapError::SymlinkFailed(..) | WrapError::SymlinksUnsupported(..) | WrapError::UnexpectedState(_) | WrapError::ScriptSyntax(_) | WrapError::ScriptWontRun(..) | WrapError::Record(_) | WrapError::Io(_) => "❌",
+        }
+    }
+}
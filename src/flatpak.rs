@@ -0,0 +1,121 @@
+use std::error::Error;
+use std::process::Command;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::internals::{on_path, WrapperEnv};
+
+/// An installed Flatpak app
+#[derive(Clone, Debug)]
+pub struct FlatpakApp {
+    /// The application ID, like `org.mozilla.firefox`
+    pub id: String,
+    pub name: String,
+}
+
+/// Check if Flatpak is installed at all
+pub fn is_available() -> bool {
+    return on_path("flatpak")
+}
+
+/// List the installed Flatpak apps (runtimes left out), sorted by name
+pub fn installed_apps() -> Result<Vec<FlatpakApp>, Box<dyn Error>> {
+    let output = Command::new("flatpak").args(["list", "--app", "--columns=application,name"]).output()?;
+    if !output.status.success() {
+        return Err(format!("flatpak list exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    // One tab separated line per app, the same app installed both system wide and for the user shows up twice
+    let mut apps: Vec<FlatpakApp> = String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|line| {
+            let (id, name) = line.split_once('\t').unwrap_or((line, line));
+            return (!id.trim().is_empty()).then(|| return FlatpakApp { id: id.trim().to_string(), name: name.trim().to_string() })
+        })
+        .collect();
+    apps.sort_by(|a, b| return a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| return a.id.cmp(&b.id)));
+    apps.dedup_by(|a, b| return a.id == b.id);
+    return Ok(apps)
+}
+
+/// What offloading a Flatpak app changed in its user overrides, so exactly that can be undone
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Override {
+    /// The variables that got set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+    /// What those were set to before, for the ones the user had overridden already
+    #[serde(default, with = "crate::config::env_vars", skip_serializing_if = "Vec::is_empty")]
+    pub previous: Vec<(String, String)>,
+}
+
+/// Offload a Flatpak app by adding the environment to its user overrides, it's sandboxed so there's no executable of its own to wrap.
+/// Whatever the user had set those variables to is kept, to be put back by `restore`
+pub fn offload(app_id: &str, env: &WrapperEnv) -> Result<Override, Box<dyn Error>> {
+    let current = environment_overrides(app_id)?;
+    let changed = Override {
+        keys: env.vars.iter().map(|(key, _)| return key.clone()).collect(),
+        previous: current.into_iter().filter(|(key, _)| return env.vars.iter().any(|(other, _)| return other == key)).collect(),
+    };
+    let env_args = env.vars.iter().map(|(key, value)| return format!("--env={key}={value}"));
+    run_override(Command::new("flatpak").args(["override", "--user"]).args(env_args).arg(app_id))?;
+    info!("Flatpak app {app_id} now launches on the NVIDIA GPU");
+    return Ok(changed)
+}
+
+/// Undo `offload`, unsetting the variables it set or putting back what they were before, so any other overrides of the user stay
+pub fn restore(app_id: &str, changed: &Override) -> Result<(), Box<dyn Error>> {
+    let args = changed.keys.iter().map(|key| {
+        return match changed.previous.iter().find(|(other, _)| return other == key) {
+            Some((_, value)) => format!("--env={key}={value}"),
+            None => format!("--unset-env={key}"),
+        }
+    });
+    run_override(Command::new("flatpak").args(["override", "--user"]).args(args).arg(app_id))?;
+    info!("Removed the offload environment from the Flatpak app {app_id}");
+    return Ok(())
+}
+
+/// Get the environment variables set in the user overrides of a Flatpak app
+fn environment_overrides(app_id: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let output = Command::new("flatpak").args(["override", "--user", "--show", app_id]).output()?;
+    if !output.status.success() {
+        return Err(format!("flatpak override exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    return Ok(parse_environment(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Read the `[Environment]` group of an overrides keyfile, which has a `KEY=VALUE` line per variable
+fn parse_environment(keyfile: &str) -> Vec<(String, String)> {
+    let mut in_environment = false;
+    let mut vars = Vec::new();
+    for line in keyfile.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_environment = line == "[Environment]";
+        } else if let (true, Some((key, value))) = (in_environment, line.split_once('=')) {
+            vars.push((key.trim().to_string(), value.to_string()));
+        }
+    }
+    return vars
+}
+
+fn run_override(command: &mut Command) -> Result<(), Box<dyn Error>> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(format!("flatpak override exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    return Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_environment_reads_only_the_environment_group() {
+        let keyfile = "[Context]\nfilesystems=home;\n\n[Environment]\nDXVK_HUD=fps\nMANGOHUD_CONFIG=fps_limit=60\n\n[Session Bus Policy]\norg.freedesktop.Flatpak=talk\n";
+        assert_eq!(parse_environment(keyfile), [("DXVK_HUD".to_string(), "fps".to_string()), ("MANGOHUD_CONFIG".to_string(), "fps_limit=60".to_string())]);
+        assert!(parse_environment("[Context]\nshared=network;\n").is_empty());
+    }
+}
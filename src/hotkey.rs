@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+
+use egui::Context;
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use procfs::process::Process;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+use crate::internals::strip_deleted_suffix;
+
+/// A global shortcut, working even when the window isn't focused
+pub struct Hotkey {
+    // Unregisters the shortcut when dropped
+    _manager: GlobalHotKeyManager,
+    receiver: Receiver<()>,
+}
+
+impl Hotkey {
+    /// Register a shortcut like `ctrl+alt+N`
+    pub fn register(ctx: &Context, shortcut: &str) -> Result<Self, Box<dyn Error>> {
+        let hotkey: HotKey = shortcut.parse()?;
+        let manager = GlobalHotKeyManager::new()?;
+        manager.register(hotkey)?;
+
+        // The events come in on another thread, which has to wake the UI up
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.id == hotkey.id() && event.state == HotKeyState::Pressed {
+                let _ = sender.send(());
+                ctx.request_repaint();
+            }
+        }));
+        return Ok(Hotkey { _manager: manager, receiver })
+    }
+
+    /// Check if the shortcut was pressed since the last check
+    pub fn pressed(&self) -> bool {
+        return self.receiver.try_iter().count() > 0
+    }
+}
+
+/// Get the executable behind the focused window.
+/// Only works for X11 (and XWayland) windows, Wayland doesn't let apps look at other apps' windows
+pub fn focused_executable() -> Result<PathBuf, Box<dyn Error>> {
+    let (connection, screen) = x11rb::connect(None)?;
+    let root = connection.setup().roots[screen].root;
+    let active_window_atom = connection.intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+    let pid_atom = connection.intern_atom(false, b"_NET_WM_PID")?.reply()?.atom;
+
+    let window = connection.get_property(false, root, active_window_atom, AtomEnum::WINDOW, 0, 1)?.reply()?
+        .value32()
+        .and_then(|mut values| return values.next())
+        .filter(|window| return *window != 0)
+        .ok_or("No window is focused")?;
+    let pid = connection.get_property(false, window, pid_atom, AtomEnum::CARDINAL, 0, 1)?.reply()?
+        .value32()
+        .and_then(|mut values| return values.next())
+        .ok_or("The focused window doesn't tell which process it belongs to")?;
+
+    let exe = Process::new(i32::try_from(pid)?)?.exe()?;
+    return Ok(strip_deleted_suffix(&exe))
+}
+
+/// Get the PIDs of every process with a window open, going by the window manager's client list.
+/// Like `focused_executable`, only X11 (and XWayland) windows can be seen
+pub fn window_pids() -> Result<HashSet<i32>, Box<dyn Error>> {
+    let (connection, screen) = x11rb::connect(None)?;
+    let root = connection.setup().roots[screen].root;
+    let client_list_atom = connection.intern_atom(false, b"_NET_CLIENT_LIST")?.reply()?.atom;
+    let pid_atom = connection.intern_atom(false, b"_NET_WM_PID")?.reply()?.atom;
+
+    let windows: Vec<u32> = connection.get_property(false, root, client_list_atom, AtomEnum::WINDOW, 0, u32::MAX)?.reply()?
+        .value32()
+        .ok_or("The window manager doesn't list its windows")?
+        .collect();
+
+    // Send all the requests before waiting on any reply, a round trip per window adds up
+    let cookies = windows.into_iter()
+        .map(|window| return connection.get_property(false, window, pid_atom, AtomEnum::CARDINAL, 0, 1))
+        .collect::<Result<Vec<_>, _>>()?;
+    return Ok(
+        cookies.into_iter()
+            .filter_map(|cookie| return cookie.reply().ok()) // Windows can close in the meantime
+            .filter_map(|reply| return reply.value32().and_then(|mut values| return values.next()))
+            .filter_map(|pid| return i32::try_from(pid).ok())
+            .collect()
+    )
+}
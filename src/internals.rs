@@ -1,239 +1,3152 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt::Write as _;
 use std::fs;
-use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::sync::{Mutex, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
-use procfs::process::all_processes;
+use log::{debug, error, info, warn};
+use procfs::process::{all_processes, Process};
+use procfs::ProcError;
+use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
 
-/// Execute the main logic of the application (Return of true indicates we reverted the changes, return of false indicates we made changes)
-pub fn execute(wrapper_dir: &PathBuf, executable_path: &Path) -> Result<bool, Box<dyn Error>> {
+use crate::config::{self, Wrapped};
+use crate::desktop_entry::{is_desktop_entry, is_desktop_entry_offloaded, offload_desktop_entry, restore_desktop_entry};
+use crate::error::WrapError;
+
+/// What `execute` did, counted per executable for folders
+#[derive(Debug, Default)]
+pub struct ExecuteSummary {
+    /// Whether it reverted (true) or wrapped (false)
+    pub reverted: bool,
+    /// Executables that were wrapped or reverted
+    pub succeeded: usize,
+    /// Executables of a folder that were wrapped or reverted
+    pub changed: Vec<PathBuf>,
+    /// Executables of a folder that were left alone, with why
+    pub skipped: Vec<(PathBuf, &'static str)>,
+    /// Executables of a folder that couldn't be changed, the rest carried on without them
+    pub failed: Vec<(PathBuf, WrapError)>,
+    /// The file that actually got wrapped, when the given path was a symlink to it
+    pub resolved: Option<PathBuf>,
+}
+
+impl ExecuteSummary {
+    /// Describe it for the user, like "Enabled 28, skipped 2"
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} {}", if self.reverted { "Reverted" } else { "Enabled" }, self.succeeded);
+        if !self.skipped.is_empty() {
+            let _ = write!(description, ", skipped {}", self.skipped.len());
+        }
+        if !self.failed.is_empty() {
+            let _ = write!(description, ", failed {} ({})", self.failed.len(), self.failed.iter().map(|(path, _)| return path.display().to_string()).collect::<Vec<_>>().join(", "));
+        }
+        return description
+    }
+}
+
+
+/// How executables get wrapped, the same for every executable of a folder. Mostly up to the settings, see `Settings::wrap_options`
+#[derive(Clone, Debug, Default)]
+pub struct WrapOptions {
+    /// Keep the backups in `wrapper_dir/backups` instead of next to the originals
+    pub central_backups: bool,
+    /// Add a `<name>-igpu` symlink that launches on the integrated GPU
+    pub igpu_link: bool,
+    /// What gets skipped when wrapping a whole folder
+    pub scan: ScanOptions,
+    /// The environment Proton/Wine executables get on top
+    pub proton_env: Vec<(String, String)>,
+    /// What the executables are launched through
+    pub helpers: Vec<LaunchHelper>,
+    /// Only print every change, nothing on disk is touched
+    pub dry_run: bool,
+}
+
+
+/// Execute the main logic of the application, reverting the executable if it's wrapped and wrapping it otherwise.
+/// A folder with anything wrapped in it gets reverted, otherwise every executable in it gets wrapped, carrying on past the ones that fail
+pub fn execute(wrapper_dir: &PathBuf, executable_path: &Path, backend: OffloadBackend, env: &WrapperEnv, options: &WrapOptions) -> Result<ExecuteSummary, WrapError> {
+    let dry_run = options.dry_run;
     // Check if the path exists, if not then return (This shouldn't happen unless the user deleted the file while the application us running, the application verifies the paths on launch and deletes them accordingly)
-    if !executable_path.exists() { return Err(format!("Path {} does not exist", executable_path.display()).into()); }
+    if !executable_path.exists() { return Err(WrapError::PathMissing(executable_path.to_path_buf())); }
+    // Every name below is derived from the path, so however it was entered it has to come out the same
+    // A wrapped executable runs from its backup, which is what shows up as the process, so that stands for the executable
+    let executable_path: &Path = &resolve_backup(&normalize_path(executable_path)?);
+    if let Some(reason) = own_path_reason(executable_path, wrapper_dir) {
+        return Err(WrapError::OwnFile(executable_path.to_path_buf(), reason));
+    }
+
+    // Desktop entries get their launch command changed instead, the executable stays as it is
+    if is_desktop_entry(executable_path) {
+        let reverting = is_desktop_entry_offloaded(executable_path);
+        if reverting {
+            restore_desktop_entry(executable_path, dry_run)?;
+        } else {
+            offload_desktop_entry(executable_path, &backend.env(env), &options.helpers, dry_run)?;
+        }
+        return Ok(ExecuteSummary { reverted: reverting, succeeded: 1, ..ExecuteSummary::default() })
+    }
     
     // Check if the path is a directory, if so apply the logic to all executables in the directory and subdirectories
     if executable_path.is_dir() {
-        let paths = find_executables(executable_path);
-        println!("Found {} executables in {}", paths.len(), executable_path.display());
-        let mut return_result: Result<bool, Box<dyn Error>> = Ok(false);
-        for path in paths {
-            if path == executable_path { continue; } // Skip the directory itself
-            if path == path.with_extension("bak") { continue; } // Skip backup files
-            println!("Processing {}", path.display());
-            return_result = Ok(execute(wrapper_dir, &path)?);
+        // Anything wrapped before the excludes changed must still be reverted, so reverting goes by what's wrapped instead
+        let wrapped = wrapped_executables(executable_path);
+        let reverting = !wrapped.is_empty();
+        let paths = if reverting { wrapped } else { find_executables(executable_path, &options.scan) };
+        info!("Found {} executables in {}", paths.len(), executable_path.display());
+
+        let mut summary = ExecuteSummary { reverted: reverting, ..ExecuteSummary::default() };
+        let folder = executable_path.canonicalize()?;
+        let paths: Vec<PathBuf> = paths.into_iter()
+            .filter(|path| return path != executable_path) // Skip the directory itself
+            .filter(|path| return !has_backup_suffix(path)) // Skip backup files
+            .filter(|path| return fs::symlink_metadata(path).is_ok() && !is_igpu_link(path)) // Skip the integrated GPU symlinks, reverting their executable already removed them
+            .filter(|path| {
+                let Some(reason) = own_path_reason(path, wrapper_dir) else { return true };
+                summary.skipped.push((path.clone(), reason));
+                return false
+            })
+            .collect();
+
+        // Every executable has its own symlink, backup and wrapper script, so they can be done side by side (the config they're recorded in is updated one at a time)
+        let results: Vec<(PathBuf, Result<_, &'static str>)> = paths.into_par_iter()
+            .map(|path| {
+                if !reverting && fs::read_link(&path).is_ok_and(|destination| return destination.starts_with(wrapper_dir)) {
+                    return (path, Err("Wrapped but its backup is gone, repair it instead")); // That's for repairing rather than wrapping again
+                }
+                if !reverting && fs::read_link(&path).is_ok() && !path.canonicalize().is_ok_and(|real_path| return real_path.starts_with(&folder)) {
+                    return (path, Err("A symlink to outside the folder")); // Its target would get wrapped, which reverting the folder can't find again
+                }
+                info!("Processing {}", path.display());
+                let result = execute(wrapper_dir, &path, backend, env, options);
+                return (path, Ok(result))
+            })
+            .collect();
+        for (path, result) in results {
+            match result {
+                Err(reason) => summary.skipped.push((path, reason)),
+                Ok(Ok(_)) => {
+                    summary.succeeded += 1;
+                    summary.changed.push(path);
+                },
+                Ok(Err(e)) => {
+                    warn!("Skipping {}: {e}", path.display());
+                    summary.failed.push((path, e));
+                }
+            }
         }
-        return return_result;
+        return Ok(summary);
     }
     
-    // Canonicalize the path to get the full path
-    // let target_path = executable_path.canonicalize()?; // BREAKS EVERYTHING FOR SOME REASON
-    let target_path = executable_path;
-    // Generate a unique name for the wrapper script based on the target path
-    let wrapper_name = generate_wrapper_name(original_path(target_path).as_path());
-
-    // Check if the backup exists, if so revert the changes
-    if backup_path(target_path).exists() {
-        return match revert_changes(target_path, wrapper_dir, &wrapper_name) {
+    // Check if the backup exists, if so revert the changes.
+    // This has to go by the path as given: a wrapped executable is a symlink to its wrapper script, canonicalizing it would land in the wrapper folder
+    if backup_path(executable_path).exists() {
+        let wrapper_name = generate_wrapper_name(executable_path);
+        return match revert_changes(executable_path, wrapper_dir, &wrapper_name, dry_run, true) {
             Err(e) => {
-                println!("Failed to revert changes for {}: {}", target_path.display(), e);
+                error!("Failed to revert changes for {}: {}", executable_path.display(), e);
                 Err(e)
             },
             _ => {
-                Ok(true) // Return true as we reverted the changes
+                Ok(ExecuteSummary { reverted: true, succeeded: 1, ..ExecuteSummary::default() }) // We reverted the changes
             }
         }
     }
 
+    // Not wrapped, so a symlink is someone else's (alternatives, versioned binaries), replacing it would break whatever manages it.
+    // The real file gets wrapped instead, as long as it's one we'd offer to wrap in the first place
+    let is_foreign_symlink = fs::read_link(executable_path).is_ok_and(|destination| return !destination.starts_with(wrapper_dir));
+    let resolved = if is_foreign_symlink { Some(executable_path.canonicalize()?) } else { None };
+    if let Some(real_path) = &resolved {
+        if system_path_match(real_path, &options.scan.system_paths).is_some() || !has_write_access(real_path) {
+            return Err(WrapError::UnwrappableSymlink(executable_path.to_path_buf(), real_path.clone()));
+        }
+        info!("{} is a symlink to {}, wrapping that instead", executable_path.display(), real_path.display());
+    }
+    let target_path = resolved.as_deref().unwrap_or(executable_path);
+    if let Some(reason) = resolved.as_deref().and_then(|real_path| return own_path_reason(real_path, wrapper_dir)) {
+        return Err(WrapError::OwnFile(executable_path.to_path_buf(), reason));
+    }
+    // Generate a unique name for the wrapper script based on the target path
+    let wrapper_name = generate_wrapper_name(target_path);
+
+    // 32-bit executables need the 32-bit NVIDIA driver, without it the offload silently does nothing
+    if elf_class(target_path) == Some(ElfClass::Elf32) && !has_32bit_nvidia_libs() {
+        warn!("{} is a 32-bit executable but the 32-bit NVIDIA libraries aren't installed, it won't be able to use the NVIDIA GPU", target_path.display());
+    }
+
     // Create the wrapper script (Enables NVIDIA GPU)
-    return match create_wrapper(target_path, wrapper_dir, &wrapper_name) {
+    return match create_wrapper(target_path, wrapper_dir, &wrapper_name, backend, env, options) {
         Err(e) => {
-            println!("Failed to create wrapper for {}: {}", target_path.display(), e);
+            error!("Failed to create wrapper for {}: {}", target_path.display(), e);
             Err(e)
         },
         _ => {
-            Ok(false) // Return false as we made changes
+            Ok(ExecuteSummary { reverted: false, succeeded: 1, resolved, ..ExecuteSummary::default() }) // We made changes
         }
     }
 }
 
 
+/// Why a path belongs to Nvidia Manager itself, which wrapping would break: its own executable, anything in the wrapper folder or in its data folder (the config and the logs)
+fn own_path_reason(path: &Path, wrapper_dir: &Path) -> Option<&'static str> {
+    // A wrapped executable leads to its wrapper script, which is never the running executable
+    let real_path = path.canonicalize().ok();
+    if real_path.is_some() && std::env::current_exe().and_then(|exe| return exe.canonicalize()).ok() == real_path {
+        return Some("it's Nvidia Manager itself")
+    }
+    // The path as given, a wrapped executable's symlink leads into the wrapper folder too
+    if path.starts_with(wrapper_dir) || path.starts_with(ELEVATED_WRAPPER_DIR) || wrapper_dir.canonicalize().is_ok_and(|wrapper_dir| return path.starts_with(wrapper_dir)) {
+        return Some("it's one of the wrapper scripts or backups of Nvidia Manager")
+    }
+    if xdg::BaseDirectories::with_prefix("NvidiaManager").is_ok_and(|xdg_dirs| return path.starts_with(xdg_dirs.get_data_home())) {
+        return Some("it's in the data folder of Nvidia Manager")
+    }
+    return None
+}
+
+
+/// Revert a wrapped executable, or every wrapped executable inside a directory
+pub fn revert(wrapper_dir: &Path, executable_path: &Path) -> Result<(), WrapError> {
+    if !executable_path.exists() { return Err(WrapError::PathMissing(executable_path.to_path_buf())); }
+    let executable_path: &Path = &normalize_path(executable_path)?;
+    if is_desktop_entry(executable_path) { return restore_desktop_entry(executable_path, false) }
+
+    for path in wrapped_executables(executable_path) {
+        let wrapper_name = generate_wrapper_name(resolve_backup(&path).as_path());
+        revert_changes(&path, wrapper_dir, &wrapper_name, false, true)?;
+    }
+    return Ok(())
+}
+
+
+/// Revert every given entry, unmounting the bind mounted ones and removing the launchers of the launcher ones, without stopping at the first failure
+pub fn revert_all(wrapper_dir: &Path, entries: impl IntoIterator<Item = String>, bind_mounted: &HashSet<String>, launchers: &HashSet<String>) -> Vec<(String, Result<(), WrapError>)> {
+    return entries.into_iter()
+        .map(|entry| {
+            let result = if bind_mounted.contains(&entry) {
+                unmount_wrapper(Path::new(&entry))
+            } else if launchers.contains(&entry) {
+                remove_launcher(Path::new(&entry))
+            } else {
+                revert(wrapper_dir, Path::new(&entry))
+            };
+            return (entry, result)
+        })
+        .collect()
+}
+
+
+/// Revert like `revert`, but restore backups even if they aren't the file that was wrapped anymore
+pub fn revert_unverified(wrapper_dir: &Path, executable_path: &Path) -> Result<(), WrapError> {
+    if !executable_path.exists() { return Err(WrapError::PathMissing(executable_path.to_path_buf())); }
+    let executable_path: &Path = &normalize_path(executable_path)?;
+    if is_desktop_entry(executable_path) { return restore_desktop_entry(executable_path, false) }
+
+    for path in wrapped_executables(executable_path) {
+        let wrapper_name = generate_wrapper_name(resolve_backup(&path).as_path());
+        revert_changes(&path, wrapper_dir, &wrapper_name, false, false)?;
+    }
+    return Ok(())
+}
+
+
+/// Make a path absolute without resolving the final component.
+/// Only the parent is canonicalized: a wrapped executable is a symlink to its wrapper script, canonicalizing all of it would land in the wrapper folder
+pub fn normalize_path(path: &Path) -> io::Result<PathBuf> {
+    return match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            Ok(parent.canonicalize()?.join(file_name))
+        },
+        _ => path.canonicalize(), // "/" or a path ending in ".."
+    }
+}
+
+
+/// How much of the path is kept readable in a wrapper name, the end of it since that tells the most. File names can't be longer than 255 bytes
+const WRAPPER_NAME_PATH_LENGTH: usize = 200;
+
+
 /// Generate a unique name for the wrapper script by transforming the target path.
+/// The path stays readable with its non-alphanumeric characters replaced by underscores, which makes `/opt/a-b`, `/opt/a_b` and `/opt/a/b` the same,
+/// so a hash of the whole path tells them apart. It has to stay the same across versions since reverting recomputes it, hence `path_hash`
 pub fn generate_wrapper_name(target_path: &Path) -> String {
-    let path_str = target_path.to_str().unwrap();
+    let readable = legacy_wrapper_name(target_path);
+    let readable = &readable["wrapper_".len()..];
+    let readable = &readable[readable.len().saturating_sub(WRAPPER_NAME_PATH_LENGTH)..]; // Only ASCII is left, so any byte is a char boundary
+    return format!("wrapper_{readable}_{}", &path_hash(target_path)[..12])
+}
+
+
+/// The name wrapper scripts had before the hash was added, which is how the ones written back then are still found
+fn legacy_wrapper_name(target_path: &Path) -> String {
     // Replace all non-alphanumeric characters with underscores to avoid conflicts
-    return format!("wrapper_{}", Regex::new(r"[^a-zA-Z0-9]").unwrap().replace_all(path_str, "_"))
+    return format!("wrapper_{}", Regex::new(r"[^a-zA-Z0-9]").unwrap().replace_all(&target_path.to_string_lossy(), "_"))
+}
+
+
+/// Move the executables wrapped before the names had a hash in them over to wrapper scripts with the new names, pointing their symlinks at them.
+/// Executables whose legacy names collided shared one script that only launched whichever was wrapped last, so rather than copying it
+/// every executable gets its script written anew for its own backup, backend and environment (`proton_env` for the Proton ones, like when wrapping).
+/// The legacy ones are only removed once nothing uses them anymore
+pub fn migrate_wrapper_names(wrapper_dir: &Path, executables: &[(PathBuf, OffloadBackend, WrapperEnv)], proton_env: &[(String, String)]) {
+    let mut migrated = Vec::new();
+    for (executable_path, backend, env) in executables {
+        let target_path = resolve_backup(executable_path);
+        let (legacy_name, name) = (legacy_wrapper_name(&target_path), generate_wrapper_name(&target_path));
+        let backup = backup_path(&target_path);
+        let scripts = [
+            (target_path.clone(), wrapper_dir.join(&legacy_name), wrapper_dir.join(&name)),
+            (igpu_link_path(&target_path), wrapper_dir.join(format!("{legacy_name}{IGPU_SUFFIX}")), wrapper_dir.join(format!("{name}{IGPU_SUFFIX}"))),
+        ];
+        for (link, legacy_path, path) in scripts {
+            if !legacy_path.exists() { continue; }
+            let destination = fs::read_link(&link).ok();
+            let points_at_legacy = destination.as_ref() == Some(&legacy_path);
+            // Switched off by the master switch it points at the backup, but the script is still needed for switching it back on
+            let masked = link == target_path && destination == Some(backup_path(&target_path));
+            if !points_at_legacy && !masked { continue; }
+
+            let written = if path.exists() {
+                Ok(())
+            } else if link == target_path {
+                // The original was an AppImage if its backup is one (a script named like one gets the benefit of the doubt)
+                let extra_env = if is_proton_path(&target_path) { proton_env } else { &[] };
+                fs::metadata(&backup).map_err(WrapError::from).and_then(|metadata| {
+                    return write_wrapper_script(&path, &backup, metadata.mode(), &backend.env(env), extra_env, &[], (is_appimage(&backup) || is_appimage(&target_path)).then_some(target_path.as_path()))
+                })
+            } else {
+                write_igpu_script(&path, &backup)
+            };
+            match written.and_then(|()| return if points_at_legacy { replace_symlink(&link, &path) } else { Ok(()) }) {
+                Ok(()) => {
+                    info!("Moved {} over to the wrapper script {}", link.display(), path.display());
+                    migrated.push(legacy_path);
+                },
+                Err(e) => error!("Failed to move {} over to the wrapper script {}: {e}", link.display(), path.display()),
+            }
+        }
+    }
+
+    for legacy_path in migrated {
+        let still_used = executables.iter().any(|(executable_path, _, _)| {
+            let target_path = resolve_backup(executable_path);
+            return [igpu_link_path(&target_path), target_path].iter().any(|link| return fs::read_link(link).is_ok_and(|destination| return destination == legacy_path))
+        });
+        if !still_used {
+            let _ = fs::remove_file(&legacy_path);
+        }
+    }
 }
 
 
-/// Create a wrapper script to force the use of the NVIDIA GPU
-pub fn create_wrapper(target_path: &Path, wrapper_dir: &Path, wrapper_name: &str) -> Result<(), Box<dyn Error>> {
-    // Create the wrapper script
+/// Create a wrapper script that exports the environment of the offload backend (`env` for a custom one), with the backup and integrated GPU symlink the options ask for.
+/// Proton/Wine executables get the options' Proton environment on top
+pub fn create_wrapper(target_path: &Path, wrapper_dir: &Path, wrapper_name: &str, backend: OffloadBackend, env: &WrapperEnv, options: &WrapOptions) -> Result<(), WrapError> {
+    let WrapOptions { central_backups, igpu_link, ref helpers, dry_run, .. } = *options;
+    let extra_env = if is_proton_path(target_path) { options.proton_env.as_slice() } else { &[] };
+    // A backup already being there means it's wrapped, wrapping it again would overwrite the original
+    if backup_path(target_path).exists() {
+        return Err(WrapError::AlreadyWrapped(target_path.to_path_buf()));
+    }
+    // So does a symlink into the wrapper folder even without its backup, wrapping that would make a wrapper of a wrapper
+    if fs::read_link(target_path).is_ok_and(|destination| return destination.starts_with(wrapper_dir)) {
+        return Err(WrapError::AlreadyWrapped(target_path.to_path_buf()));
+    }
+
+    // Pick where the original goes
+    let backups_dir = wrapper_dir.join("backups");
+    let backup_path = if central_backups {
+        backups_dir.join(path_hash(target_path))
+    } else {
+        backup_path(target_path)
+    };
     let wrapper_path = wrapper_dir.join(wrapper_name);
-    let mut wrapper_file = fs::File::create(&wrapper_path)?;
+
+    if dry_run {
+        let script = wrapper_script(&backup_path, &backend.env(env), extra_env, helpers, is_appimage(target_path).then_some(target_path), wrapper_shell());
+        info!("[dry run] Would write the wrapper script {}:\n{script}", wrapper_path.display());
+        info!("[dry run] Would move {} to {}", target_path.display(), backup_path.display());
+        info!("[dry run] Would symlink {} to {}", target_path.display(), wrapper_path.display());
+        if igpu_link {
+            info!("[dry run] Would symlink {} to {}{IGPU_SUFFIX}", igpu_link_path(target_path).display(), wrapper_path.display());
+        }
+        return Ok(())
+    }
+
+    // Find out now if the symlink can take the original's place, failing halfway would leave it half wrapped
+    check_symlink_target(target_path, &wrapper_path)?;
+
+    if central_backups {
+        fs::create_dir_all(&backups_dir)?;
+    }
+
+    // Remember what the original looked like, so reverting can tell if the backup got swapped out in between and put the permissions back as they were
+    let original_hash = file_sha256(target_path)?;
+    let original_metadata = fs::metadata(target_path)?;
 
     // Write the wrapper script
-    write!(
-        wrapper_file,
-        r#"#!/bin/bash
-export __NV_PRIME_RENDER_OFFLOAD=1
-export __GLX_VENDOR_LIBRARY_NAME=nvidia
-export __VK_LAYER_NV_optimus=NVIDIA_only
-exec "{}.bak" "$@"
-"#,
-        target_path.display()
-    )?;
+    write_wrapper_script(&wrapper_path, &backup_path, original_metadata.mode(), &backend.env(env), extra_env, helpers, is_appimage(target_path).then_some(target_path))?;
 
-    // Make the wrapper script executable
-    Command::new("chmod")
-        .arg("+x")
-        .arg(&wrapper_path)
-        .status()?;
+    // Create a backup of the original while leaving the original in place, recording where it went if it's not next to the original.
+    // Nothing has replaced the original until the symlink is renamed over it, so getting killed anywhere in between can't lose it
+    link_or_copy(target_path, &backup_path).map_err(|e| {
+        let _ = fs::remove_file(&wrapper_path);
+        return match e {
+            WrapError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied => WrapError::NotWritable(target_path.to_path_buf()),
+            e => e,
+        }
+    })?;
+    if central_backups {
+        if let Err(e) = record_wrapped(target_path, |wrapped| wrapped.backup = Some(backup_path.clone())) {
+            rollback_wrapper(target_path, &wrapper_path, &backup_path);
+            return Err(e);
+        }
+    }
+    // Copying to another filesystem drops setuid/setgid bits and ownership, the backup is what runs while wrapped
+    let original_permissions = OriginalPermissions::of(&original_metadata);
+    original_permissions.apply(&backup_path);
 
-    // Create a backup of the original
-    let backup_path = backup_path(target_path);
-    fs::rename(target_path, backup_path)?;
+    // Swap the original for a symbolic link to the wrapper script in one rename, and make sure everything really ended up where it should (some filesystems fail silently)
+    let linked = replace_symlink(target_path, &wrapper_path)
+        .and_then(|()| return verify_wrapped(target_path, &wrapper_path, &backup_path));
+    if let Err(e) = linked {
+        rollback_wrapper(target_path, &wrapper_path, &backup_path);
+        return Err(e);
+    }
+    if let Err(e) = record_wrapped(target_path, |wrapped| (wrapped.sha256, wrapped.permissions) = (Some(original_hash), Some(original_permissions))) {
+        error!("Failed to record the hash and permissions of {}, reverting can't verify its backup and keeps whatever permissions that has: {e}", target_path.display());
+    }
 
-    // Create a symbolic link to the wrapper script
-    std::os::unix::fs::symlink(&wrapper_path, target_path)?;
+    // The executable is wrapped by now, so a failing integrated GPU symlink isn't worth undoing that
+    if igpu_link {
+        if let Err(e) = create_igpu_link(target_path, wrapper_dir, wrapper_name, &backup_path) {
+            error!("Failed to create the integrated GPU symlink for {}: {}", target_path.display(), e);
+        }
+    }
 
-    println!("Application {} is now configured to use the NVIDIA GPU by default", target_path.display());
+    info!("Application {} is now configured to use the NVIDIA GPU by default", target_path.display());
     return Ok(())
 }
 
 
-/// Revert the changes made to the target executable
-fn revert_changes(target: &Path, wrapper_dir: &Path, wrapper_name: &str) -> Result<(), Box<dyn Error>> {
-    // Get the path to the backup
-    let target_path = original_path(target); let target_path = target_path.as_path();
-    let backup_path = backup_path(target);
+/// Write a wrapper script that runs `exec_path` with the given environment, executable by whoever could execute `exec_path` (`exec_mode` is its mode)
+fn write_wrapper_script(wrapper_path: &Path, exec_path: &Path, exec_mode: u32, env: &WrapperEnv, extra_env: &[(String, String)], helpers: &[LaunchHelper], appimage: Option<&Path>) -> Result<(), WrapError> {
+    let shell = wrapper_shell();
+    let script = wrapper_script(exec_path, env, extra_env, helpers, appimage, shell);
 
-    // Check if the backup exists
-    if !backup_path.exists() {
-        return Err(format!("No backup found for {}. Cannot revert changes.", target_path.display()).into());
+    // Make sure a weird path didn't break the script and that scripts can run from there at all before installing it,
+    // the original is only swapped out after this so a broken wrapper never replaces a working executable
+    if let Some(dir) = wrapper_path.parent() {
+        check_script_runs(dir, shell)?;
     }
+    check_script_syntax(&script, shell)?;
 
-    // Remove the symbolic link
-    if let Err(e) = fs::remove_file(target_path) {
-        println!("Failed to remove symbolic link for {}: {}", target_path.display(), e);
+    // Written next to it first and renamed into place, so there's never a half written wrapper script
+    let temp_path = temp_sibling(wrapper_path);
+    if let Err(e) = write_executable(&temp_path, &script, exec_mode).and_then(|()| return fs::rename(&temp_path, wrapper_path)) {
+        let _ = fs::remove_file(&temp_path);
         return Err(e.into());
     }
+    return Ok(())
+}
 
-    // Restore the original executable from the backup
-    if let Err(e) = fs::rename(&backup_path, target_path) {
-        println!("Failed to restore original executable for {}: {}", target_path.display(), e);
-        return Err(e.into());
+
+/// Write a file and make it executable the same way as `exec_mode` (the owner always can, it's their wrapper), flushed to disk before returning
+fn write_executable(path: &Path, contents: &str, exec_mode: u32) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    let mut permissions = file.metadata()?.permissions();
+    permissions.set_mode(permissions.mode() | (exec_mode & 0o111) | 0o100);
+    file.set_permissions(permissions)?;
+    return file.sync_all()
+}
+
+
+/// Which shell wrapper scripts run with
+///
+/// - `EnvBash` is whichever bash is on PATH (`#!/usr/bin/env bash`), wherever the distro put it.
+/// - `Bash` is `#!/bin/bash`, what wrapper scripts always used before.
+/// - `Sh` is `#!/bin/sh` for systems without bash, the scripts stick to POSIX sh for it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapperShell {
+    EnvBash,
+    Bash,
+    Sh,
+}
+
+impl WrapperShell {
+    pub const ALL: [WrapperShell; 3] = [WrapperShell::EnvBash, WrapperShell::Bash, WrapperShell::Sh];
+
+    /// How it's stored in the settings
+    pub fn name(self) -> &'static str {
+        return match self {
+            WrapperShell::EnvBash => "env_bash",
+            WrapperShell::Bash => "bash",
+            WrapperShell::Sh => "sh",
+        }
     }
 
-    // Remove the wrapper script
-    if let Err(e) = fs::remove_file(wrapper_dir.join(wrapper_name)) {
-        println!("Failed to remove wrapper script for {}: {}", target_path.display(), e);
-        return Err(e.into());
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|shell| return shell.name() == name)
     }
 
-    println!("Reverted changes for {}. Restored original executable.", target_path.display());
-    return Ok(())
-}
+    /// The first line of the scripts
+    pub fn shebang(self) -> &'static str {
+        return match self {
+            WrapperShell::EnvBash => "#!/usr/bin/env bash",
+            WrapperShell::Bash => "#!/bin/bash",
+            WrapperShell::Sh => "#!/bin/sh",
+        }
+    }
 
+    fn from_shebang(line: &str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|shell| return shell.shebang() == line)
+    }
 
-/// Get the path to the backup file
-fn backup_path(path: &Path) -> PathBuf {
-    // Check if the path has an extension
-    let backup_path = if let Some(ext) = path.extension() {
-        // If the extension is "bak", return the path as is
-        if ext.to_str() == Some("bak") {
-            path.to_path_buf()
-        } else {
-            // Otherwise, append ".bak" to the existing extension
-            path.with_extension(format!("{}.bak", ext.to_str().unwrap_or_default()))
+    /// What the shebang ends up running, for checking the scripts with it
+    fn program(self) -> &'static str {
+        return match self {
+            WrapperShell::EnvBash => "bash",
+            WrapperShell::Bash => "/bin/bash",
+            WrapperShell::Sh => "/bin/sh",
         }
-    } else {
-        // If there is no extension, simply add "bak" as the extension
-        path.with_extension("bak")
-    };
-    return backup_path
+    }
 }
 
+/// The shell of newly written wrapper scripts, from the settings
+static WRAPPER_SHELL: RwLock<WrapperShell> = RwLock::new(WrapperShell::EnvBash);
 
-/// Get the path to the original file
-fn original_path(path: &Path) -> PathBuf {
-    // Check if the path has an extension
-    let original_path = if let Some(ext) = path.extension() {
-        // If the extension is "bak", remove it
-        if ext.to_str() == Some("bak") {
-            path.with_extension("")
-        } else {
-            // If the extension is not "bak", return the path as is
-            path.to_path_buf()
+/// Pick the shell of the wrapper scripts written from now on, the existing ones keep theirs
+pub fn set_wrapper_shell(shell: WrapperShell) {
+    *WRAPPER_SHELL.write().unwrap_or_else(PoisonError::into_inner) = shell;
+}
+
+fn wrapper_shell() -> WrapperShell {
+    return *WRAPPER_SHELL.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Check if a script starts with the shebang of a wrapper script of ours, whichever shell it was written with
+fn has_wrapper_shebang(script: &str) -> bool {
+    return script.lines().next().is_some_and(|line| return WrapperShell::from_shebang(line).is_some())
+}
+
+/// The variables whose names are safe to write into a script, the names aren't quoted.
+/// The GUI and imports already refuse other names, this is for whatever got into the config some other way
+fn exportable(vars: &[(String, String)]) -> impl Iterator<Item = &(String, String)> {
+    return vars.iter().filter(|(key, _)| {
+        if is_env_name(key) { return true }
+        warn!("Leaving out the environment variable {key:?}, it's not a valid name");
+        return false
+    })
+}
+
+/// Generate the wrapper script that runs `exec_path` with the given environment and shell, `appimage` being the original path if it's an AppImage
+fn wrapper_script(exec_path: &Path, env: &WrapperEnv, extra_env: &[(String, String)], helpers: &[LaunchHelper], appimage: Option<&Path>, shell: WrapperShell) -> String {
+    // Any extra environment goes after the NVIDIA variables
+    let mut extra_exports: String = exportable(extra_env)
+        .map(|(key, value)| return format!("export {key}={}\n", shell_quote(value)))
+        .collect();
+
+    let mut launch_prefix = exec_prefix(env, helpers);
+    if let Some(original) = appimage {
+        // AppImageLauncher would offer to integrate (move) the backup it's launched from
+        extra_exports.push_str("export APPIMAGELAUNCHER_DISABLE=1\n");
+        // What apps and updaters take for the AppImage they run from, which is the original rather than the backup
+        let _ = writeln!(extra_exports, "export APPIMAGE={}", shell_quote(&original.display().to_string()));
+        // The AppImage runtime passes its argv[0] on as $ARGV0, which apps use to find their launcher, so it should be the original path rather than the backup.
+        // Launch helpers and command prefixes would get it instead, so it's only possible without them, and `exec -a` is bash only
+        if launch_prefix.is_empty() && shell != WrapperShell::Sh {
+            launch_prefix = "-a \"$0\" ".to_string();
         }
-    } else {
-        // If there is no extension, return the path as is
-        path.to_path_buf()
-    };
-    return original_path
+    }
+
+    // Generate the wrapper script
+    let offload_exports: String = exportable(&env.vars)
+        .map(|(key, value)| return format!("export {key}={}\n", shell_word(value)))
+        .collect();
+    // The path is single quoted, double quotes would still expand `$`, backticks and backslashes in it
+    return format!(
+        r#"{}
+{offload_exports}{extra_exports}exec {launch_prefix}{} "$@"
+"#,
+        shell.shebang(),
+        shell_quote(&exec_path.display().to_string())
+    )
 }
 
 
-/// Check if a file is executable
-fn is_executable(file_path: &Path) -> bool {
-    return match fs::metadata(file_path) {
-        Ok(metadata) => {
-            let permissions = metadata.permissions();
-            permissions.mode() & 0o111 != 0 // Check if any executable bit is set
-        },
-        Err(_) => false,
+/// How a wrapped executable gets its wrapper script put in front of it
+///
+/// - `InPlaceSymlink` renames the original to a backup and puts a symlink to the wrapper in its place.
+///   It survives reboots and needs no privileges, but it modifies the game folder (updates and anti-cheat may notice).
+/// - `BindMount` bind mounts the wrapper over the original, leaving the files untouched.
+///   It needs root (through `pkexec`) for every add and revert, and is undone by a reboot.
+/// - `SeparateLauncher` leaves the executable alone and writes a launcher named after it to `~/.local/bin` instead, like `prime-run`.
+///   Only launching it through that (from a terminal, or a launcher pointed at it) uses the NVIDIA GPU.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapStrategy {
+    InPlaceSymlink,
+    BindMount,
+    SeparateLauncher,
+}
+
+impl WrapStrategy {
+    pub const ALL: [WrapStrategy; 3] = [WrapStrategy::InPlaceSymlink, WrapStrategy::BindMount, WrapStrategy::SeparateLauncher];
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            WrapStrategy::InPlaceSymlink => "in_place_symlink",
+            WrapStrategy::BindMount => "bind_mount",
+            WrapStrategy::SeparateLauncher => "separate_launcher",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        return match self {
+            WrapStrategy::InPlaceSymlink => "Symlink (persistent)",
+            WrapStrategy::BindMount => "Bind mount (needs root, undone by reboot)",
+            WrapStrategy::SeparateLauncher => "Separate launcher in ~/.local/bin (executable untouched)",
+        }
     }
 }
 
 
-/// Find executable files inside a directory and its subdirectories
-fn find_executables(directory: &Path) -> Vec<PathBuf> {
-    let mut executables = Vec::new();
+/// Where the separate launcher of an executable goes, `~/.local/bin/<its name>`
+pub fn launcher_path(executable_path: &Path) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    return Some(PathBuf::from(home).join(".local/bin").join(executable_path.file_name()?))
+}
+
+
+/// Check if a launcher is one we wrote for the executable, so nothing else of the same name ever gets replaced or removed
+pub fn is_launcher_of(launcher: &Path, executable_path: &Path) -> bool {
+    let Ok(script) = fs::read_to_string(launcher) else { return false };
+    return has_wrapper_shebang(&script) && script.lines().any(|line| {
+        return line.starts_with("exec ") && line.contains(&shell_quote(&executable_path.display().to_string()))
+    })
+}
 
-    for entry in WalkDir::new(directory).into_iter().filter_map(Result::ok) {
-        let path = entry.path();
-        if path.is_file() && is_executable(path) {
-            executables.push(path.to_path_buf());
+
+/// Write a launcher that runs the executable with the offload environment, without touching the executable itself
+pub fn create_launcher(executable_path: &Path, backend: OffloadBackend, env: &WrapperEnv, extra_env: &[(String, String)], helpers: &[LaunchHelper]) -> Result<PathBuf, WrapError> {
+    if !executable_path.is_file() { return Err(WrapError::PathMissing(executable_path.to_path_buf())); }
+    let launcher = launcher_path(executable_path).ok_or_else(|| return WrapError::NotWritable(executable_path.to_path_buf()))?;
+    if launcher.exists() && !is_launcher_of(&launcher, executable_path) {
+        return Err(WrapError::AlreadyExists(launcher)); // Another app of the same name, or the user's own script
+    }
+
+    if let Some(bin_dir) = launcher.parent() {
+        fs::create_dir_all(bin_dir)?;
+        if !std::env::var_os("PATH").is_some_and(|path| return std::env::split_paths(&path).any(|dir| return dir == bin_dir)) {
+            warn!("{} isn't on PATH, launch {} by its full path or add the folder to PATH", bin_dir.display(), launcher.display());
         }
     }
+    // The executable launches from where it is, AppImages included, so there's no argv[0] to fix up
+    write_wrapper_script(&launcher, executable_path, fs::metadata(executable_path)?.mode(), &backend.env(env), extra_env, helpers, None)?;
 
-    return executables
+    info!("Launching {} now uses the NVIDIA GPU, {} is left untouched", launcher.display(), executable_path.display());
+    return Ok(launcher)
 }
 
 
-/// Get a list of all executable paths for running processes
-pub fn get_executable_paths() -> Result<HashSet<String>, Box<dyn Error>> {
-    // TODO: Maybe filter to only include processes currently on the desktop
-    return Ok(
-        all_processes()?
-          .filter_map(Result::ok) // Automatically filter out Err results and unwrap Ok values
-          .filter_map(|proc| return proc.exe().ok()) // Attempt to get the executable path, filter out Err results
-          .filter(|exe_path| return exe_path.exists() && has_write_access(exe_path) && !is_system_path(exe_path)) // Check if the path exists, we have write access, and is not a system path
-          .filter_map(|exe_path| return exe_path.to_str().map(ToString::to_string)) // Convert to String and filter out None results
-          .collect::<HashSet<String>>() // Collect into a HashSet<String>
-    )
+/// Remove the launcher written by `create_launcher`, leaving alone anything of the same name that isn't ours
+pub fn remove_launcher(executable_path: &Path) -> Result<(), WrapError> {
+    let Some(launcher) = launcher_path(executable_path) else { return Ok(()) };
+    if !launcher.exists() { return Ok(()) } // Deleted by hand, nothing left to do
+    if !is_launcher_of(&launcher, executable_path) {
+        warn!("{} isn't the launcher we wrote for {} anymore, leaving it alone", launcher.display(), executable_path.display());
+        return Ok(())
+    }
+    fs::remove_file(&launcher)?;
+    info!("Removed the launcher {}", launcher.display());
+    return Ok(())
+}
+
+
+/// Where the original of a bind mounted executable stays reachable once the wrapper is mounted over it.
+/// In the root-owned folder, since the mounted wrapper script launches whatever is there
+fn bind_mount_original(target_path: &Path) -> PathBuf {
+    return Path::new(ELEVATED_WRAPPER_DIR).join("originals").join(path_hash(target_path))
+}
+
+/// The wrapper script mounted over a bind mounted executable, root-owned so nobody but root can change what the executable runs
+fn bind_mount_script(target_path: &Path) -> PathBuf {
+    return Path::new(ELEVATED_WRAPPER_DIR).join("mounted").join(generate_wrapper_name(target_path))
+}
+
+
+/// Bind mount a wrapper script over an executable, without touching the executable itself.
+/// The original is bind mounted somewhere in the root-owned wrapper folder first, since its own path only leads to the wrapper afterwards.
+/// The wrapper script is written and checked as the user in the wrapper folder, root installs it next to the original before mounting it
+pub fn bind_mount_wrapper(wrapper_dir: &Path, target_path: &Path, backend: OffloadBackend, env: &WrapperEnv, extra_env: &[(String, String)], helpers: &[LaunchHelper]) -> Result<(), WrapError> {
+    if !target_path.is_file() { return Err(WrapError::PathMissing(target_path.to_path_buf())); }
+    if is_bind_mounted(target_path) { return Err(WrapError::AlreadyWrapped(target_path.to_path_buf())); }
+    if let Some(reason) = own_path_reason(target_path, wrapper_dir) {
+        return Err(WrapError::OwnFile(target_path.to_path_buf(), reason));
+    }
+
+    let (original, wrapper_path) = (bind_mount_original(target_path), bind_mount_script(target_path));
+    let staged = wrapper_dir.join(format!("{}.mounted", generate_wrapper_name(target_path)));
+    write_wrapper_script(&staged, &original, fs::metadata(target_path)?.mode(), &backend.env(env), extra_env, helpers, is_appimage(target_path).then_some(target_path))?;
+
+    // Everything in one go so there's only one password prompt. Mount points of files have to be files themselves, anything failing is undone
+    let quote = |path: &Path| return shell_quote(&path.display().to_string());
+    let (target, original, wrapper) = (quote(target_path), quote(&original), quote(&wrapper_path));
+    let commands = format!(
+        "install -d -m 755 {originals} {mounted} && install -m 644 -o root -g root /dev/null {original} && install -m 755 -o root -g root {staged} {wrapper} && mount --bind {target} {original} && mount --bind {wrapper} {target} || {{ umount {original} 2>/dev/null; rm -f {original} {wrapper}; exit 1; }}",
+        originals = quote(&Path::new(ELEVATED_WRAPPER_DIR).join("originals")),
+        mounted = quote(&Path::new(ELEVATED_WRAPPER_DIR).join("mounted")),
+        staged = quote(&staged),
+    );
+    let result = run_privileged(&commands);
+    let _ = fs::remove_file(&staged);
+    result?;
+
+    info!("Application {} is now bind mounted to use the NVIDIA GPU until the next reboot", target_path.display());
+    return Ok(())
 }
 
 
-/// Check if a given path is a system path
-fn is_system_path(path: &Path) -> bool {
-    if let Some(path_str) = path.to_str() {
-        return path_str.starts_with("/usr") || path_str.starts_with("/bin") || path_str.starts_with("/sbin");
+/// Unmount the wrapper script from a bind mounted executable, and clean up after it (as root too, the files left behind are root's)
+pub fn unmount_wrapper(target_path: &Path) -> Result<(), WrapError> {
+    let (original, wrapper_path) = (bind_mount_original(target_path), bind_mount_script(target_path));
+    let quote = |path: &Path| return shell_quote(&path.display().to_string());
+    let mut commands = Vec::new();
+    let mount_points: Vec<String> = [target_path, &original].into_iter().filter(|path| return is_bind_mounted(path)).map(quote).collect();
+    if !mount_points.is_empty() {
+        commands.push(format!("umount {}", mount_points.join(" ")));
+    }
+    if original.exists() || wrapper_path.exists() {
+        commands.push(format!("rm -f {} {}", quote(&original), quote(&wrapper_path)));
+    }
+    if !commands.is_empty() {
+        run_privileged(&commands.join(" && "))?;
     }
-    return false
+
+    info!("Unmounted the wrapper from {}", target_path.display());
+    return Ok(())
 }
 
 
-/// Check if a path has write access
-fn has_write_access(path: &Path) -> bool {
-    return match fs::metadata(path) {
-        Ok(metadata) => {
-            let permissions = metadata.permissions();
-            permissions.mode() & 0o200 != 0 // Check if user write bit is set
+/// Check if something is mounted right at a path
+pub fn is_bind_mounted(path: &Path) -> bool {
+    let path = path.canonicalize().unwrap_or_else(|_| return path.to_path_buf());
+    return fs::read_to_string("/proc/mounts").unwrap_or_default()
+        .lines()
+        .filter_map(|line| return line.split_whitespace().nth(1))
+        .any(|mount_point| return Path::new(&unescape_mount_field(mount_point)) == path)
+}
+
+
+/// Run shell commands as root through `pkexec`
+fn run_privileged(commands: &str) -> Result<(), WrapError> {
+    let status = Command::new("pkexec").arg("sh").arg("-c").arg(commands).status()?;
+    if !status.success() {
+        return Err(WrapError::Privileged(commands.to_string()));
+    }
+    return Ok(())
+}
+
+
+/// Where the wrapper scripts of executables wrapped as root go. Owned by root, so nobody but root can change what a root-owned executable runs
+const ELEVATED_WRAPPER_DIR: &str = "/usr/local/lib/NvidiaManager";
+
+/// The wrapper script of an executable wrapped as root
+fn elevated_wrapper_path(target_path: &Path) -> PathBuf {
+    return Path::new(ELEVATED_WRAPPER_DIR).join(generate_wrapper_name(target_path))
+}
+
+/// Check if an executable was wrapped as root, going by its symlink pointing into the root-owned wrapper folder
+pub fn is_elevated(executable_path: &Path) -> bool {
+    return fs::read_link(executable_path).is_ok_and(|destination| return destination.starts_with(ELEVATED_WRAPPER_DIR))
+}
+
+/// Check if wrapping an executable in place needs root, which is when its folder isn't writable for us
+pub fn needs_elevation(executable_path: &Path) -> bool {
+    if !executable_path.is_file() || is_desktop_entry(executable_path) { return false }
+    // Permission bits don't tell about ACLs, read-only mounts and such, so just try
+    let probe = temp_sibling(executable_path);
+    return match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            false
         },
-        Err(_) => false,
+        Err(e) => e.kind() == io::ErrorKind::PermissionDenied,
+    }
+}
+
+/// Wrap an executable we can't write to (like a root-owned one) in place with one `pkexec` prompt, for when the whole GUI shouldn't run as root.
+/// The wrapper script is written and checked as the user, root only installs it into its own folder, backs up the executable next to itself and swaps in the symlink
+pub fn wrap_elevated(wrapper_dir: &Path, target_path: &Path, backend: OffloadBackend, env: &WrapperEnv, extra_env: &[(String, String)], helpers: &[LaunchHelper]) -> Result<(), WrapError> {
+    if !target_path.is_file() { return Err(WrapError::PathMissing(target_path.to_path_buf())); }
+    let target_path = &normalize_path(target_path)?;
+    if fs::read_link(target_path).is_ok() { return Err(WrapError::UnwrappableSymlink(target_path.clone(), target_path.canonicalize()?)); }
+    if backup_path(target_path).exists() { return Err(WrapError::AlreadyWrapped(target_path.clone())); }
+    if let Some(reason) = own_path_reason(target_path, wrapper_dir) {
+        return Err(WrapError::OwnFile(target_path.clone(), reason));
+    }
+
+    let backup_path = backup_path(target_path);
+    let wrapper_path = elevated_wrapper_path(target_path);
+    let original_metadata = fs::metadata(target_path)?;
+    let original_hash = file_sha256(target_path)?;
+    let staged = wrapper_dir.join(format!("{}.elevated", generate_wrapper_name(target_path)));
+    write_wrapper_script(&staged, &backup_path, original_metadata.mode(), &backend.env(env), extra_env, helpers, is_appimage(target_path).then_some(target_path))?;
+
+    // The original stays in place until the symlink is renamed over it, anything failing before that is undone
+    let quote = |path: &Path| return shell_quote(&path.display().to_string());
+    let (target, backup, wrapper, temp_link) = (quote(target_path), quote(&backup_path), quote(&wrapper_path), quote(&temp_sibling(target_path)));
+    let commands = format!(
+        "install -d -m 755 {dir} && install -m 755 -o root -g root {staged} {wrapper} && {{ ln {target} {backup} 2>/dev/null || cp -p {target} {backup}; }} && ln -sfn {wrapper} {temp_link} && mv -Tf {temp_link} {target} || {{ rm -f {temp_link} {backup} {wrapper}; exit 1; }}",
+        dir = shell_quote(ELEVATED_WRAPPER_DIR),
+        staged = quote(&staged),
+    );
+    let result = run_privileged(&commands);
+    let _ = fs::remove_file(&staged);
+    result?;
+
+    if fs::read_link(target_path).ok().as_deref() != Some(wrapper_path.as_path()) || !backup_path.exists() {
+        return Err(WrapError::UnexpectedState(target_path.clone()));
+    }
+    // Same as wrapping it as the user, so reverting can check the backup
+    if let Err(e) = record_wrapped(target_path, |wrapped| (wrapped.sha256, wrapped.permissions) = (Some(original_hash), Some(OriginalPermissions::of(&original_metadata)))) {
+        error!("Failed to record the hash and permissions of {}, its backup can't be verified when reverting: {e}", target_path.display());
+    }
+    info!("Wrapped {} as root, its wrapper script is {}", target_path.display(), wrapper_path.display());
+    return Ok(())
+}
+
+/// The shell commands `revert_elevated` runs as root
+fn revert_elevated_commands(target_path: &Path, backup_path: &Path) -> String {
+    let quote = |path: &Path| return shell_quote(&path.display().to_string());
+    return format!("mv -Tf {backup} {target} && rm -f {wrapper}", backup = quote(backup_path), target = quote(target_path), wrapper = quote(&elevated_wrapper_path(target_path)))
+}
+
+/// Undo `wrap_elevated` with another `pkexec` prompt: the backup goes back in place of the symlink and the wrapper script is removed
+fn revert_elevated(target_path: &Path, backup_path: &Path, dry_run: bool) -> Result<(), WrapError> {
+    if dry_run {
+        info!("[dry run] Would move {} back to {} and remove the wrapper script {} as root", backup_path.display(), target_path.display(), elevated_wrapper_path(target_path).display());
+        return Ok(())
+    }
+    run_privileged(&revert_elevated_commands(target_path, backup_path))?;
+    info!("Reverted {} as root. Restored original executable.", target_path.display());
+    return Ok(())
+}
+
+
+/// Check that a freshly wrapped executable is a symlink to its wrapper script, with its backup where it's expected
+fn verify_wrapped(target_path: &Path, wrapper_path: &Path, expected_backup: &Path) -> Result<(), WrapError> {
+    let linked = fs::read_link(target_path).is_ok_and(|destination| return destination == wrapper_path);
+    if !linked || backup_path(target_path) != expected_backup || !expected_backup.exists() {
+        return Err(WrapError::UnexpectedState(target_path.to_path_buf()));
+    }
+    return Ok(())
+}
+
+
+/// Undo a half finished wrapping, putting the original back from its backup
+fn rollback_wrapper(target_path: &Path, wrapper_path: &Path, backup: &Path) {
+    match fs::symlink_metadata(target_path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => { let _ = fs::remove_file(target_path); },
+        // The original was never replaced, the backup is just a spare copy of it
+        Ok(_) => { let _ = fs::remove_file(backup); },
+        Err(_) => {},
+    }
+    if backup.exists() && fs::symlink_metadata(target_path).is_err() {
+        if let Err(e) = move_file(backup, target_path) {
+            error!("Failed to roll back {}, the original is still at {}: {e}", target_path.display(), backup.display());
+            return;
+        }
+    }
+    if config::wrapped(target_path).is_some() {
+        let _ = record_wrapped(target_path, |wrapped| *wrapped = Wrapped::default());
+    }
+    let _ = fs::remove_file(wrapper_path);
+    info!("Rolled back the changes to {}", target_path.display());
+}
+
+
+/// Suffix of the integrated GPU symlinks and their wrapper scripts (generated wrapper names never contain a `-`)
+const IGPU_SUFFIX: &str = "-igpu";
+
+
+/// Get the path of the integrated GPU symlink of an executable, `game.x86_64` gets `game-igpu.x86_64`
+fn igpu_link_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    return match path.extension() {
+        Some(ext) => path.with_file_name(format!("{stem}{IGPU_SUFFIX}.{}", ext.to_string_lossy())),
+        None => path.with_file_name(format!("{stem}{IGPU_SUFFIX}")),
     }
-}
\ No newline at end of file
+}
+
+
+/// Check if a path is an integrated GPU symlink pointing at one of our wrapper scripts
+fn is_igpu_link(path: &Path) -> bool {
+    return fs::read_link(path).is_ok_and(|destination| {
+        return destination.file_name().and_then(|name| return name.to_str()).is_some_and(|name| return name.starts_with("wrapper_") && name.ends_with(IGPU_SUFFIX))
+    })
+}
+
+
+/// Create a symlink next to a wrapped executable with a wrapper script that keeps it on the integrated GPU, so both can be picked per launch
+fn create_igpu_link(target_path: &Path, wrapper_dir: &Path, wrapper_name: &str, backup_path: &Path) -> Result<(), WrapError> {
+    let link_path = igpu_link_path(target_path);
+    if fs::symlink_metadata(&link_path).is_ok() {
+        return Err(WrapError::AlreadyExists(link_path));
+    }
+
+    let wrapper_path = wrapper_dir.join(format!("{wrapper_name}{IGPU_SUFFIX}"));
+    write_igpu_script(&wrapper_path, backup_path)?;
+    std::os::unix::fs::symlink(&wrapper_path, &link_path).map_err(|e| return WrapError::SymlinkFailed(link_path.clone(), e))?;
+
+    info!("{} now launches {} on the integrated GPU", link_path.display(), target_path.display());
+    return Ok(())
+}
+
+
+/// Write the script behind the integrated GPU symlink of an executable, which launches its backup with `DRI_PRIME=0`
+fn write_igpu_script(wrapper_path: &Path, backup_path: &Path) -> Result<(), WrapError> {
+    let shell = wrapper_shell();
+    let script = format!(
+        r#"{}
+export DRI_PRIME=0
+exec {} "$@"
+"#,
+        shell.shebang(),
+        shell_quote(&backup_path.display().to_string())
+    );
+    check_script_syntax(&script, shell)?;
+
+    fs::write(wrapper_path, script)?;
+    make_executable(wrapper_path)?;
+    return Ok(())
+}
+
+
+/// Which GPU offload a wrapper script sets up
+///
+/// - `Nvidia` is PRIME render offload for the proprietary NVIDIA driver.
+/// - `DriPrime` is `DRI_PRIME=1`, for hybrid AMD/Intel setups on Mesa (and nouveau).
+/// - `Custom` is whatever environment was set for the executable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OffloadBackend {
+    Nvidia,
+    DriPrime,
+    Custom,
+}
+
+impl OffloadBackend {
+    pub const ALL: [OffloadBackend; 3] = [OffloadBackend::Nvidia, OffloadBackend::DriPrime, OffloadBackend::Custom];
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            OffloadBackend::Nvidia => "nvidia",
+            OffloadBackend::DriPrime => "dri_prime",
+            OffloadBackend::Custom => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|backend| return backend.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        return match self {
+            OffloadBackend::Nvidia => "NVIDIA (PRIME render offload)",
+            OffloadBackend::DriPrime => "AMD/Intel (DRI_PRIME)",
+            OffloadBackend::Custom => "Custom environment",
+        }
+    }
+
+    /// The environment a wrapper script of this backend exports, `custom` being the one set for the executable.
+    /// Its variables go on top of the backend's own (leaving out the NVIDIA offload ones for `DriPrime`), so per-executable ones like `DXVK_HUD` apply with every backend.
+    /// On Wayland the NVIDIA one also points EGL at the NVIDIA driver, the GLX variable only reaches X11 (XWayland) apps
+    pub fn env(self, custom: &WrapperEnv) -> WrapperEnv {
+        return match self {
+            OffloadBackend::Nvidia => {
+                let mut env = WrapperEnv::default();
+                if let (SessionType::Wayland, Some(vendor_file)) = (SessionType::detect(), nvidia_egl_vendor()) {
+                    env.set("__EGL_VENDOR_LIBRARY_FILENAMES", vendor_file);
+                }
+                for (key, value) in custom.extras.iter().flat_map(|extra| return extra.vars()) {
+                    env.set(key, value);
+                }
+                // With more than one NVIDIA GPU the offload goes to whichever the driver picks, unless it's named
+                if let Some(index) = custom.nvidia_gpu.as_ref().and_then(|bus_id| return nvidia_gpus().iter().position(|gpu| return gpu.bus_id == *bus_id)) {
+                    env.set("__NV_PRIME_RENDER_OFFLOAD_PROVIDER", &format!("NVIDIA-G{index}"));
+                }
+                for (key, value) in &custom.vars {
+                    env.set(key, value);
+                }
+                env.command_prefix.clone_from(&custom.command_prefix);
+                env
+            },
+            OffloadBackend::DriPrime => {
+                let mut env = WrapperEnv { vars: vec![("DRI_PRIME".to_string(), "1".to_string())], extras: Vec::new(), nvidia_gpu: None, command_prefix: custom.command_prefix.clone() };
+                // Every executable starts out with the NVIDIA offload variables, which would send it to the NVIDIA driver instead
+                for (key, value) in custom.vars.iter().filter(|(key, _)| return !OFFLOAD_ENV.iter().any(|(offload_key, _)| return offload_key == key)) {
+                    env.set(key, value);
+                }
+                env
+            },
+            OffloadBackend::Custom => custom.clone(),
+        }
+    }
+}
+
+
+/// The kind of graphical session we're running in, which decides what the offload variables reach
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SessionType {
+    X11,
+    Wayland,
+    Unknown,
+}
+
+impl SessionType {
+    /// Go by `XDG_SESSION_TYPE`, falling back to which display variables are set
+    pub fn detect() -> Self {
+        match std::env::var("XDG_SESSION_TYPE").as_deref() {
+            Ok("wayland") => return SessionType::Wayland,
+            Ok("x11") => return SessionType::X11,
+            _ => {},
+        }
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() { return SessionType::Wayland }
+        if std::env::var_os("DISPLAY").is_some() { return SessionType::X11 }
+        return SessionType::Unknown
+    }
+
+    pub fn label(self) -> &'static str {
+        return match self {
+            SessionType::X11 => "X11",
+            SessionType::Wayland => "Wayland",
+            SessionType::Unknown => "unknown",
+        }
+    }
+}
+
+
+/// Where glvnd looks for the EGL driver of the NVIDIA GPU
+const NVIDIA_EGL_VENDORS: [&str; 2] = ["/usr/share/glvnd/egl_vendor.d/10_nvidia.json", "/etc/glvnd/egl_vendor.d/10_nvidia.json"];
+
+
+/// The EGL vendor file of the NVIDIA driver, if it's installed
+fn nvidia_egl_vendor() -> Option<&'static str> {
+    return NVIDIA_EGL_VENDORS.into_iter().find(|path| return Path::new(path).exists())
+}
+
+
+/// Why wrapping with a backend may not offload anything in this session, if there's a reason to think so
+pub fn session_warning(session: SessionType, backend: OffloadBackend, custom: &WrapperEnv) -> Option<String> {
+    let env = backend.env(custom);
+    let has = |key: &str| return env.vars.iter().any(|(existing, _)| return existing == key);
+    return match session {
+        SessionType::Wayland if backend == OffloadBackend::Nvidia && nvidia_egl_vendor().is_none() => {
+            Some("The NVIDIA EGL driver wasn't found, native Wayland apps may stay on the integrated GPU (XWayland and Vulkan apps are fine)".to_string())
+        },
+        SessionType::Wayland if has("__GLX_VENDOR_LIBRARY_NAME") && !["__NV_PRIME_RENDER_OFFLOAD", "__EGL_VENDOR_LIBRARY_FILENAMES", "DRI_PRIME"].iter().any(|key| return has(key)) => {
+            Some("__GLX_VENDOR_LIBRARY_NAME only reaches X11 (XWayland) apps, native Wayland apps need __NV_PRIME_RENDER_OFFLOAD too".to_string())
+        },
+        SessionType::Unknown => Some("No graphical session was detected, the offload only applies to apps launched from one".to_string()),
+        _ => None,
+    }
+}
+
+
+/// Optional variables on top of the NVIDIA offload, picked per executable
+///
+/// - `VideoDecode` points libva and VDPAU at the NVIDIA driver, for hardware video decoding (libva needs nvidia-vaapi-driver).
+/// - `VulkanIcd` hides every Vulkan driver but NVIDIA's from the Vulkan loader, for apps that pick the first GPU they find.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NvidiaExtra {
+    VideoDecode,
+    VulkanIcd,
+}
+
+impl NvidiaExtra {
+    pub const ALL: [NvidiaExtra; 2] = [NvidiaExtra::VideoDecode, NvidiaExtra::VulkanIcd];
+
+    /// How it's stored in the config
+    pub fn name(self) -> &'static str {
+        return match self {
+            NvidiaExtra::VideoDecode => "video_decode",
+            NvidiaExtra::VulkanIcd => "vulkan_icd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|extra| return extra.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        return match self {
+            NvidiaExtra::VideoDecode => "Video decoding (LIBVA_DRIVER_NAME, VDPAU_DRIVER)",
+            NvidiaExtra::VulkanIcd => "Only the NVIDIA Vulkan driver (VK_ICD_FILENAMES)",
+        }
+    }
+
+    /// Whether the driver files it points at are installed
+    pub fn is_available(self) -> bool {
+        return match self {
+            NvidiaExtra::VideoDecode => true,
+            NvidiaExtra::VulkanIcd => nvidia_vulkan_icd().is_some(),
+        }
+    }
+
+    /// The variables it sets, nothing if the driver files it needs aren't there
+    fn vars(self) -> Vec<(&'static str, &'static str)> {
+        return match self {
+            NvidiaExtra::VideoDecode => vec![("LIBVA_DRIVER_NAME", "nvidia"), ("VDPAU_DRIVER", "nvidia")],
+            // Newer Vulkan loaders go by VK_DRIVER_FILES, older ones only know VK_ICD_FILENAMES
+            NvidiaExtra::VulkanIcd => nvidia_vulkan_icd().map(|icd| return vec![("VK_DRIVER_FILES", icd), ("VK_ICD_FILENAMES", icd)]).unwrap_or_default(),
+        }
+    }
+}
+
+
+/// Where the Vulkan loader looks for the driver manifest of the NVIDIA GPU
+const NVIDIA_VULKAN_ICDS: [&str; 4] = ["/usr/share/vulkan/icd.d/nvidia_icd.json", "/usr/share/vulkan/icd.d/nvidia_icd.x86_64.json", "/etc/vulkan/icd.d/nvidia_icd.json", "/etc/vulkan/icd.d/nvidia_icd.x86_64.json"];
+
+
+/// The Vulkan driver manifest of the NVIDIA driver, if it's installed
+fn nvidia_vulkan_icd() -> Option<&'static str> {
+    return NVIDIA_VULKAN_ICDS.into_iter().find(|path| return Path::new(path).exists())
+}
+
+
+/// The environment that makes an executable render on the NVIDIA GPU
+const OFFLOAD_ENV: [(&str, &str); 3] = [
+    ("__NV_PRIME_RENDER_OFFLOAD", "1"),
+    ("__GLX_VENDOR_LIBRARY_NAME", "nvidia"),
+    ("__VK_LAYER_NV_optimus", "NVIDIA_only"),
+];
+
+
+/// The environment a wrapper script exports, in the order it's exported.
+/// The NVIDIA offload variables by default, each executable can override or add to them
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WrapperEnv {
+    #[serde(rename = "env", with = "crate::config::env_vars", skip_serializing_if = "crate::config::env_vars::is_default")]
+    pub vars: Vec<(String, String)>,
+    /// What the NVIDIA backend sets on top of its offload variables
+    #[serde(rename = "nvidia_extras", with = "crate::config::nvidia_extras", skip_serializing_if = "Vec::is_empty")]
+    pub extras: Vec<NvidiaExtra>,
+    /// PCI bus id of the NVIDIA GPU to offload to, the driver picks if it's not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nvidia_gpu: Option<String>,
+    /// A command the executable is launched through (e.g. `mangohud`), split into words on whitespace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_prefix: Option<String>,
+}
+
+impl Default for WrapperEnv {
+    fn default() -> Self {
+        return WrapperEnv { vars: OFFLOAD_ENV.iter().map(|(key, value)| return ((*key).to_string(), (*value).to_string())).collect(), extras: Vec::new(), nvidia_gpu: None, command_prefix: None }
+    }
+}
+
+impl WrapperEnv {
+    /// The words of the command prefix, which get quoted one by one so the shell takes them as typed
+    pub(crate) fn prefix_words(&self) -> Vec<&str> {
+        return self.command_prefix.as_deref().map(|prefix| return prefix.split_whitespace().collect()).unwrap_or_default()
+    }
+
+    /// Set a variable, keeping its place if it's already there
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.vars.iter_mut().find(|(existing, _)| return existing == key) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.vars.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// Check that every name can be exported by a shell, anything else would break the wrapper script
+    pub fn invalid_key(&self) -> Option<&str> {
+        return self.vars.iter().map(|(key, _)| return key.as_str()).find(|key| return !is_env_name(key))
+    }
+}
+
+
+/// Launch helpers go in front of the executable, in a fixed order no matter how they were picked
+pub(crate) fn launch_prefix(helpers: &[LaunchHelper]) -> String {
+    return LaunchHelper::ALL.into_iter()
+        .filter(|helper| return helpers.contains(helper))
+        .map(LaunchHelper::prefix)
+        .collect()
+}
+
+
+/// Commands that are commonly put in front of games, offered as a pick for the command prefix of an entry
+pub const COMMON_PREFIXES: [&str; 4] = ["mangohud", "gamemoderun", "prime-run", "obs-gamecapture"];
+
+/// What goes in front of the executable in a wrapper script: the launch helpers, then the entry's own command prefix
+fn exec_prefix(env: &WrapperEnv, helpers: &[LaunchHelper]) -> String {
+    let prefix: String = env.prefix_words().into_iter().map(|word| return format!("{} ", shell_word(word))).collect();
+    return launch_prefix(helpers) + &prefix
+}
+
+
+/// Get a command that runs an executable on the NVIDIA GPU without wrapping it, for pasting into a launcher
+pub fn offload_command(executable_path: &Path, env: &WrapperEnv, extra_env: &[(String, String)], helpers: &[LaunchHelper]) -> String {
+    let assignments: Vec<String> = exportable(&env.vars)
+        .map(|(key, value)| return format!("{key}={}", shell_word(value)))
+        .chain(exportable(extra_env).map(|(key, value)| return format!("{key}={}", shell_quote(value))))
+        .collect();
+    return format!("{} {}{}", assignments.join(" "), exec_prefix(env, helpers), shell_quote(&executable_path.display().to_string()))
+}
+
+
+/// Check the syntax of a script without running it (`-n`), with the shell its shebang ends up running
+fn check_script_syntax(script: &str, shell: WrapperShell) -> Result<(), WrapError> {
+    let mut bash = Command::new(shell.program())
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    bash.stdin.take().ok_or_else(|| return io::Error::other("Failed to open stdin of bash"))?.write_all(script.as_bytes())?;
+
+    let output = bash.wait_with_output()?;
+    if !output.status.success() {
+        return Err(WrapError::ScriptSyntax(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    return Ok(())
+}
+
+
+/// The errno of running a file that's still open for writing ("Text file busy"), `ETXTBSY` on Linux
+const ETXTBSY: i32 = 26;
+
+/// Folders that wrapper scripts with a shell are known to run from, so a folder of executables doesn't probe once for each of them
+static RUNNABLE_DIRS: Mutex<Vec<(PathBuf, WrapperShell)>> = Mutex::new(Vec::new());
+
+/// Run a script that does nothing from `dir` the same way a wrapper script gets run, which fails if the shell is missing or `dir` is mounted noexec
+fn check_script_runs(dir: &Path, shell: WrapperShell) -> Result<(), WrapError> {
+    // Held while probing, so wrapping a folder in parallel probes only once
+    let mut runnable_dirs = RUNNABLE_DIRS.lock().unwrap_or_else(PoisonError::into_inner);
+    if runnable_dirs.iter().any(|(runnable, runnable_shell)| return runnable == dir && *runnable_shell == shell) {
+        return Ok(())
+    }
+
+    let probe_path = dir.join(format!(".nvidia_manager_probe_{}", std::process::id()));
+    let result = write_executable(&probe_path, &format!("{}\nexit 0\n", shell.shebang()), 0o700).and_then(|()| {
+        // Another thread spawning something right after it was written can still hold it open for writing (ETXTBSY), which clears up quickly
+        let mut attempts = 0;
+        loop {
+            let status = Command::new(&probe_path).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status();
+            attempts += 1;
+            if attempts == 5 || !matches!(&status, Err(e) if e.raw_os_error() == Some(ETXTBSY)) {
+                return status;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+    let _ = fs::remove_file(&probe_path);
+
+    return match result {
+        Ok(status) if status.success() => {
+            runnable_dirs.push((dir.to_path_buf(), shell));
+            Ok(())
+        },
+        // `env` exits with 127 when it can't find the shell on PATH
+        Ok(status) if status.code() == Some(127) => Err(WrapError::ScriptWontRun(dir.to_path_buf(), format!("{} isn't installed", shell.program()))),
+        Ok(status) => Err(WrapError::ScriptWontRun(dir.to_path_buf(), format!("a test script exited with {status}"))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(WrapError::ScriptWontRun(dir.to_path_buf(), format!("{} doesn't exist", shell.shebang().trim_start_matches("#!")))),
+        Err(e) => Err(WrapError::ScriptWontRun(dir.to_path_buf(), e.to_string())),
+    }
+}
+
+
+/// Programs a wrapped executable can be launched through on top of the offload
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LaunchHelper {
+    Gamemode,
+    Gamescope,
+}
+
+impl LaunchHelper {
+    pub const ALL: [LaunchHelper; 2] = [LaunchHelper::Gamemode, LaunchHelper::Gamescope];
+
+    /// The command of the helper
+    pub fn command(self) -> &'static str {
+        return match self {
+            LaunchHelper::Gamemode => "gamemoderun",
+            LaunchHelper::Gamescope => "gamescope",
+        }
+    }
+
+    /// What goes in front of the executable in the wrapper script
+    fn prefix(self) -> &'static str {
+        return match self {
+            LaunchHelper::Gamemode => "gamemoderun ",
+            LaunchHelper::Gamescope => "gamescope -- ",
+        }
+    }
+
+    /// Check if the helper is installed (somewhere on PATH), it's pointless to offer it otherwise
+    pub fn is_installed(self) -> bool {
+        return on_path(self.command())
+    }
+}
+
+/// Check if an executable with that name is somewhere on PATH
+pub(crate) fn on_path(command: &str) -> bool {
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    return std::env::split_paths(&path_var)
+        .map(|dir| return dir.join(command))
+        .any(|candidate| return candidate.is_file() && is_executable(&candidate))
+}
+
+/// How long a hook gets to finish before it's killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run a user hook command with the target path as its only argument, logging whatever it prints
+/// A hook that exits nonzero or runs past the timeout is an error, but whatever it was hooked to has already happened
+pub fn run_hook(hook: &str, target: &Path) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new(hook)
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain the output on the side so a chatty hook can't block on a full pipe while we wait for it
+    let mut stdout = child.stdout.take().ok_or_else(|| return io::Error::other("Failed to open stdout of the hook"))?;
+    let mut stderr = child.stderr.take().ok_or_else(|| return io::Error::other("Failed to open stderr of the hook"))?;
+    let stdout_reader = thread::spawn(move || { let mut output = String::new(); let _ = stdout.read_to_string(&mut output); return output });
+    let stderr_reader = thread::spawn(move || { let mut output = String::new(); let _ = stderr.read_to_string(&mut output); return output });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? { break Some(status); }
+        if started.elapsed() > HOOK_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    for line in stdout_reader.join().unwrap_or_default().lines() {
+        info!("[{hook}] {line}");
+    }
+    for line in stderr_reader.join().unwrap_or_default().lines() {
+        warn!("[{hook}] {line}");
+    }
+
+    return match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => Err(format!("{hook} exited with {status}").into()),
+        None => Err(format!("{hook} didn't finish within {} seconds and was killed", HOOK_TIMEOUT.as_secs()).into()),
+    }
+}
+
+/// Check if the proprietary NVIDIA driver is installed, without it the NVIDIA offload variables break whatever they're set for
+pub fn nvidia_driver_present() -> bool {
+    return Path::new("/proc/driver/nvidia/version").exists() || on_path("nvidia-smi")
+}
+
+
+/// An NVIDIA GPU in the system
+#[derive(Clone, Debug)]
+pub struct NvidiaGpu {
+    /// Like `0000:01:00.0`
+    pub bus_id: String,
+    /// The model from lspci, or the PCI device id without it
+    pub name: String,
+}
+
+/// The NVIDIA GPUs, in the order of their PCI bus ids which is also the order of their `NVIDIA-G<n>` offload providers.
+/// Read once, they don't change while running
+pub fn nvidia_gpus() -> &'static [NvidiaGpu] {
+    static GPUS: OnceLock<Vec<NvidiaGpu>> = OnceLock::new();
+    return GPUS.get_or_init(|| {
+        let Ok(devices) = fs::read_dir("/sys/bus/pci/devices") else { return Vec::new() };
+        let read = |path: PathBuf| return fs::read_to_string(path).map(|text| return text.trim().to_string()).unwrap_or_default();
+        let mut gpus: Vec<NvidiaGpu> = devices.flatten()
+            // 0x10de is NVIDIA, classes 0x03xxxx are display controllers (not their audio and USB functions)
+            .filter(|device| return read(device.path().join("vendor")) == "0x10de" && read(device.path().join("class")).starts_with("0x03"))
+            .map(|device| {
+                let bus_id = device.file_name().to_string_lossy().to_string();
+                let name = lspci_name(&bus_id).unwrap_or_else(|| return format!("NVIDIA GPU [10de:{}]", read(device.path().join("device")).trim_start_matches("0x")));
+                return NvidiaGpu { bus_id, name }
+            })
+            .collect();
+        gpus.sort_by(|gpu_a, gpu_b| return gpu_a.bus_id.cmp(&gpu_b.bus_id));
+        return gpus
+    })
+}
+
+
+/// The model name of a PCI device according to lspci, if it's installed
+fn lspci_name(bus_id: &str) -> Option<String> {
+    let output = Command::new("lspci").args(["-mm", "-s", bus_id]).output().ok()?;
+    // `01:00.0 "VGA compatible controller" "NVIDIA Corporation" "GA107M [GeForce RTX 3050 Mobile]" ...`, the fourth quoted field is the device
+    let line = String::from_utf8_lossy(&output.stdout).lines().next()?.to_string();
+    return line.split('"').nth(5).filter(|name| return !name.is_empty()).map(ToString::to_string)
+}
+
+
+/// Which backend rfd can show a file dialog with, if any
+/// Without one rfd returns nothing, which looks exactly like the user cancelling the dialog
+pub fn file_dialog_backend() -> Option<&'static str> {
+    // rfd goes through the desktop portal first, so ask the session bus if anything provides it
+    let portal = Command::new("dbus-send")
+        .args(["--session", "--print-reply", "--dest=org.freedesktop.DBus", "/org/freedesktop/DBus",
+            "org.freedesktop.DBus.NameHasOwner", "string:org.freedesktop.portal.Desktop"])
+        .stderr(Stdio::null())
+        .output();
+    if portal.is_ok_and(|output| return output.status.success() && String::from_utf8_lossy(&output.stdout).contains("boolean true")) {
+        return Some("xdg-desktop-portal");
+    }
+
+    // And falls back to zenity when there's no portal
+    if on_path("zenity") { return Some("zenity"); }
+    return None
+}
+
+
+/// Open a folder in the file manager, without waiting for it
+pub fn open_folder(folder: &Path) -> io::Result<()> {
+    let mut xdg_open = Command::new("xdg-open").arg(folder).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    // Reaped in the background so it doesn't linger as a zombie
+    thread::spawn(move || return xdg_open.wait());
+    return Ok(())
+}
+
+
+/// Show a file selected in the file manager through the `FileManager1` D-Bus interface most of them have, opening its folder instead if there's none
+pub fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    let shown = Command::new("dbus-send")
+        .args(["--session", "--print-reply", "--dest=org.freedesktop.FileManager1", "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems", &format!("array:string:{}", file_uri(path)), "string:"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if shown.is_ok_and(|status| return status.success()) { return Ok(()) }
+    return open_folder(path.parent().unwrap_or(path))
+}
+
+
+/// A `file://` URI of a path, percent-encoded so commas (which dbus-send splits arrays on) and anything else unusual survive
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(byte) {
+            uri.push(char::from(*byte));
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    return uri
+}
+
+
+/// Whether an ELF binary is 32 or 64-bit
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+
+/// Read the class of an ELF binary from its header, `None` if it's not an ELF binary (scripts, Windows executables, ...)
+pub fn elf_class(path: &Path) -> Option<ElfClass> {
+    let mut header = [0u8; 5];
+    fs::File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if header[..4] != *b"\x7fELF" { return None }
+    return match header[4] {
+        1 => Some(ElfClass::Elf32),
+        2 => Some(ElfClass::Elf64),
+        _ => None,
+    }
+}
+
+
+/// Check if a file is an AppImage, by its magic bytes (`AI` and the type right after the ELF header's identification) or else its extension
+pub fn is_appimage(path: &Path) -> bool {
+    let mut header = [0u8; 11];
+    if fs::File::open(path).and_then(|mut file| return file.read_exact(&mut header)).is_ok() && header[..4] == *b"\x7fELF" && header[8..10] == *b"AI" && matches!(header[10], 1 | 2) {
+        return true;
+    }
+    return path.extension().is_some_and(|ext| return ext.eq_ignore_ascii_case("appimage"))
+}
+
+
+/// Check if the 32-bit NVIDIA GLX library is installed, in any of the places distros put 32-bit libraries
+fn has_32bit_nvidia_libs() -> bool {
+    return ["/usr/lib32", "/usr/lib/i386-linux-gnu", "/usr/lib", "/lib32"].iter()
+        .map(|dir| return Path::new(dir).join("libGLX_nvidia.so.0"))
+        .filter(|library| return library.exists())
+        .any(|library| return elf_class(&library) == Some(ElfClass::Elf32)) // /usr/lib holds the 64-bit ones on some distros
+}
+
+
+/// Recommended extra environment for Proton/Wine executables
+pub const PROTON_ENV: &str = "PROTON_ENABLE_NVAPI=1\nDXVK_ENABLE_NVAPI=1\nPROTON_HIDE_NVIDIA_GPU=0";
+
+
+/// Check if an executable belongs to Proton/Wine by looking at its path and parent directories
+pub fn is_proton_path(path: &Path) -> bool {
+    return path.components()
+        .filter_map(|component| return component.as_os_str().to_str())
+        .map(str::to_lowercase)
+        .any(|component| return component.contains("proton") || component.contains("wine"))
+}
+
+
+/// Files and folders that belong to anti-cheat software, games shipping them may ban you for modified binaries
+pub const ANTI_CHEAT_PATTERNS: [&str; 9] = ["easyanticheat", "eac_launcher", "start_protected_game", "battleye", "beclient", "vanguard", "gameguard", "xigncode", "punkbuster"];
+
+
+/// How deep into a folder to look for anti-cheat files. They sit close to the game executables, and a big folder shouldn't be walked whole
+const ANTI_CHEAT_DEPTH: usize = 3;
+
+
+/// Find the anti-cheat pattern (built-in or extra) matching a path, the files next to it, or anything up to `ANTI_CHEAT_DEPTH` inside it if it's a directory.
+/// Only the names below the game folder count, the folders above it (like a user named after an anti-cheat) say nothing about the game
+pub fn anti_cheat_match(path: &Path, extra_patterns: &[String]) -> Option<String> {
+    let patterns: Vec<String> = ANTI_CHEAT_PATTERNS.iter()
+        .map(ToString::to_string)
+        .chain(extra_patterns.iter().map(|pattern| return pattern.to_lowercase()))
+        .filter(|pattern| return !pattern.is_empty())
+        .collect();
+
+    // Anti-cheat usually sits right next to the game executable, so look around it too
+    let (folder, depth) = if path.is_dir() { (path, ANTI_CHEAT_DEPTH) } else { (path.parent().unwrap_or(path), 1) };
+    return std::iter::once(path.to_path_buf())
+        .chain(WalkDir::new(folder).min_depth(1).max_depth(depth).into_iter().filter_map(Result::ok).map(walkdir::DirEntry::into_path))
+        .find_map(|candidate| {
+            return candidate.strip_prefix(folder).unwrap_or(&candidate).components()
+                .map(|component| return component.as_os_str().to_string_lossy().to_lowercase())
+                .find_map(|component| return patterns.iter().find(|pattern| return component.contains(pattern.as_str())).cloned())
+        })
+}
+
+
+/// Why wrapping a path could affect more than the user's own stuff: it's outside their home folder (like `/opt`), or other users can write to it.
+/// The file picker can select anything, unlike the process list which leaves out system paths
+pub fn shared_path_reason(path: &Path) -> Option<String> {
+    // Desktop entries we can't write to get a copy in the user's applications folder instead, the original isn't touched
+    if is_desktop_entry(path) && fs::OpenOptions::new().write(true).open(path).is_err() { return None }
+    // A symlink gets its real file wrapped, so that's the one that matters
+    let real_path = fs::canonicalize(path).unwrap_or_else(|_| return path.to_path_buf());
+
+    let mut reasons = Vec::new();
+    if std::env::var_os("HOME").is_some_and(|home| return !real_path.starts_with(home)) {
+        reasons.push(format!("{} is outside your home folder, so it may be shared by the whole system", real_path.display()));
+    }
+    if let Ok(metadata) = fs::metadata(&real_path) {
+        if metadata.mode() & 0o022 != 0 {
+            reasons.push(format!("{} can be written by other users (mode {:o})", real_path.display(), metadata.mode() & 0o7777));
+        }
+    }
+    return (!reasons.is_empty()).then(|| return reasons.join(", and "))
+}
+
+
+/// Check that a variable name can be exported by a shell. Wrapper scripts write names as they are, so anything else could run as a command
+pub fn is_env_name(key: &str) -> bool {
+    return Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap().is_match(key)
+}
+
+
+/// Parse `KEY=VALUE` lines into environment variables, skipping empty lines and invalid keys
+pub fn parse_env_lines(text: &str) -> Vec<(String, String)> {
+    return text.lines()
+        .map(str::trim)
+        .filter(|line| return !line.is_empty())
+        .filter_map(|line| {
+            let Some((key, value)) = line.split_once('=') else {
+                warn!("Ignoring environment line without '=': {line}");
+                return None;
+            };
+            if !is_env_name(key.trim()) {
+                warn!("Ignoring invalid environment variable name: {key}");
+                return None;
+            }
+            return Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+
+/// Quote a string so bash takes it literally
+fn shell_quote(text: &str) -> String {
+    return format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+
+/// Like `shell_quote`, but values the shell takes as is are left alone so the default scripts stay readable
+fn shell_word(text: &str) -> String {
+    if !text.is_empty() && text.chars().all(|c| return c.is_ascii_alphanumeric() || "_-./:,=+".contains(c)) {
+        return text.to_string()
+    }
+    return shell_quote(text)
+}
+
+
+/// Revert the changes made to the target executable
+fn revert_changes(target: &Path, wrapper_dir: &Path, wrapper_name: &str, dry_run: bool, verify: bool) -> Result<(), WrapError> {
+    // Get the path to the backup
+    let target_path = resolve_backup(target); let target_path = target_path.as_path();
+    let recorded = config::wrapped(target_path);
+    let backup_path = recorded_backup_path(target_path, recorded.as_ref());
+
+    // Check if the backup exists
+    if !backup_path.exists() {
+        return Err(WrapError::BackupMissing(target_path.to_path_buf()));
+    }
+
+    // Make sure the backup is still the original that was wrapped (wraps from before hashes were recorded can't be checked)
+    if verify {
+        if let Some(expected) = recorded.as_ref().and_then(|recorded| return recorded.sha256.as_ref()) {
+            if file_sha256(&backup_path)? != *expected {
+                return Err(WrapError::BackupMismatch(target_path.to_path_buf()));
+            }
+        }
+    }
+
+    // Wrapped as root, so it takes root to undo
+    if is_elevated(target_path) {
+        revert_elevated(target_path, &backup_path, dry_run)?;
+        // Root keeps the permissions of the backup (a hard link or a `cp -p` copy) when moving it back, only the record is left to forget
+        if !dry_run && recorded.is_some() {
+            record_wrapped(target_path, |wrapped| *wrapped = Wrapped::default())?;
+        }
+        return Ok(())
+    }
+
+    if dry_run {
+        info!("[dry run] Would remove the symlink {}", target_path.display());
+        info!("[dry run] Would move {} back to {}", backup_path.display(), target_path.display());
+        info!("[dry run] Would remove the wrapper script {}", wrapper_dir.join(wrapper_name).display());
+        return Ok(())
+    }
+
+    // Remove the symbolic link
+    if let Err(e) = fs::remove_file(target_path) {
+        error!("Failed to remove symbolic link for {}: {}", target_path.display(), e);
+        return Err(e.into());
+    }
+
+    // Restore the original executable from the backup
+    if let Err(e) = move_file(&backup_path, target_path) {
+        error!("Failed to restore original executable for {}: {}", target_path.display(), e);
+        return Err(e);
+    }
+    if let Some(recorded) = recorded {
+        if let Some(permissions) = recorded.permissions {
+            permissions.apply(target_path);
+        }
+        record_wrapped(target_path, |wrapped| *wrapped = Wrapped::default())?;
+    }
+
+    // Remove the wrapper script, unless it's the one that went missing
+    if let Err(e) = fs::remove_file(wrapper_dir.join(wrapper_name)).or_else(|e| return if e.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(e) }) {
+        error!("Failed to remove wrapper script for {}: {}", target_path.display(), e);
+        return Err(e.into());
+    }
+
+    // Remove the integrated GPU symlink and its wrapper script, if it has them
+    let igpu_wrapper_path = wrapper_dir.join(format!("{wrapper_name}{IGPU_SUFFIX}"));
+    let igpu_link = igpu_link_path(target_path);
+    if fs::read_link(&igpu_link).is_ok_and(|destination| return destination == igpu_wrapper_path) {
+        fs::remove_file(&igpu_link)?;
+    }
+    if igpu_wrapper_path.exists() {
+        fs::remove_file(&igpu_wrapper_path)?;
+    }
+
+    info!("Reverted changes for {}. Restored original executable.", target_path.display());
+    return Ok(())
+}
+
+
+/// Remove the wrapper scripts of an executable that's gone
+pub fn remove_stale_wrapper(wrapper_dir: &Path, executable_path: &Path) {
+    let wrapper_name = generate_wrapper_name(&resolve_backup(executable_path));
+    for name in [format!("{wrapper_name}{IGPU_SUFFIX}"), wrapper_name] {
+        let _ = fs::remove_file(wrapper_dir.join(name)); // Folders and executables without an integrated GPU symlink don't have them
+    }
+}
+
+
+/// Check if an executable is a symlink we left behind without its backup: pointing at its wrapper script, or straight at the backup that's gone
+pub fn is_orphaned_symlink(wrapper_dir: &Path, executable_path: &Path) -> bool {
+    let Ok(destination) = fs::read_link(executable_path) else { return false };
+    return !backup_path(executable_path).exists() && (destination.starts_with(wrapper_dir) || !executable_path.exists())
+}
+
+
+/// Remove the dangling symlink of an executable whose backup is gone, along with its wrapper scripts and integrated GPU symlink.
+/// The original can't be recovered, but at least nothing is left pointing at a wrapper that can't launch it
+pub fn remove_orphaned_symlink(wrapper_dir: &Path, executable_path: &Path) -> Result<(), WrapError> {
+    if !is_orphaned_symlink(wrapper_dir, executable_path) { return Ok(()) } // Cleaned up or restored in the meantime
+    fs::remove_file(executable_path)?;
+    let igpu_link = igpu_link_path(executable_path);
+    if is_igpu_link(&igpu_link) {
+        fs::remove_file(&igpu_link)?;
+    }
+    remove_stale_wrapper(wrapper_dir, executable_path);
+    info!("Removed the orphaned symlink {}", executable_path.display());
+    return Ok(())
+}
+
+
+/// Check if an executable is a symlink pointing at a wrapper script that's gone (e.g. a deleted wrapper folder), with its backup still there.
+/// It can't launch like this, but the original is safe
+pub fn has_missing_wrapper(executable_path: &Path) -> bool {
+    return fs::read_link(executable_path).is_ok() && !executable_path.exists() && backup_path(executable_path).exists()
+}
+
+
+/// Put the original back in place of a symlink to a wrapper script that's gone, so it's a plain executable that can be added again
+pub fn restore_missing_wrapper(wrapper_dir: &Path, executable_path: &Path) -> Result<(), WrapError> {
+    if !has_missing_wrapper(executable_path) { return Ok(()) } // Fixed in the meantime
+    let wrapper_name = generate_wrapper_name(&resolve_backup(executable_path));
+    return revert_changes(executable_path, wrapper_dir, &wrapper_name, false, true)
+}
+
+
+/// Put back the originals of executables that got moved along with their symlink and backup (e.g. a relocated Steam library),
+/// their symlinks still point at the wrapper scripts of the old location
+pub fn restore_moved(executable_path: &Path) -> Result<(), WrapError> {
+    for path in wrapped_executables(executable_path) {
+        let target_path = resolve_backup(&path);
+        let backup_path = backup_path(&target_path);
+        if !backup_path.exists() || !fs::symlink_metadata(&target_path)?.file_type().is_symlink() { continue; }
+
+        fs::remove_file(&target_path)?;
+        move_file(&backup_path, &target_path)?;
+        let igpu_link = igpu_link_path(&target_path);
+        if is_igpu_link(&igpu_link) {
+            fs::remove_file(&igpu_link)?;
+        }
+        info!("Restored the moved original of {}", target_path.display());
+    }
+    return Ok(())
+}
+
+
+/// Point the symlink of a wrapped executable either at its wrapper script (offload on) or straight at its backup (offload off)
+pub fn set_offload(wrapper_dir: &Path, executable_path: &Path, enabled: bool) -> Result<(), WrapError> {
+    if is_desktop_entry(executable_path) { return Ok(()) } // There's no symlink to point elsewhere, desktop entries stay offloaded until removed
+    let target_path = resolve_backup(executable_path); let target_path = target_path.as_path();
+    let backup_path = backup_path(target_path);
+    if !backup_path.exists() {
+        return Err(WrapError::BackupMissing(target_path.to_path_buf()));
+    }
+    // Its symlink can only be replaced by root, which isn't worth a password prompt for every flip of the switch
+    if is_elevated(target_path) {
+        return Err(WrapError::NotWritable(target_path.to_path_buf()));
+    }
+
+    // Pick where the symlink should point to
+    let destination = if enabled {
+        wrapper_dir.join(generate_wrapper_name(target_path))
+    } else {
+        backup_path
+    };
+    if fs::read_link(target_path)? == destination { return Ok(()) } // Already in the requested state
+
+    replace_symlink(target_path, &destination)?;
+
+    info!("NVIDIA offload for {} is now {}", target_path.display(), if enabled { "on" } else { "off" });
+    return Ok(())
+}
+
+
+/// Point a symlink (or turn a file into one) somewhere else by creating a temporary one next to it and renaming it over the old one, so the path never goes missing
+fn replace_symlink(link_path: &Path, destination: &Path) -> Result<(), WrapError> {
+    let temp_path = temp_sibling(link_path);
+    let _ = fs::remove_file(&temp_path); // Left over from a run that got killed
+    std::os::unix::fs::symlink(destination, &temp_path).map_err(|e| return WrapError::SymlinkFailed(temp_path.clone(), e))?;
+    if let Err(e) = fs::rename(&temp_path, link_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    return Ok(())
+}
+
+
+/// Move every wrapper script to a new directory and re-point the symlinks of the given wrapped executables at it
+pub fn relocate_wrappers(old_dir: &Path, new_dir: &Path, executables: &[PathBuf]) -> Result<(), WrapError> {
+    fs::create_dir_all(new_dir)?;
+
+    // Copy the wrapper scripts over first (fs::copy keeps the permissions), so the old ones keep working until the symlinks are switched
+    for entry in fs::read_dir(old_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::copy(entry.path(), new_dir.join(entry.file_name()))?;
+        }
+    }
+
+    // Re-point the symlinks that go into the old directory, masked ones point at their backup and are left alone
+    for executable in executables {
+        let target_path = resolve_backup(executable);
+        for link_path in [igpu_link_path(&target_path), target_path] {
+            let Ok(destination) = fs::read_link(&link_path) else { continue; };
+            if let (true, Some(name)) = (destination.starts_with(old_dir), destination.file_name()) {
+                replace_symlink(&link_path, &new_dir.join(name))?;
+            }
+        }
+    }
+
+    // Nothing points at the old scripts anymore
+    for entry in fs::read_dir(old_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    info!("Moved the wrapper scripts from {} to {}", old_dir.display(), new_dir.display());
+    return Ok(())
+}
+
+
+/// Get the mount options of the filesystem a path lives on, from `/proc/mounts`
+pub fn mount_options_for(path: &Path) -> Vec<String> {
+    let path = path.canonicalize().unwrap_or_else(|_| return path.to_path_buf());
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+
+    // The mount point with the longest matching prefix is the one the path lives on
+    return mounts.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = unescape_mount_field(fields.nth(1)?);
+            let options = fields.nth(1)?;
+            return Some((PathBuf::from(mount_point), options))
+        })
+        .filter(|(mount_point, _)| return path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| return mount_point.components().count())
+        .map(|(_, options)| return options.split(',').map(ToString::to_string).collect())
+        .unwrap_or_default()
+}
+
+
+/// Decode the octal escapes (`\040` for spaces and such) used in `/proc/mounts`
+fn unescape_mount_field(field: &str) -> String {
+    return Regex::new(r"\\([0-7]{3})").unwrap()
+        .replace_all(field, |captures: &regex::Captures| {
+            let code = u8::from_str_radix(&captures[1], 8).unwrap_or(b'?');
+            return char::from(code).to_string()
+        })
+        .into_owned()
+}
+
+
+/// Check if a wrapped executable currently has its offload switched off (its symlink points at the backup)
+pub fn is_offload_masked(executable_path: &Path) -> bool {
+    let target_path = resolve_backup(executable_path);
+    return fs::read_link(&target_path).is_ok_and(|destination| return destination == backup_path(&target_path))
+}
+
+
+/// What's actually on disk for an added executable
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapperState {
+    /// Wrapped, with the offload on
+    Enabled,
+    /// Wrapped with the offload switched off, or not wrapped at all
+    Disabled,
+    /// Half wrapped, a symlink without its backup or a backup without its symlink
+    Broken,
+}
+
+impl WrapperState {
+    pub fn label(self) -> &'static str {
+        return match self {
+            WrapperState::Enabled => "Enabled",
+            WrapperState::Disabled => "Disabled",
+            WrapperState::Broken => "Broken",
+        }
+    }
+}
+
+
+/// Check the state of an executable on disk, a folder is only as good as its worst executable
+pub fn wrapper_state(executable_path: &Path) -> WrapperState {
+    if executable_path.is_dir() {
+        let states: Vec<WrapperState> = wrapped_executables(executable_path).iter().map(|path| return wrapper_state(path)).collect();
+        if states.is_empty() { return WrapperState::Disabled }
+        if states.contains(&WrapperState::Broken) { return WrapperState::Broken }
+        if states.contains(&WrapperState::Disabled) { return WrapperState::Disabled }
+        return WrapperState::Enabled
+    }
+
+    if is_desktop_entry(executable_path) {
+        return if is_desktop_entry_offloaded(executable_path) { WrapperState::Enabled } else { WrapperState::Disabled }
+    }
+
+    let target_path = resolve_backup(executable_path);
+    let is_symlink = fs::symlink_metadata(&target_path).is_ok_and(|metadata| return metadata.file_type().is_symlink());
+    return match (is_symlink, backup_path(&target_path).exists()) {
+        (true, true) if !target_path.exists() => WrapperState::Broken, // The wrapper script is gone
+        (true, true) if is_offload_masked(&target_path) => WrapperState::Disabled,
+        (true, true) => WrapperState::Enabled,
+        (false, false) => WrapperState::Disabled,
+        _ => WrapperState::Broken,
+    }
+}
+
+
+/// Wrapper scripts are tiny, anything bigger than this can't be one
+const MAX_WRAPPER_SIZE: u64 = 64 * 1024;
+
+
+/// Check if the symlink of a wrapped executable got replaced by a copy of its wrapper script (some backup/sync tools dereference symlinks)
+pub fn is_dereferenced_wrapper(wrapper_dir: &Path, executable_path: &Path) -> bool {
+    let target_path = resolve_backup(executable_path);
+    let Ok(metadata) = fs::symlink_metadata(&target_path) else { return false };
+    if !metadata.is_file() || metadata.len() > MAX_WRAPPER_SIZE || !backup_path(&target_path).exists() { return false }
+    let Ok(content) = fs::read_to_string(&target_path) else { return false }; // The original binary wouldn't be valid UTF-8
+
+    // Either it's the exact wrapper script, or at least a wrapper of ours that runs this backup (in case the script was changed or is gone)
+    let script = fs::read_to_string(wrapper_dir.join(generate_wrapper_name(&target_path))).unwrap_or_default();
+    // Scripts from before the path got single quoted still have it in double quotes
+    let backup = backup_path(&target_path).display().to_string();
+    return content == script
+        || (has_wrapper_shebang(&content) && content.lines().nth(1).is_some_and(|line| return line.starts_with("export ")) && [shell_quote(&backup), format!("\"{backup}\"")].iter().any(|quoted| return content.contains(&format!("{quoted} \"$@\""))))
+}
+
+
+/// Turn a copy of a wrapper script back into the symlink it should be, restoring the script from the copy if it's gone
+pub fn relink_wrapper(wrapper_dir: &Path, executable_path: &Path) -> Result<(), WrapError> {
+    let target_path = resolve_backup(executable_path);
+    let wrapper_path = wrapper_dir.join(generate_wrapper_name(&target_path));
+    if !wrapper_path.exists() {
+        fs::copy(&target_path, &wrapper_path)?; // fs::copy keeps the permissions
+    }
+    replace_symlink(&target_path, &wrapper_path)?;
+
+    info!("Restored the symlink of {} to its wrapper script", target_path.display());
+    return Ok(())
+}
+
+
+/// Get the wrapped executables behind a config entry (the entry itself, or every wrapped executable inside it if it's a directory)
+pub fn wrapped_executables(entry: &Path) -> Vec<PathBuf> {
+    if !entry.is_dir() { return vec![entry.to_path_buf()] }
+    return find_executables(entry, &ScanOptions::default()) // Anything wrapped before the scan options changed must still be found
+        .into_iter()
+        .filter(|path| return path.extension().and_then(|ext| return ext.to_str()) != Some("bak") && backup_path(path).exists())
+        .collect()
+}
+
+
+/// Folders a manual setup likely wrapped executables in, `~/.local/bin` and every Steam library
+pub fn common_scan_dirs() -> Vec<PathBuf> {
+    let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else { return Vec::new() };
+    let mut dirs = vec![home.join(".local/bin")];
+
+    // Steam lists its libraries (including the default one) in libraryfolders.vdf
+    let path_regex = Regex::new(r#""path"\s+"([^"]+)""#).unwrap();
+    let mut libraries = HashSet::new();
+    for steam_root in [home.join(".local/share/Steam"), home.join(".steam/steam")] {
+        libraries.insert(steam_root.canonicalize().unwrap_or_else(|_| return steam_root.clone()));
+        let library_folders = fs::read_to_string(steam_root.join("steamapps/libraryfolders.vdf")).unwrap_or_default();
+        for captures in path_regex.captures_iter(&library_folders) {
+            let library = PathBuf::from(captures[1].replace("\\\\", "\\"));
+            libraries.insert(library.canonicalize().unwrap_or(library));
+        }
+    }
+    dirs.extend(libraries.into_iter().map(|library| return library.join("steamapps/common")));
+
+    return dirs.into_iter().filter(|dir| return dir.is_dir()).collect()
+}
+
+
+/// Find executables that are already wrapped (a symlink to something that looks like a wrapper script, with a backup next to it), e.g. by a manual setup
+pub fn find_wrapped_symlinks(dirs: &[PathBuf], wrapper_dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for dir in dirs {
+        for entry in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
+            if !entry.path_is_symlink() || is_igpu_link(entry.path()) { continue; }
+            let Ok(destination) = fs::read_link(entry.path()) else { continue; };
+            let looks_like_wrapper = destination.starts_with(wrapper_dir)
+                || destination.file_name().and_then(|name| return name.to_str()).is_some_and(|name| return name.starts_with("wrapper_"));
+            if looks_like_wrapper && backup_path(entry.path()).exists() {
+                found.push(entry.into_path());
+            }
+        }
+    }
+    return found
+}
+
+
+/// Get the path to the backup file
+pub fn backup_path(path: &Path) -> PathBuf {
+    return recorded_backup_path(path, config::wrapped(path).as_ref())
+}
+
+
+/// Get the path to the backup file from what's recorded about the executable.
+/// Backups kept in the central directory are recorded on its config entry, everything else is next to the original
+pub fn recorded_backup_path(path: &Path, recorded: Option<&Wrapped>) -> PathBuf {
+    if let Some(backup_path) = recorded.and_then(|recorded| return recorded.backup.clone()) {
+        return backup_path
+    }
+
+    // ".bak" gets added to the whole name, even one already ending in ".bak" (that's an executable of its own, not a backup).
+    // Not through `with_extension`, which only sees the part after the last dot (and none at all for dotfiles)
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push(BACKUP_SUFFIX);
+    return PathBuf::from(backup_path)
+}
+
+
+/// What's added to the name of an executable for its backup next to it
+const BACKUP_SUFFIX: &str = ".bak";
+
+
+/// Check if a path ends in ".bak" with a name in front of it (a file called just ".bak" isn't a backup of anything)
+fn has_backup_suffix(path: &Path) -> bool {
+    return path.file_name().is_some_and(|name| return name.len() > BACKUP_SUFFIX.len() && name.as_bytes().ends_with(BACKUP_SUFFIX.as_bytes()))
+}
+
+
+/// Change what's recorded about a wrapped executable on its config entry
+fn record_wrapped(original_path: &Path, change: impl FnOnce(&mut Wrapped)) -> Result<(), WrapError> {
+    return config::record_wrapped(original_path, change).map_err(|e| return WrapError::Record(e.to_string()))
+}
+
+
+/// The mode and ownership of an original, which a backup copied across filesystems doesn't keep
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OriginalPermissions {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+}
+
+impl OriginalPermissions {
+    fn of(metadata: &fs::Metadata) -> Self {
+        return OriginalPermissions { mode: metadata.mode() & 0o7777, uid: metadata.uid(), gid: metadata.gid() }
+    }
+
+    /// Give a file these permissions, only complaining if it doesn't work since the file itself is fine either way.
+    /// Ownership goes first, changing it clears the setuid and setgid bits
+    fn apply(self, path: &Path) {
+        let Ok(metadata) = fs::metadata(path) else { return };
+        if (metadata.uid(), metadata.gid()) != (self.uid, self.gid) {
+            if let Err(e) = std::os::unix::fs::chown(path, Some(self.uid), Some(self.gid)) {
+                error!("Failed to give {} back its owner {}:{}: {e}", path.display(), self.uid, self.gid);
+            }
+        }
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(self.mode)) {
+            error!("Failed to give {} back its mode {:o}: {e}", path.display(), self.mode);
+        }
+    }
+}
+
+
+/// Get the SHA-256 of a file as hex
+fn file_sha256(path: &Path) -> Result<String, WrapError> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    return Ok(format!("{:x}", hasher.finalize()))
+}
+
+
+/// Get a name for the central backup (or bind mounted original) of an executable that won't clash with other executables.
+/// SHA-256 since it has to stay the same across versions, which `DefaultHasher` doesn't promise
+fn path_hash(path: &Path) -> String {
+    return format!("{:x}", Sha256::digest(path.as_os_str().as_bytes()))
+}
+
+
+/// Move a file, falling back to copying it when it has to cross filesystems (central backups often do)
+fn move_file(from: &Path, to: &Path) -> Result<(), WrapError> {
+    if fs::rename(from, to).is_ok() { return Ok(()) }
+    fs::copy(from, to)?; // fs::copy keeps the permissions
+    fs::remove_file(from)?;
+    return Ok(())
+}
+
+
+/// Put a copy of a file somewhere, as a hard link when it's on the same filesystem since that's instant and takes no space
+fn link_or_copy(from: &Path, to: &Path) -> Result<(), WrapError> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(WrapError::AlreadyExists(to.to_path_buf()));
+    }
+    if fs::hard_link(from, to).is_ok() { return Ok(()) }
+
+    // A half copied backup would pass for a wrapped executable, so it only shows up once it's complete
+    let temp_path = temp_sibling(to);
+    if let Err(e) = fs::copy(from, &temp_path).and_then(|_| return fs::rename(&temp_path, to)) { // fs::copy keeps the permissions
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    return Ok(())
+}
+
+
+/// Make sure the executable can be swapped for a symlink by renaming one over it, before anything is changed.
+/// Renaming over a file that is a mount point of its own fails (it's on another device than its folder), and some FUSE/network filesystems can't hold symlinks at all.
+/// The backup doesn't need checking, it falls back to copying when it can't be hard linked
+fn check_symlink_target(target_path: &Path, wrapper_path: &Path) -> Result<(), WrapError> {
+    let folder = target_path.parent().unwrap_or(Path::new("/"));
+    if fs::metadata(target_path)?.dev() != fs::metadata(folder)?.dev() {
+        return Err(WrapError::MountPoint(target_path.to_path_buf()));
+    }
+
+    // Create a throwaway symlink where the real one will be created, some filesystems accept it but store something else
+    let probe = temp_sibling(target_path);
+    let _ = fs::remove_file(&probe);
+    let result = std::os::unix::fs::symlink(wrapper_path, &probe).and_then(|()| return fs::read_link(&probe));
+    let _ = fs::remove_file(&probe);
+    return match result {
+        Ok(destination) if destination == wrapper_path => Ok(()),
+        Ok(destination) => Err(WrapError::SymlinksUnsupported(folder.to_path_buf(), io::Error::other(format!("a test symlink came back pointing at {}", destination.display())))),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Err(WrapError::NotWritable(target_path.to_path_buf())),
+        Err(e) => Err(WrapError::SymlinksUnsupported(folder.to_path_buf(), e)),
+    }
+}
+
+/// A hidden path next to the given one, for creating something before renaming it into place
+pub(crate) fn temp_sibling(path: &Path) -> PathBuf {
+    return path.with_file_name(format!(".{}.nvidia_manager_tmp", path.file_name().unwrap_or_default().to_string_lossy()))
+}
+
+
+/// The executable a path is the backup of, or the path as is if it isn't one.
+/// Only a ".bak" next to a wrapped executable (a symlink) counts, so an executable that just happens to end in ".bak" isn't mistaken for a backup
+pub fn resolve_backup(path: &Path) -> PathBuf {
+    let original = original_path(path);
+    if original != path && fs::symlink_metadata(&original).is_ok_and(|metadata| return metadata.file_type().is_symlink()) {
+        return original
+    }
+    return path.to_path_buf()
+}
+
+
+/// Get the path to the original file
+fn original_path(path: &Path) -> PathBuf {
+    // Only the ".bak" suffix comes off, whatever else is in the name (`.so.1`, `.tar.gz`) stays
+    if !has_backup_suffix(path) {
+        return path.to_path_buf()
+    }
+    let bytes = path.as_os_str().as_bytes();
+    return PathBuf::from(OsStr::from_bytes(&bytes[..bytes.len() - BACKUP_SUFFIX.len()]))
+}
+
+
+/// Add the execute bits to a file's mode, like `chmod +x`
+fn make_executable(path: &Path) -> Result<(), WrapError> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    fs::set_permissions(path, permissions)?;
+    return Ok(())
+}
+
+
+/// Check if a file is executable
+fn is_executable(file_path: &Path) -> bool {
+    return match fs::metadata(file_path) {
+        Ok(metadata) => {
+            let permissions = metadata.permissions();
+            permissions.mode() & 0o111 != 0 // Check if any executable bit is set
+        },
+        Err(_) => false,
+    }
+}
+
+
+/// Folders that usually only hold installers, redistributables or bundled dependencies, skipped when wrapping a whole folder
+pub const DEFAULT_EXCLUDE_GLOBS: [&str; 7] = ["redist", "_CommonRedist", "__Installer", "Redistributables", "vcredist*", "DirectX", "node_modules"];
+
+
+/// Turn a glob into a regex, `*` and `?` stay within a path component while `**` crosses them.
+/// Globs without a `/` match any single component (so `redist` skips every folder called redist, however deep), the others match from the top and include everything below
+fn glob_regex(glob: &str) -> Option<Regex> {
+    let glob = glob.trim().trim_matches('/');
+    if glob.is_empty() { return None }
+
+    let mut pattern = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            },
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            character => pattern.push_str(&regex::escape(&character.to_string())),
+        }
+    }
+
+    // Game folders come from Windows more often than not, so case doesn't matter
+    let pattern = if glob.contains('/') { format!("(?i)^{pattern}(/.*)?$") } else { format!("(?i)(^|/){pattern}(/|$)") };
+    return match Regex::new(&pattern) {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            warn!("Ignoring invalid exclude glob {glob}: {e}");
+            None
+        }
+    }
+}
+
+
+/// What gets skipped when wrapping a whole folder
+#[derive(Clone, Debug, Default)]
+pub struct ScanOptions {
+    /// Globs of the subfolders to skip
+    pub exclude_globs: Vec<String>,
+    /// How many folders deep to look, 1 being only the folder itself. `None` looks all the way down
+    pub max_depth: Option<usize>,
+    /// Skip hidden files and folders, the ones starting with a dot
+    pub skip_hidden: bool,
+    /// Paths never wrapped, which also goes for the real files of symlinks
+    pub system_paths: Vec<String>,
+}
+
+
+/// Find executable files inside a directory and its subdirectories, skipping whatever the scan options say to skip
+fn find_executables(directory: &Path, scan: &ScanOptions) -> Vec<PathBuf> {
+    let mut executables = Vec::new();
+    let excludes: Vec<Regex> = scan.exclude_globs.iter().filter_map(|glob| return glob_regex(glob)).collect();
+
+    let mut walker = WalkDir::new(directory);
+    if let Some(max_depth) = scan.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let walker = walker.into_iter().filter_entry(|entry| {
+        // The folder itself is always looked into, even if it's hidden
+        if entry.depth() == 0 { return true }
+        if scan.skip_hidden && entry.file_name().to_string_lossy().starts_with('.') { return false }
+        let Ok(relative_path) = entry.path().strip_prefix(directory) else { return true };
+        let Some(relative_path) = relative_path.to_str() else { return true };
+        return !excludes.iter().any(|regex| return regex.is_match(relative_path))
+    });
+    // Symlinked folders aren't followed (the WalkDir default), so a symlink back up the tree can't make it loop
+    let mut candidates: Vec<(bool, PathBuf)> = walker.filter_map(Result::ok)
+        .filter(|entry| return !has_backup_suffix(entry.path()))
+        .filter(|entry| return entry.path().is_file() && is_executable(entry.path()))
+        .map(|entry| return (entry.path_is_symlink(), entry.into_path()))
+        .collect();
+
+    // The same file reached through another symlink in the tree is only picked once, and as the real file rather than the symlink.
+    // Our wrapped symlinks resolve to their own wrapper script, so each of them still counts
+    candidates.sort_by_key(|(is_symlink, _)| return *is_symlink);
+    let mut visited = HashSet::new();
+    for (_, path) in candidates {
+        if visited.insert(path.canonicalize().unwrap_or_else(|_| return path.clone())) {
+            executables.push(path);
+        }
+    }
+
+    return executables
+}
+
+
+/// Resolve a bare command name against `$PATH`, refusing commands that aren't found or found as different executables
+pub fn resolve_command(name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.contains('/') {
+        warn!("{name} is not a bare command name");
+        return None;
+    }
+
+    // Find every executable with that name on PATH, the same file reached through different directories only counts once
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut seen = HashSet::new();
+    for dir in std::env::split_paths(&path_var) {
+        let candidate = dir.join(name);
+        if !candidate.is_file() || !is_executable(&candidate) { continue; }
+        if seen.insert(candidate.canonicalize().unwrap_or_else(|_| return candidate.clone())) {
+            found.push(candidate);
+        }
+    }
+
+    return match found.len() {
+        0 => {
+            warn!("Command {name} was not found on PATH");
+            None
+        },
+        1 => found.pop(),
+        _ => {
+            let found: Vec<String> = found.iter().map(|path| return path.display().to_string()).collect();
+            warn!("Command {name} is ambiguous, it resolves to: {}", found.join(", "));
+            None
+        }
+    }
+}
+
+
+/// The PID and raw executable path of every running process, and how many of our own processes couldn't be read
+pub type ProcessExecutables = (Vec<(i32, PathBuf)>, usize);
+
+/// Somewhere to get the running processes from
+pub trait ProcessSource {
+    /// Get the PID and raw executable path of every running process, as the kernel reports them,
+    /// along with how many of our own processes it wasn't allowed to read the executable of
+    fn executables(&self) -> Result<ProcessExecutables, Box<dyn Error>>;
+    /// Get what a process calls itself, only asked for the ones that are new since the last refresh
+    fn name(&self, pid: i32) -> Option<String>;
+}
+
+
+/// The real running processes, read from procfs
+pub struct Procfs;
+
+impl ProcessSource for Procfs {
+    fn executables(&self) -> Result<ProcessExecutables, Box<dyn Error>> {
+        let own_uid = Process::myself().and_then(|myself| return myself.uid()).ok();
+        let mut executables = Vec::new();
+        let mut denied = 0;
+        for proc in all_processes()?.filter_map(Result::ok) { // Processes that exited while being listed are skipped
+            match proc.exe() {
+                Ok(exe_path) => executables.push((proc.pid(), exe_path)),
+                // Other users' processes (system services and such) are never readable and couldn't be wrapped anyway, only our own are surprising
+                Err(ProcError::PermissionDenied(_)) if proc.uid().ok().is_some_and(|uid| return Some(uid) == own_uid) => denied += 1,
+                Err(_) => {}, // Kernel threads and zombies don't have an executable
+            }
+        }
+        return Ok((executables, denied))
+    }
+
+    fn name(&self, pid: i32) -> Option<String> {
+        // comm is cut off at 15 characters, but it's what the process calls itself (e.g. Electron apps renaming themselves)
+        return Process::new(pid).and_then(|proc| return proc.stat()).map(|stat| return stat.comm).ok()
+    }
+}
+
+
+/// The processes running a single executable
+#[derive(Debug, Clone, Default)]
+pub struct RunningExecutable {
+    pub name: String,
+    pub pids: Vec<i32>,
+}
+
+impl RunningExecutable {
+    /// How the executable is shown in the process list, e.g. `firefox (1234, 1250) — /usr/lib/firefox/firefox`
+    pub fn label(&self, path: &str) -> String {
+        let pids: Vec<String> = self.pids.iter().map(ToString::to_string).collect();
+        return format!("{} ({}) — {path}", self.name, pids.join(", "))
+    }
+}
+
+
+/// PIDs of every process nvidia-smi lists as running on the NVIDIA GPU, graphics and compute alike
+pub fn nvidia_pids() -> Result<HashSet<i32>, Box<dyn Error>> {
+    // `--query-compute-apps` leaves out graphics apps, so go through the process table of the plain output instead
+    let output = Command::new("nvidia-smi").stderr(Stdio::null()).output()?;
+    if !output.status.success() {
+        return Err(format!("nvidia-smi exited with {}", output.status).into());
+    }
+
+    // The rows look like `|    0   N/A  N/A      1234      G   /usr/bin/game    42MiB |`, the PID is right before the type
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    return Ok(
+        stdout.lines()
+            .skip_while(|line| return !line.contains("Processes:"))
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let type_index = fields.iter().position(|field| return matches!(*field, "G" | "C" | "C+G"))?;
+                return fields.get(type_index.checked_sub(1)?)?.parse().ok()
+            })
+            .collect()
+    )
+}
+
+
+/// How long a test launch gets to show up on the NVIDIA GPU, apps can take a while before they create their GL/Vulkan context
+const TEST_LAUNCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How a test launch went
+#[derive(Debug)]
+pub enum TestLaunch {
+    /// It (or something it started) showed up in nvidia-smi
+    OnNvidia,
+    /// Still running, but it never showed up in nvidia-smi
+    NotOnNvidia,
+    /// Exited before it showed up in nvidia-smi
+    Exited(ExitStatus),
+    /// Started, but there's no nvidia-smi to check with
+    Unchecked,
+}
+
+/// Launch an added executable the way anything else would (through its symlink or launcher) and watch nvidia-smi until it or one of its children shows up on the NVIDIA GPU.
+/// Blocks until it's known how it went, the app is left running
+pub fn test_launch(path: &Path) -> io::Result<TestLaunch> {
+    let mut child = Command::new(path)
+        .current_dir(path.parent().unwrap_or(Path::new("/")))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let pid = i32::try_from(child.id()).unwrap_or_default();
+    let check = on_path("nvidia-smi");
+
+    let started = Instant::now();
+    let result = loop {
+        thread::sleep(Duration::from_millis(500));
+        match child.try_wait() {
+            Ok(Some(status)) => break TestLaunch::Exited(status),
+            Ok(None) => {},
+            Err(e) => warn!("Failed to check if the test launch of {} is still running: {e}", path.display()),
+        }
+        if !check { break TestLaunch::Unchecked }
+        match nvidia_pids() {
+            Ok(nvidia_pids) if !nvidia_pids.is_disjoint(&process_tree(pid)) => break TestLaunch::OnNvidia,
+            Ok(_) => {},
+            Err(e) => {
+                warn!("Couldn't check if the test launch of {} is on the NVIDIA GPU: {e}", path.display());
+                break TestLaunch::Unchecked
+            },
+        }
+        if started.elapsed() >= TEST_LAUNCH_TIMEOUT { break TestLaunch::NotOnNvidia }
+    };
+
+    // Reap it whenever it gets closed
+    thread::spawn(move || { let _ = child.wait(); });
+    return Ok(result)
+}
+
+/// The process and everything it started, apps often hand the rendering to a child process
+fn process_tree(pid: i32) -> HashSet<i32> {
+    let parents: Vec<(i32, i32)> = all_processes().into_iter().flatten()
+        .filter_map(Result::ok)
+        .filter_map(|proc| return proc.stat().ok().map(|stat| return (stat.pid, stat.ppid)))
+        .collect();
+
+    // Parents can come after their children in the list, so go over it until nothing new turns up
+    let mut tree = HashSet::from([pid]);
+    loop {
+        let before = tree.len();
+        tree.extend(parents.iter().filter(|(_, ppid)| return tree.contains(ppid)).map(|(pid, _)| return *pid).collect::<Vec<i32>>());
+        if tree.len() == before { return tree }
+    }
+}
+
+
+/// Added entries that are running right now but not on the NVIDIA GPU, meaning the wrapper got bypassed somehow
+pub fn entries_off_nvidia(entries: &HashSet<String>) -> Result<HashSet<String>, Box<dyn Error>> {
+    let nvidia_pids = nvidia_pids()?;
+
+    // A wrapped executable runs as its backup once the wrapper execs it, or as itself when something bypassed the symlink
+    let mut entry_of = HashMap::new();
+    for entry in entries {
+        for path in wrapped_executables(Path::new(entry)) {
+            entry_of.insert(backup_path(&path), entry.clone());
+            entry_of.insert(path, entry.clone());
+        }
+    }
+
+    return Ok(
+        all_processes()?
+            .filter_map(Result::ok)
+            .filter(|proc| return !nvidia_pids.contains(&proc.pid()))
+            .filter_map(|proc| return proc.exe().ok())
+            .filter_map(|exe_path| return entry_of.get(&strip_deleted_suffix(&exe_path)).cloned())
+            .collect()
+    )
+}
+
+
+/// What was found out about a process the last time the processes were read
+struct CachedProcess {
+    /// The executable as the kernel reported it, a process that execs something else gets looked at again
+    raw_exe_path: PathBuf,
+    name: String,
+    /// Where it runs from, `None` if that can't be wrapped (gone, not writable unless that's allowed, or a system path)
+    exe_path: Option<String>,
+}
+
+/// The running processes, read again on every refresh but only looked at closely when they're new or exec'd something else since the last one.
+/// A process still running the same executable keeps its name and whether it can be wrapped, so it only costs the readlink of its exe and not
+/// reading its stat, checking its executable on disk and matching the system paths again. Processes that are gone are dropped.
+/// With 300 processes, a refresh after one of them exec'd something else, one exited and two started examines 3 of them instead of 301
+#[derive(Default)]
+pub struct ProcessCache {
+    processes: HashMap<i32, CachedProcess>,
+    system_paths: Vec<String>,
+    writable_only: bool,
+    hidden: usize,
+}
+
+impl ProcessCache {
+    /// Get all executable paths of running processes, along with the processes running each of them.
+    /// Executables we can't write to are left out with `writable_only`, they can only be wrapped as root
+    pub fn executables(&mut self, source: &impl ProcessSource, system_paths: &[String], writable_only: bool) -> Result<HashMap<String, RunningExecutable>, Box<dyn Error>> {
+        // Which executables can be wrapped depends on these
+        if self.system_paths != system_paths || self.writable_only != writable_only {
+            self.processes.clear();
+            system_paths.clone_into(&mut self.system_paths);
+            self.writable_only = writable_only;
+        }
+
+        let mut processes = HashMap::new();
+        let mut examined = 0;
+        let (executables, hidden) = source.executables()?;
+        self.hidden = hidden;
+        for (pid, raw_exe_path) in executables {
+            let process = match self.processes.remove(&pid) {
+                Some(cached) if cached.raw_exe_path == raw_exe_path => cached,
+                _ => {
+                    examined += 1;
+                    examine_process(source, pid, raw_exe_path, system_paths, writable_only)
+                },
+            };
+            processes.insert(pid, process);
+        }
+        debug!("Read {} processes, {examined} of them new or changed, {} gone since the last refresh, {hidden} of ours not readable", processes.len(), self.processes.len());
+        self.processes = processes; // Whatever wasn't taken out of the old ones isn't running anymore
+
+        let mut executables: HashMap<String, RunningExecutable> = HashMap::new();
+        for (pid, process) in &self.processes {
+            let Some(exe_path) = &process.exe_path else { continue };
+            // Several processes (e.g. a browser and its content processes) share one executable, they get grouped under it
+            let running = executables.entry(exe_path.clone()).or_default();
+            // The lowest PID names it, that's usually the main process that started the others
+            if running.pids.iter().all(|other| return other > pid) {
+                running.name.clone_from(&process.name);
+            }
+            running.pids.push(*pid);
+        }
+        for running in executables.values_mut() {
+            running.pids.sort_unstable();
+        }
+        return Ok(executables)
+    }
+
+    /// How many of our own processes were left out of the last refresh because their executable couldn't be read,
+    /// e.g. ones that made themselves undumpable (some anti-cheats do) or that run setuid
+    pub fn hidden(&self) -> usize {
+        return self.hidden
+    }
+}
+
+/// Find out what a process that wasn't seen before runs, and if that can be wrapped
+fn examine_process(source: &impl ProcessSource, pid: i32, raw_exe_path: PathBuf, system_paths: &[String], writable_only: bool) -> CachedProcess {
+    let exe_path = strip_deleted_suffix(&raw_exe_path); // Processes whose binary got replaced (e.g. after an update) point at the new one
+    let name = source.name(pid)
+        .or_else(|| return exe_path.file_name().map(|name| return name.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    // Check if the path exists, we have write access, and is not a system path
+    let wrappable = exe_path.exists() && (!writable_only || has_write_access(&exe_path)) && system_path_match(&exe_path, system_paths).is_none();
+    return CachedProcess { name, exe_path: exe_path.to_str().filter(|_| return wrappable).map(ToString::to_string), raw_exe_path }
+}
+
+
+/// Sort executables by when they were last used (the latest of their access and modification times), most recent first
+pub fn sort_by_last_used(executables: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut executables: Vec<(String, SystemTime)> = executables.into_iter()
+        .map(|path| {
+            // atime is only updated lazily (relatime) or not at all (noatime), so the mtime helps out
+            let last_used = fs::metadata(&path)
+                .map_or(SystemTime::UNIX_EPOCH, |metadata| return metadata.accessed().into_iter().chain(metadata.modified()).max().unwrap_or(SystemTime::UNIX_EPOCH));
+            return (path, last_used)
+        })
+        .collect();
+    executables.sort_by(|(path_a, time_a), (path_b, time_b)| return time_b.cmp(time_a).then_with(|| return path_a.cmp(path_b)));
+    return executables.into_iter().map(|(path, _)| return path).collect()
+}
+
+
+/// Strip the " (deleted)" suffix the kernel adds to the exe link of processes whose binary was removed or replaced
+pub fn strip_deleted_suffix(exe_path: &Path) -> PathBuf {
+    return match exe_path.to_str().and_then(|path_str| return path_str.strip_suffix(" (deleted)")) {
+        Some(stripped) => PathBuf::from(stripped),
+        None => exe_path.to_path_buf(),
+    }
+}
+
+
+/// Paths that are never wrapped or listed, the package manager's (or the image's on immutable distros) and not ours to touch
+pub const DEFAULT_SYSTEM_PATHS: [&str; 9] = ["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/opt", "/nix/store", "/snap"];
+
+
+/// Find the entry of the blocklist a path is in, matching whole folders so `/usr` doesn't match `/usrdata`
+pub fn system_path_match(path: &Path, system_paths: &[String]) -> Option<String> {
+    return system_paths.iter()
+        .map(|prefix| return prefix.trim())
+        .find(|prefix| return !prefix.is_empty() && path.starts_with(prefix))
+        .map(ToString::to_string)
+}
+
+
+/// Check if a path has write access
+fn has_write_access(path: &Path) -> bool {
+    return match fs::metadata(path) {
+        Ok(metadata) => {
+            let permissions = metadata.permissions();
+            permissions.mode() & 0o200 != 0 // Check if user write bit is set
+        },
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::os::unix::fs::symlink;
+    use std::sync::Once;
+
+    use super::*;
+    use crate::config::Config;
+
+    /// A fresh folder for a test to work in. The config and everything else kept in the data folder go to a throwaway one too
+    fn test_folder(name: &str) -> PathBuf {
+        static DATA_HOME: Once = Once::new();
+        DATA_HOME.call_once(|| std::env::set_var("XDG_DATA_HOME", std::env::temp_dir().join(format!("nvidia_manager_test_data_{}", std::process::id()))));
+        let folder = std::env::temp_dir().join(format!("nvidia_manager_test_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        // Wrapping goes by the real location, which the temporary folder may be a symlink to
+        return folder.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn backup_path_appends_bak() {
+        assert_eq!(backup_path(Path::new("/games/game")), Path::new("/games/game.bak"));
+        assert_eq!(backup_path(Path::new("/games/game.x86_64")), Path::new("/games/game.x86_64.bak"));
+        assert_eq!(backup_path(Path::new("/games/game.tar.gz")), Path::new("/games/game.tar.gz.bak"));
+        assert_eq!(backup_path(Path::new("/games/libfoo.so.1")), Path::new("/games/libfoo.so.1.bak"));
+        assert_eq!(backup_path(Path::new("/home/user/.game")), Path::new("/home/user/.game.bak"));
+    }
+
+    #[test]
+    fn original_path_strips_bak() {
+        assert_eq!(original_path(Path::new("/games/game.bak")), Path::new("/games/game"));
+        assert_eq!(original_path(Path::new("/games/game.x86_64.bak")), Path::new("/games/game.x86_64"));
+        assert_eq!(original_path(Path::new("/games/game.tar.gz.bak")), Path::new("/games/game.tar.gz"));
+        assert_eq!(original_path(Path::new("/home/user/.game.bak")), Path::new("/home/user/.game"));
+    }
+
+    #[test]
+    fn original_path_leaves_other_paths_alone() {
+        for path in ["/games/game", "/games/game.x86_64", "/games/game.tar.gz", "/games/libfoo.so.1", "/home/user/.game"] {
+            assert_eq!(original_path(Path::new(path)), Path::new(path));
+        }
+    }
+
+    #[test]
+    fn backup_path_round_trips() {
+        for path in ["/games/game", "/games/game.x86_64", "/games/game.tar.gz", "/games/libfoo.so.1", "/home/user/.game", "/games/my game/run.sh"] {
+            assert_eq!(original_path(&backup_path(Path::new(path))), Path::new(path), "{path}");
+        }
+    }
+
+    #[test]
+    fn backup_path_of_a_bak_name() {
+        assert_eq!(backup_path(Path::new("/games/game.bak")), Path::new("/games/game.bak.bak"));
+        assert_eq!(original_path(&backup_path(Path::new("/games/game.bak"))), Path::new("/games/game.bak"));
+    }
+
+    #[test]
+    fn resolve_backup_needs_a_wrapped_executable() {
+        let folder = test_folder("resolve_backup");
+        // On its own it's an executable that happens to end in .bak
+        fs::write(folder.join("game.bak"), "").unwrap();
+        assert_eq!(resolve_backup(&folder.join("game.bak")), folder.join("game.bak"));
+        // Next to the symlink of a wrapped executable it's the backup of that
+        symlink("game.bak", folder.join("game")).unwrap();
+        assert_eq!(resolve_backup(&folder.join("game.bak")), folder.join("game"));
+        assert_eq!(resolve_backup(&folder.join("game")), folder.join("game"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn wrapping_a_bak_name_keeps_the_original() {
+        let folder = test_folder("wrap_bak_name");
+        let wrapper_dir = folder.join("wrappers");
+        fs::create_dir(&wrapper_dir).unwrap();
+        let executable = folder.join("game.bak");
+        write_executable(&executable, "#!/bin/sh\necho game\n", 0o755).unwrap();
+
+        let wrapped = execute(&wrapper_dir, &executable, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions::default()).unwrap();
+        assert!(!wrapped.reverted);
+        assert_eq!(fs::read_to_string(folder.join("game.bak.bak")).unwrap(), "#!/bin/sh\necho game\n");
+        assert!(fs::read_link(&executable).unwrap().starts_with(&wrapper_dir));
+
+        let reverted = execute(&wrapper_dir, &executable, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions::default()).unwrap();
+        assert!(reverted.reverted);
+        assert_eq!(fs::read_to_string(&executable).unwrap(), "#!/bin/sh\necho game\n");
+        assert!(!folder.join("game.bak.bak").exists() && !folder.join("game").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn wrapper_script_leaves_out_invalid_names() {
+        let mut env = WrapperEnv::default();
+        env.set("X=1; touch /tmp/pwned; Y", "1");
+        env.set("DXVK_HUD", "fps");
+        let script = wrapper_script(Path::new("/games/game.bak"), &env, &[("$(reboot)".to_string(), "1".to_string())], &[], None, WrapperShell::Sh);
+        assert!(!script.contains("pwned") && !script.contains("reboot"));
+        assert!(script.contains("export DXVK_HUD=fps\n"));
+        assert_eq!(env.invalid_key(), Some("X=1; touch /tmp/pwned; Y"));
+    }
+
+    #[test]
+    fn records_made_in_parallel_are_all_kept() {
+        let folder = test_folder("parallel_records");
+        let recorded = folder.join("recorded_first");
+        let hash_of = |original: &Path| return config::wrapped(original).and_then(|wrapped| return wrapped.sha256);
+        record_wrapped(&recorded, |wrapped| wrapped.sha256 = Some("first".to_string())).unwrap();
+
+        let originals: Vec<PathBuf> = (0..64).map(|number| return folder.join(format!("game{number}"))).collect();
+        originals.par_iter().for_each(|original| {
+            record_wrapped(original, |wrapped| wrapped.sha256 = Some(original.display().to_string())).unwrap();
+            // Every write replaces the whole config, which a reader in between must never see half done
+            assert_eq!(hash_of(&recorded).as_deref(), Some("first"));
+        });
+        for original in &originals {
+            assert_eq!(hash_of(original), Some(original.display().to_string()));
+            record_wrapped(original, |wrapped| *wrapped = Wrapped::default()).unwrap();
+        }
+        record_wrapped(&recorded, |wrapped| *wrapped = Wrapped::default()).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn central_backup_notices_changes_to_the_config() {
+        let folder = test_folder("central_backup_cache");
+        let original = folder.join("game");
+        record_wrapped(&original, |wrapped| wrapped.backup = Some(folder.join("backup"))).unwrap();
+        assert_eq!(backup_path(&original), folder.join("backup"));
+
+        // Like the CLI recording another backup while the GUI is open
+        let config_path = config::path().unwrap();
+        let mut changed = Config::load(&config_path).unwrap();
+        changed.record_wrapped(original.to_str().unwrap(), |wrapped| wrapped.backup = Some(folder.join("moved_backup")));
+        changed.save(&config_path).unwrap();
+        assert_eq!(backup_path(&original), folder.join("moved_backup"));
+
+        record_wrapped(&original, |wrapped| *wrapped = Wrapped::default()).unwrap();
+        assert_eq!(backup_path(&original), folder.join("game.bak"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn wrapper_names_of_colliding_paths_differ() {
+        let paths = [Path::new("/opt/a-b"), Path::new("/opt/a_b"), Path::new("/opt/a/b"), Path::new("/opt/a b")];
+        // The legacy names of these were all the same
+        assert!(paths.iter().all(|path| return legacy_wrapper_name(path) == legacy_wrapper_name(paths[0])));
+        for (index, path) in paths.iter().enumerate() {
+            for other in &paths[index + 1..] {
+                assert_ne!(generate_wrapper_name(path), generate_wrapper_name(other), "{} and {}", path.display(), other.display());
+            }
+        }
+    }
+
+    #[test]
+    fn migration_gives_colliding_executables_their_own_scripts() {
+        let folder = test_folder("migrate_wrapper_names");
+        let wrapper_dir = folder.join("wrappers");
+        fs::create_dir(&wrapper_dir).unwrap();
+        let (first, second) = (folder.join("a-b"), folder.join("a_b"));
+        // Wrapped back when both got the same script, which launches whichever was wrapped last
+        let legacy_path = wrapper_dir.join(legacy_wrapper_name(&first));
+        assert_eq!(legacy_path, wrapper_dir.join(legacy_wrapper_name(&second)));
+        write_executable(&legacy_path, &format!("#!/bin/sh\nexec {} \"$@\"\n", shell_quote(&backup_path(&second).display().to_string())), 0o755).unwrap();
+        for executable in [&first, &second] {
+            write_executable(&backup_path(executable), &format!("#!/bin/sh\necho {}\n", executable.display()), 0o755).unwrap();
+            symlink(&legacy_path, executable).unwrap();
+        }
+
+        let mut env = WrapperEnv::default();
+        env.set("DXVK_HUD", "fps");
+        migrate_wrapper_names(&wrapper_dir, &[(first.clone(), OffloadBackend::Nvidia, WrapperEnv::default()), (second.clone(), OffloadBackend::Custom, env)], &[]);
+        for (executable, hud) in [(&first, false), (&second, true)] {
+            let script_path = wrapper_dir.join(generate_wrapper_name(executable));
+            assert_eq!(fs::read_link(executable).unwrap(), script_path);
+            let script = fs::read_to_string(&script_path).unwrap();
+            assert!(script.contains(&shell_quote(&backup_path(executable).display().to_string())), "{script}");
+            assert_eq!(script.contains("DXVK_HUD"), hud, "{script}");
+            let output = Command::new(executable).output().unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), executable.display().to_string());
+        }
+        assert!(!legacy_path.exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn path_hash_stays_the_same_across_versions() {
+        // Central backups and bind mounted originals are found again by it, so it can never change
+        assert_eq!(path_hash(Path::new("/games/game")), "cdf92726878a568cfc20a5f72d4fffaa9489d208a55bf8faa78b5ef694e15101");
+        assert!(generate_wrapper_name(Path::new("/games/game")).ends_with("_cdf92726878a"));
+        assert!(bind_mount_original(Path::new("/games/game")).starts_with(ELEVATED_WRAPPER_DIR));
+        assert!(bind_mount_script(Path::new("/games/game")).starts_with(ELEVATED_WRAPPER_DIR));
+    }
+
+    /// Made up running processes, recording which ones it gets asked for the name of (which only happens when a process gets examined)
+    #[derive(Default)]
+    struct FakeProcesses {
+        executables: RefCell<Vec<(i32, PathBuf)>>,
+        examined: RefCell<Vec<i32>>,
+    }
+
+    impl FakeProcesses {
+        /// The PIDs examined since the last call, in order
+        fn take_examined(&self) -> Vec<i32> {
+            let mut examined = self.examined.take();
+            examined.sort_unstable();
+            return examined
+        }
+    }
+
+    impl ProcessSource for FakeProcesses {
+        fn executables(&self) -> Result<ProcessExecutables, Box<dyn Error>> {
+            return Ok((self.executables.borrow().clone(), 0))
+        }
+
+        fn name(&self, pid: i32) -> Option<String> {
+            self.examined.borrow_mut().push(pid);
+            return Some(format!("process{pid}"))
+        }
+    }
+
+    #[test]
+    fn process_cache_only_examines_new_processes() {
+        let folder = test_folder("process_cache");
+        let (game, other) = (folder.join("game"), folder.join("other"));
+        write_executable(&game, "", 0o755).unwrap();
+        write_executable(&other, "", 0o755).unwrap();
+        let source = FakeProcesses { executables: RefCell::new((1..=300).map(|pid| return (pid, game.clone())).collect()), ..FakeProcesses::default() };
+        let mut cache = ProcessCache::default();
+
+        assert_eq!(cache.executables(&source, &[], true).unwrap()[game.to_str().unwrap()].pids.len(), 300);
+        assert_eq!(source.take_examined().len(), 300);
+        // Nothing changed, nothing gets looked at again
+        cache.executables(&source, &[], true).unwrap();
+        assert!(source.take_examined().is_empty());
+        // Only the process that exec'd something else and the new ones do, the one that's gone is dropped
+        {
+            let mut executables = source.executables.borrow_mut();
+            executables.retain(|(pid, _)| return *pid != 300);
+            executables[1].1.clone_from(&other);
+            executables.extend([(301, other.clone()), (302, other.clone())]);
+        }
+        let executables = cache.executables(&source, &[], true).unwrap();
+        assert_eq!(source.take_examined(), [2, 301, 302]);
+        assert_eq!(executables[game.to_str().unwrap()].pids.len(), 298);
+        assert_eq!(executables[other.to_str().unwrap()].pids, [2, 301, 302]);
+        assert_eq!(executables[other.to_str().unwrap()].name, "process2");
+        // Different system paths make every process count as new again
+        cache.executables(&source, &["/usr".to_string()], true).unwrap();
+        assert_eq!(source.take_examined().len(), 301);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn anti_cheat_match_looks_next_to_the_game() {
+        let folder = test_folder("anti_cheat");
+        let game_dir = folder.join("Games/Game");
+        fs::create_dir_all(game_dir.join("EasyAntiCheat")).unwrap();
+        write_executable(&game_dir.join("game"), "", 0o755).unwrap();
+        assert_eq!(anti_cheat_match(&game_dir.join("game"), &[]).as_deref(), Some("easyanticheat"));
+        assert_eq!(anti_cheat_match(&folder.join("Games"), &[]).as_deref(), Some("easyanticheat"));
+        assert_eq!(anti_cheat_match(&game_dir.join("game"), &["GAME".to_string()]).as_deref(), Some("game"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn anti_cheat_match_ignores_the_folders_above_the_game() {
+        let folder = test_folder("anti_cheat_above");
+        let game_dir = folder.join("battleye_fan/Games/Game");
+        fs::create_dir_all(&game_dir).unwrap();
+        write_executable(&game_dir.join("game"), "", 0o755).unwrap();
+        assert_eq!(anti_cheat_match(&game_dir.join("game"), &[]), None);
+        assert_eq!(anti_cheat_match(&game_dir, &[]), None);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn anti_cheat_match_stops_at_the_depth_limit() {
+        let folder = test_folder("anti_cheat_depth");
+        let deep = (0..ANTI_CHEAT_DEPTH).fold(folder.clone(), |path, level| return path.join(format!("level{level}")));
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("BEClient_x64.dll"), "").unwrap();
+        assert_eq!(anti_cheat_match(&folder, &[]), None);
+        assert_eq!(anti_cheat_match(&folder.join("level0"), &[]).as_deref(), Some("beclient"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn revert_restores_the_original_exactly() {
+        let folder = test_folder("revert_round_trip");
+        let wrapper_dir = folder.join("wrappers");
+        fs::create_dir(&wrapper_dir).unwrap();
+        let executable = folder.join("game");
+        let contents: Vec<u8> = b"#!/bin/sh\nexit 0\n".iter().copied().chain((0..=255).cycle().take(4096)).collect();
+        fs::write(&executable, &contents).unwrap();
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o751)).unwrap();
+        let before = fs::metadata(&executable).unwrap();
+
+        let wrapped = execute(&wrapper_dir, &executable, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions::default()).unwrap();
+        assert!(!wrapped.reverted && fs::read_link(&executable).is_ok());
+        let reverted = execute(&wrapper_dir, &executable, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions::default()).unwrap();
+        assert!(reverted.reverted);
+
+        assert_eq!(fs::read(&executable).unwrap(), contents);
+        let after = fs::symlink_metadata(&executable).unwrap();
+        assert!(after.file_type().is_file());
+        // The backup is a hard link of the original that gets renamed back, so it's the very same file
+        assert_eq!((after.ino(), after.mode(), after.uid(), after.gid()), (before.ino(), before.mode(), before.uid(), before.gid()));
+        assert_eq!(after.modified().unwrap(), before.modified().unwrap());
+        assert!(!backup_path(&executable).exists() && fs::read_dir(&wrapper_dir).unwrap().next().is_none());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn wrapper_names_are_stable_and_safe() {
+        let long = format!("/games/{}/game", "a very long folder name ".repeat(20));
+        for path in ["/games/game", "/home/user/Games/My Game!/run.sh", "/games/游戏/game", "/games/$(reboot)/'game'", long.as_str()] {
+            let name = generate_wrapper_name(Path::new(path));
+            assert_eq!(name, generate_wrapper_name(Path::new(path)), "{path}");
+            assert!(name.starts_with("wrapper_"), "{name}");
+            assert!(name.chars().all(|character| return character.is_ascii_alphanumeric() || character == '_'), "{name}");
+            assert!(name.len() < 255, "{name}");
+        }
+    }
+
+    #[test]
+    fn process_list_leaves_out_what_cant_be_wrapped() {
+        let folder = test_folder("process_filter");
+        let system = folder.join("system");
+        fs::create_dir(&system).unwrap();
+        let (game, updated, read_only, system_exe) = (folder.join("game"), folder.join("updated"), folder.join("read_only"), system.join("daemon"));
+        for path in [&game, &updated, &system_exe] {
+            write_executable(path, "", 0o755).unwrap();
+        }
+        write_executable(&read_only, "", 0o755).unwrap();
+        fs::set_permissions(&read_only, fs::Permissions::from_mode(0o555)).unwrap();
+        let mut deleted = folder.join("removed").into_os_string();
+        deleted.push(" (deleted)");
+        let mut replaced = updated.clone().into_os_string();
+        replaced.push(" (deleted)");
+        let source = FakeProcesses {
+            executables: RefCell::new(vec![(1, game.clone()), (2, system_exe), (3, read_only.clone()), (4, deleted.into()), (5, replaced.into())]),
+            ..FakeProcesses::default()
+        };
+        let system_paths = [system.display().to_string()];
+
+        let executables = ProcessCache::default().executables(&source, &system_paths, true).unwrap();
+        let mut listed: Vec<&String> = executables.keys().collect();
+        listed.sort();
+        // A process whose binary got replaced by an update is listed under the new one, one whose binary is gone isn't listed at all
+        assert_eq!(listed, [&game.display().to_string(), &updated.display().to_string()]);
+        assert_eq!(executables[updated.to_str().unwrap()].pids, [5]);
+        // Executables we can't write to are only left out when they can't be wrapped as root
+        let executables = ProcessCache::default().executables(&source, &system_paths, false).unwrap();
+        assert!(executables.contains_key(read_only.to_str().unwrap()));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn find_executables_gets_through_symlink_loops() {
+        let folder = test_folder("symlink_loops");
+        fs::create_dir(folder.join("sub")).unwrap();
+        write_executable(&folder.join("sub/game"), "", 0o755).unwrap();
+        symlink("..", folder.join("sub/up")).unwrap(); // Back up the tree
+        symlink("loop_b", folder.join("loop_a")).unwrap(); // Pointing at each other
+        symlink("loop_a", folder.join("loop_b")).unwrap();
+        symlink("sub/game", folder.join("game_link")).unwrap();
+
+        // A loop would never finish, so it gets a deadline instead of hanging the tests
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let directory = folder.clone();
+        thread::spawn(move || { let _ = sender.send(find_executables(&directory, &ScanOptions::default())); });
+        let executables = receiver.recv_timeout(Duration::from_secs(10)).expect("find_executables didn't finish");
+        assert_eq!(executables, [folder.join("sub/game")]);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    /// A folder name with everything a shell would otherwise make something of
+    const AWKWARD_NAME: &str = "my \"game\" 'n $HOME $(false) `false` \\ dir";
+
+    #[test]
+    fn wrapped_executables_with_awkward_paths_launch() {
+        let folder = test_folder("awkward_paths");
+        let wrapper_dir = folder.join("wrappers");
+        fs::create_dir(&wrapper_dir).unwrap();
+        let game_dir = folder.join(AWKWARD_NAME);
+        fs::create_dir(&game_dir).unwrap();
+        let executable = game_dir.join("game $1");
+        write_executable(&executable, "#!/bin/sh\nprintf '%s|' \"$0\" \"$@\"\n", 0o755).unwrap();
+
+        execute(&wrapper_dir, &executable, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions::default()).unwrap();
+        let output = Command::new(&executable).args(["an argument", "$HOME"]).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{}|an argument|$HOME|", backup_path(&executable).display()));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn shell_quote_survives_sh_c() {
+        for text in [AWKWARD_NAME, "", "'", "''", "a'b\"c", "line\nbreak", "-n"] {
+            let output = Command::new("sh").arg("-c").arg(format!("printf '%s' {}", shell_quote(text))).output().unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), text);
+            let output = Command::new("sh").arg("-c").arg(format!("printf '%s' {}", shell_word(text))).output().unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), text);
+        }
+    }
+
+    #[test]
+    fn privileged_commands_take_awkward_paths_literally() {
+        let folder = test_folder("awkward_privileged");
+        let game_dir = folder.join(AWKWARD_NAME);
+        fs::create_dir(&game_dir).unwrap();
+        let executable = game_dir.join("game");
+        fs::write(backup_path(&executable), "original").unwrap();
+        symlink("/nonexistent/wrapper", &executable).unwrap();
+
+        // What pkexec runs, minus pkexec
+        let status = Command::new("sh").arg("-c").arg(revert_elevated_commands(&executable, &backup_path(&executable))).status().unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&executable).unwrap(), "original");
+        assert!(!backup_path(&executable).exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn is_appimage_goes_by_the_magic_bytes_or_the_extension() {
+        let folder = test_folder("appimage");
+        let elf_header = |appimage_type: &[u8]| -> Vec<u8> {
+            return b"\x7fELF\x02\x01\x01\x00".iter().chain(appimage_type).chain([0u8; 16].iter()).copied().collect()
+        };
+        for (name, contents, expected) in [
+            ("type2", elf_header(b"AI\x02"), true),
+            ("type1", elf_header(b"AI\x01"), true),
+            ("plain_elf", elf_header(b"\x00\x00\x00"), false),
+            ("other_type", elf_header(b"AI\x07"), false),
+            ("script", b"#!/bin/sh\nexit 0\n".to_vec(), false),
+            ("short", b"\x7fELF".to_vec(), false),
+            ("Game-x86_64.AppImage", Vec::new(), true),
+            ("game.appimage", Vec::new(), true),
+        ] {
+            fs::write(folder.join(name), contents).unwrap();
+            assert_eq!(is_appimage(&folder.join(name)), expected, "{name}");
+        }
+        assert!(!is_appimage(&folder.join("missing")));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn appimage_wrappers_point_appimage_at_the_original() {
+        let script = wrapper_script(Path::new("/games/My Game.AppImage.bak"), &WrapperEnv::default(), &[], &[], Some(Path::new("/games/My Game.AppImage")), WrapperShell::Bash);
+        assert!(script.contains("export APPIMAGE='/games/My Game.AppImage'\n"), "{script}");
+        assert!(script.contains("export APPIMAGELAUNCHER_DISABLE=1\n"), "{script}");
+        assert!(script.contains("exec -a \"$0\" '/games/My Game.AppImage.bak' \"$@\""), "{script}");
+        let script = wrapper_script(Path::new("/games/game.bak"), &WrapperEnv::default(), &[], &[], None, WrapperShell::Bash);
+        assert!(!script.contains("APPIMAGE"), "{script}");
+    }
+
+    #[test]
+    fn created_wrappers_are_executable() {
+        let folder = test_folder("wrapper_executable");
+        let wrapper_dir = folder.join("wrappers");
+        fs::create_dir(&wrapper_dir).unwrap();
+        for (name, mode, exec_bits) in [("game", 0o755, 0o111), ("private", 0o700, 0o100), ("group", 0o750, 0o110)] {
+            let executable = folder.join(name);
+            fs::write(&executable, "#!/bin/sh\nexit 0\n").unwrap();
+            fs::set_permissions(&executable, fs::Permissions::from_mode(mode)).unwrap();
+            let wrapper_name = generate_wrapper_name(&executable);
+            create_wrapper(&executable, &wrapper_dir, &wrapper_name, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions { igpu_link: true, ..WrapOptions::default() }).unwrap();
+
+            // The wrapper runs for whoever could run the original, the iGPU one gets the execute bits added to what it was written with
+            for (wrapper, expected) in [(wrapper_dir.join(&wrapper_name), exec_bits), (wrapper_dir.join(format!("{wrapper_name}{IGPU_SUFFIX}")), 0o111)] {
+                let wrapper_mode = fs::metadata(&wrapper).unwrap().mode();
+                assert_eq!(wrapper_mode & 0o111, expected, "{} has mode {wrapper_mode:o}", wrapper.display());
+            }
+            // And what runs in its place is the wrapper, through the symlink
+            assert!(Command::new(&executable).status().unwrap().success());
+        }
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn exclude_globs_match_nested_paths() {
+        let matches = |glob: &str, path: &str| -> bool { return glob_regex(glob).unwrap().is_match(path) };
+        // A bare name matches that component at any depth, along with everything below it
+        assert!(matches("redist", "redist"));
+        assert!(matches("redist", "redist/setup.exe"));
+        assert!(matches("redist", "game/bin/redist/vc/setup.exe"));
+        assert!(matches("_CommonRedist", "_commonredist/DirectX/dxsetup"));
+        assert!(!matches("redist", "redistributables"));
+        assert!(!matches("redist", "game/myredist/setup"));
+        // `*` and `?` stay within a component
+        assert!(matches("vcredist*", "deps/vcredist_x64/install"));
+        assert!(matches("lib?", "game/lib6/libfoo.so"));
+        assert!(!matches("lib?", "game/lib64/libfoo.so"));
+        assert!(matches("game*", "other/gamedata"));
+        assert!(!matches("game*", "other/my_gamedata"));
+        // With a slash it goes from the top of the folder
+        assert!(matches("tools/redist", "tools/redist/setup"));
+        assert!(!matches("tools/redist", "game/tools/redist/setup"));
+        assert!(matches("/tools/redist/", "tools/redist"));
+        assert!(matches("*/redist", "tools/redist/setup"));
+        assert!(!matches("*/redist", "a/tools/redist"));
+        // While `**` crosses components
+        assert!(matches("**/redist", "a/tools/redist/setup"));
+        assert!(matches("tools/**/setup", "tools/a/b/setup"));
+        // Nothing is made of the regex characters in names
+        assert!(matches("game (old)", "games/game (old)/run"));
+        assert!(!matches("a.b", "axb"));
+        assert!(glob_regex(" / ").is_none());
+    }
+
+    #[test]
+    fn find_executables_skips_excluded_folders() {
+        let folder = test_folder("exclude_globs");
+        for path in ["game", "bin/game", "bin/Redist/setup", "_CommonRedist/vcredist/2019/setup", "tools/redist/setup", "deps/vcredist_x64/setup", "notredist/tool"] {
+            fs::create_dir_all(folder.join(path).parent().unwrap()).unwrap();
+            write_executable(&folder.join(path), "", 0o755).unwrap();
+        }
+        let scan = ScanOptions { exclude_globs: DEFAULT_EXCLUDE_GLOBS.iter().map(ToString::to_string).collect(), ..ScanOptions::default() };
+        let mut executables = find_executables(&folder, &scan);
+        executables.sort();
+        assert_eq!(executables, ["bin/game", "game", "notredist/tool"].map(|path| return folder.join(path)));
+
+        let scan = ScanOptions { exclude_globs: vec!["bin".to_string(), "tools/**".to_string()], ..ScanOptions::default() };
+        let mut executables = find_executables(&folder, &scan);
+        executables.sort();
+        assert_eq!(executables, ["_CommonRedist/vcredist/2019/setup", "deps/vcredist_x64/setup", "game", "notredist/tool"].map(|path| return folder.join(path)));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn per_executable_variables_apply_with_every_backend() {
+        let folder = test_folder("backend_env");
+        let wrapper_dir = folder.join("wrappers");
+        fs::create_dir(&wrapper_dir).unwrap();
+        let mut env = WrapperEnv::default();
+        env.set("DXVK_HUD", "fps");
+        env.set("__GL_SYNC_TO_VBLANK", "0");
+        env.set("__GLX_VENDOR_LIBRARY_NAME", "mesa"); // Overriding one of the offload variables
+
+        let executable = folder.join("game");
+        fs::write(&executable, "#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+        let wrapper_name = generate_wrapper_name(&executable);
+        create_wrapper(&executable, &wrapper_dir, &wrapper_name, OffloadBackend::Nvidia, &env, &WrapOptions::default()).unwrap();
+        let script = fs::read_to_string(wrapper_dir.join(&wrapper_name)).unwrap();
+        for line in ["export __NV_PRIME_RENDER_OFFLOAD=1\n", "export DXVK_HUD=fps\n", "export __GL_SYNC_TO_VBLANK=0\n", "export __GLX_VENDOR_LIBRARY_NAME=mesa\n"] {
+            assert!(script.contains(line), "{line} missing from {script}");
+        }
+        assert!(!script.contains("__GLX_VENDOR_LIBRARY_NAME=nvidia"), "{script}");
+
+        // DRI_PRIME gets them too, but not the NVIDIA offload variables every executable starts out with
+        let dri_prime = OffloadBackend::DriPrime.env(&env);
+        assert_eq!(dri_prime.vars, [("DRI_PRIME", "1"), ("DXVK_HUD", "fps"), ("__GL_SYNC_TO_VBLANK", "0")].map(|(key, value)| return (key.to_string(), value.to_string())));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
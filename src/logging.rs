@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// The log file is rotated once it gets this big, keeping the previous one as `<name>.1`
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// How many of the latest lines are kept around for the log pane
+const RECENT_LINES: usize = 500;
+
+/// Set to `error`, `warn`, `info`, `debug` or `trace` to change how much gets logged
+const LEVEL_VARIABLE: &str = "NVIDIA_MANAGER_LOG";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Logs to stderr and to a log file in the data directory, keeping the latest lines in memory for the log pane
+struct Logger {
+    path: Option<PathBuf>,
+    file: Mutex<Option<File>>,
+    recent: Mutex<VecDeque<(Level, String)>>,
+}
+
+impl Logger {
+    /// Start a new file once the current one is too big, logging to stderr only if that fails
+    fn rotate_if_full(&self, file: &mut Option<File>) {
+        let (Some(path), Some(current)) = (&self.path, file.as_ref()) else { return };
+        if current.metadata().map_or(0, |metadata| return metadata.len()) < MAX_LOG_SIZE { return }
+
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+        let _ = fs::rename(path, rotated);
+        *file = OpenOptions::new().create(true).append(true).open(path).ok();
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        return metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) { return }
+        let message = record.args().to_string();
+        eprintln!("[{}] {message}", record.level());
+
+        if let Ok(mut file) = self.file.lock() {
+            self.rotate_if_full(&mut file);
+            if let Some(file) = file.as_mut() {
+                let _ = writeln!(file, "{} {:<5} {}: {message}", timestamp(), record.level(), record.target());
+            }
+        }
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back((record.level(), message));
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Start logging, to stderr only if the log file can't be opened
+pub fn init() {
+    let level = std::env::var(LEVEL_VARIABLE).ok()
+        .and_then(|level| return level.parse::<LevelFilter>().ok())
+        .unwrap_or(LevelFilter::Info);
+    let path = xdg::BaseDirectories::with_prefix("NvidiaManager").ok()
+        .and_then(|xdg_dirs| return xdg_dirs.place_data_file("logs/nvidia_manager.log").ok());
+    let file = path.as_ref().and_then(|path| return OpenOptions::new().create(true).append(true).open(path).ok());
+    if file.is_none() {
+        eprintln!("Failed to open the log file, only logging to stderr");
+    }
+
+    let logger = LOGGER.get_or_init(|| return Logger { path, file: Mutex::new(file), recent: Mutex::new(VecDeque::new()) });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Change how much gets logged, unless `NVIDIA_MANAGER_LOG` says otherwise
+pub fn set_level(level: LevelFilter) {
+    if std::env::var_os(LEVEL_VARIABLE).is_some() { return }
+    log::set_max_level(level);
+}
+
+/// Where the log file is, if there is one
+pub fn log_path() -> Option<PathBuf> {
+    return LOGGER.get()?.path.clone()
+}
+
+/// The latest logged lines, oldest first
+pub fn recent_lines() -> Vec<(Level, String)> {
+    return LOGGER.get()
+        .and_then(|logger| return logger.recent.lock().ok().map(|recent| return recent.iter().cloned().collect()))
+        .unwrap_or_default()
+}
+
+/// The current time in UTC like `2024-07-01 12:34:56`, without pulling in a date crate for it
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| return duration.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from the days since the epoch, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    return format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}", time / 3600, time % 3600 / 60, time % 60)
+}
@@ -2,192 +2,3094 @@
 #![warn(clippy::implicit_return)]
 #![allow(clippy::needless_return)]
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::Write as _;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
-use backtrace::Backtrace;
 use eframe::{icon_data, NativeOptions};
-use egui::{CentralPanel, Context, ScrollArea, TopBottomPanel, vec2, ViewportBuilder, Window};
+use log::{error, info, warn, Level, LevelFilter};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use egui::{CentralPanel, Context, EventFilter, Key, KeyboardShortcut, Modifiers, Response, ScrollArea, TopBottomPanel, vec2, ViewportBuilder, Window};
 use rfd::FileDialog;
-use crate::internals::{execute, get_executable_paths};
+use crate::config::{Config, Entry, FlatpakOffload, Wrapped};
+use crate::desktop_entry::{is_desktop_entry, is_desktop_entry_offloaded};
+use crate::error::WrapError;
+use crate::flatpak::FlatpakApp;
+use crate::hotkey::{focused_executable, window_pids, Hotkey};
+use crate::settings::{Density, Settings};
+use crate::toast::Toasts;
+use crate::tray::Tray;
+use crate::window_state::WindowState;
+use crate::internals::{anti_cheat_match, bind_mount_wrapper, COMMON_PREFIXES, common_scan_dirs, create_launcher, entries_off_nvidia, execute, ExecuteSummary, file_dialog_backend, find_wrapped_symlinks, generate_wrapper_name, has_missing_wrapper, is_bind_mounted, is_dereferenced_wrapper, is_elevated, is_launcher_of, is_offload_masked, is_orphaned_symlink, is_proton_path, launcher_path, LaunchHelper, migrate_wrapper_names, mount_options_for, needs_elevation, normalize_path, nvidia_driver_present, nvidia_gpus, nvidia_pids, NvidiaExtra, offload_command, OffloadBackend, on_path, open_folder, parse_env_lines, ProcessCache, Procfs, PROTON_ENV, recorded_backup_path, relink_wrapper, relocate_wrappers, remove_launcher, remove_orphaned_symlink, remove_stale_wrapper, resolve_backup, resolve_command, restore_missing_wrapper, restore_moved, reveal_in_file_manager, revert, revert_all, revert_unverified, run_hook, RunningExecutable, session_warning, SessionType, set_offload, set_wrapper_shell, shared_path_reason, sort_by_last_used, system_path_match, test_launch, TestLaunch, unmount_wrapper, wrap_elevated, wrapped_executables, wrapper_state, WrapOptions, WrapperEnv, WrapperShell, WrapperState, WrapStrategy};
 
+mod cli;
+mod config;
+mod desktop_entry;
+mod error;
+mod flatpak;
+mod hotkey;
 mod internals;
+mod logging;
+mod self_test;
+mod settings;
+mod toast;
+mod tray;
+mod window_state;
+
+/// How many of the most recently used processes are shown on top of the process list
+const RECENT_COUNT: usize = 5;
+
+/// Put after processes nvidia-smi lists as running on the NVIDIA GPU
+const NVIDIA_MARK: &str = "[NVIDIA]";
+
+/// Add/Remove the selected process
+const ADD_REMOVE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Enter);
+/// Read the running processes again
+const REFRESH_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F5);
+/// Revert All, which still asks first
+const REVERT_ALL_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers { shift: true, ..Modifiers::COMMAND }, Key::R);
 
 struct MyApp {
-    executables: HashSet<String>,
+    processes: Processes,
+    added: Added,
+    system: System,
+    windows: Windows,
+    prompts: Prompts,
     selected_executable: Option<String>,
-    modified_executables: HashSet<String>,
+    /// The window geometry and selection, saved when the window is closed
+    window_state: WindowState,
     wrapper_dir: PathBuf,
     config_path: PathBuf,
-    show_picker_dialog: bool,
+    report: Option<String>,
+    /// Confirmations and errors of Add/Remove, which the status line alone is easy to miss for
+    toasts: Toasts,
+    last_action: Option<LastAction>,
+    pending: Option<PendingExecute>,
+    batch: Option<BatchAdd>,
+    test_launch: Option<(String, Receiver<io::Result<TestLaunch>>)>,
+    /// Hooks still running, each sends a warning if it failed
+    running_hooks: Vec<Receiver<Option<String>>>,
+    tray: Option<Tray>,
+    hotkey: Option<Hotkey>,
+    wrap_strategy: WrapStrategy,
+    offload_backend: OffloadBackend,
+    dry_run: bool,
+    wrapper_env: Option<(String, WrapperEnv)>,
+    config_watcher: Option<(RecommendedWatcher, Receiver<()>)>,
+    config_changed_at: Option<Instant>,
+    launch_helpers: Vec<LaunchHelper>,
+    proton_env: String,
+    exclude_globs: String,
+    system_paths: String,
+    settings: Settings,
+    settings_path: PathBuf,
+    command_name: String,
+    filter: String,
 }
 
-impl MyApp {
-    fn new() -> Self {
+/// The running processes to pick from
+struct Processes {
+    executables: HashMap<String, RunningExecutable>,
+    cache: ProcessCache,
+    /// The most recently used ones, shown on top
+    recent: Vec<String>,
+    refreshed_at: Instant,
+    /// PIDs of the processes with a window, `None` if the windows can't be listed (e.g. a Wayland session without XWayland)
+    window_pids: Option<HashSet<i32>>,
+    /// What nvidia-smi lists as running on the NVIDIA GPU, `None` without nvidia-smi
+    nvidia_pids: Option<HashSet<i32>>,
+    /// Processes checked to be added together with Add Checked
+    checked: HashSet<String>,
+}
+
+/// What's added, as the list shows it
+struct Added {
+    entries: HashSet<String>,
+    bind_mounted: HashSet<String>,
+    /// Entries with a separate launcher instead of being wrapped
+    launchers: HashSet<String>,
+    /// Offloaded Flatpak apps and what was changed in their overrides
+    flatpaks: BTreeMap<String, FlatpakOffload>,
+    /// The master switch, off while the offload of the wrapped executables is masked
+    offload_enabled: bool,
+    broken: Vec<(String, BrokenEntry)>,
+    replaced_symlinks: Vec<PathBuf>,
+    /// Entries running but not on the NVIDIA GPU, as of the last Check Running
+    off_nvidia: HashSet<String>,
+    states: HashMap<String, WrapperState>,
+    /// The entries and master switch the states were checked for
+    states_of: (Vec<String>, bool),
+    /// Entries checked to be reverted together with Revert Checked
+    checked: HashSet<String>,
+}
+
+/// What was found out about the machine at startup
+struct System {
+    file_dialog_backend: Option<&'static str>,
+    /// Wrappers only get what suits the session they're made in
+    session: SessionType,
+    installed_helpers: Vec<LaunchHelper>,
+    installed_prefixes: Vec<&'static str>,
+    /// Installed Flatpak apps, empty if Flatpak isn't installed
+    flatpak_apps: Vec<FlatpakApp>,
+    wrapper_dir_noexec: bool,
+    nvidia_driver: bool,
+}
+
+/// The windows open next to the main one
+#[derive(Default)]
+struct Windows {
+    picker: bool,
+    manual_path: String,
+    log: bool,
+    settings: bool,
+    /// The outcome of the last self-test, shown until it's closed
+    self_test: Option<Vec<self_test::Check>>,
+    /// What the last Add/Remove of a folder did to each executable in it, shown until it's closed
+    folder_results: Option<FolderResults>,
+    /// The entry whose path is being edited, with the new path as typed so far
+    editing_entry: Option<(String, String)>,
+}
+
+/// Questions waiting on an answer before going on
+#[derive(Default)]
+struct Prompts {
+    /// Path about to be wrapped with an anti-cheat next to it, with what was found
+    anti_cheat: Option<(String, String)>,
+    /// Path about to be wrapped that's outside the home folder or writable by others, with why
+    shared_path: Option<(String, String)>,
+    backup_mismatch: Option<String>,
+    revert_all: bool,
+    /// Offer to import wrappers from a manual setup, on the first run
+    import: bool,
+    import_candidates: Vec<(String, bool)>,
+}
+
+/// The last Add/Remove, kept so it can be undone
+struct LastAction {
+    entry: String,
+    /// Whether it was wrapped (true) or reverted (false)
+    wrapped: bool,
+    backend: OffloadBackend,
+    /// The environment it was wrapped with, the one it had for a revert
+    env: WrapperEnv,
+    strategy: WrapStrategy,
+}
+
+impl LastAction {
+    /// Removing an entry, which undoing adds back the way its config entry says it was added
+    fn removed(entry: &str, removed: Option<Entry>) -> Self {
+        let removed = removed.unwrap_or_else(|| return Entry::new(entry));
+        return LastAction { entry: entry.to_string(), wrapped: false, backend: removed.backend, strategy: removed.strategy(), env: removed.env }
+    }
+}
+
+/// An Add/Remove running in the background
+struct PendingExecute {
+    selected: String,
+    backend: OffloadBackend,
+    env: WrapperEnv,
+    receiver: Receiver<Finished>,
+}
+
+/// What a background Add/Remove comes back with, depending on how it was done
+enum Finished {
+    Execute(Result<ExecuteSummary, WrapError>),
+    /// Wrapping as root, which waits on the password prompt
+    WrapAsRoot(Result<(), WrapError>),
+    /// Reverting several entries at once, how it went for each and for how many of the reverted ones the `post_unwrap` hook failed
+    Revert(Vec<(String, Result<(), WrapError>)>, usize),
+}
+
+/// An Add Checked, adding its processes one after another
+struct BatchAdd {
+    queued: VecDeque<String>,
+    total: usize,
+    /// How many of them got wrapped so far, going by what wrapping each returned
+    added: usize,
+    /// Left out because they'd ask for confirmation first, they're added one by one instead
+    skipped: Vec<String>,
+}
+
+/// What Add/Remove did to every executable of a folder
+struct FolderResults {
+    folder: String,
+    summary: String,
+    rows: Vec<(PathBuf, FolderOutcome)>,
+}
+
+enum FolderOutcome {
+    /// Wrapped, or reverted if the folder was
+    Changed,
+    Skipped(&'static str),
+    Failed(String),
+}
+
+impl FolderResults {
+    fn of(folder: &str, summary: &ExecuteSummary) -> Self {
+        let mut rows: Vec<(PathBuf, FolderOutcome)> = summary.changed.iter().map(|path| return (path.clone(), FolderOutcome::Changed))
+            .chain(summary.skipped.iter().map(|(path, reason)| return (path.clone(), FolderOutcome::Skipped(reason))))
+            .chain(summary.failed.iter().map(|(path, e)| return (path.clone(), FolderOutcome::Failed(wrap_error_report(e)))))
+            .collect();
+        rows.sort_by(|(path_a, _), (path_b, _)| return path_a.cmp(path_b));
+        return FolderResults { folder: folder.to_string(), summary: summary.describe(), rows }
+    }
+}
+
+/// Where everything we store lives
+struct AppPaths {
+    wrapper_dir: PathBuf,
+    config_path: PathBuf,
+    settings_path: PathBuf,
+}
+
+impl AppPaths {
+    /// Create our folders if needed, and load the user preferences since they can move the wrapper folder.
+    /// Fails if the config can't be read, or was written by a newer version we must not touch
+    fn load() -> Result<(Self, Settings), Box<dyn Error>> {
         // Create our data folder
         let xdg_dirs = xdg::BaseDirectories::with_prefix("NvidiaManager").unwrap();
-        let wrapper_dir = xdg_dirs.create_data_directory("ONLY_DELETE_IF_YOU_KNOW_WHAT_YOU_ARE_DOING").unwrap();
+        let default_wrapper_dir = xdg_dirs.create_data_directory("ONLY_DELETE_IF_YOU_KNOW_WHAT_YOU_ARE_DOING").unwrap();
         
         // Create our config folder
         let config_dir = xdg_dirs.create_data_directory("config").unwrap();
-        let config_path = config_dir.join("config.txt");
+        let config_path = config_dir.join("config.toml");
+        migrate_config(&config_dir.join("config.txt"), &config_path)?;
         if !config_path.exists() {
-            let mut file = fs::File::create(&config_path).unwrap();
-            file.write_all(b"").unwrap();
+            Config::default().save(&config_path)?;
+        }
+        // Refuses configs from a newer version, we'd drop whatever it changed
+        Config::load(&config_path)?;
+        migrate_indexes(&config_dir, &config_path)?;
+        
+        // Load the user preferences
+        let settings_path = config_dir.join("settings.toml");
+        let settings = Settings::load(&settings_path);
+        logging::set_level(settings.log_level);
+        set_wrapper_shell(settings.wrapping.shell);
+        let wrapper_dir = settings.wrapper_dir.clone().unwrap_or(default_wrapper_dir);
+        fs::create_dir_all(&wrapper_dir).unwrap();
+
+        return Ok((AppPaths { wrapper_dir, config_path, settings_path }, settings))
+    }
+}
+
+impl System {
+    /// Look at what the machine has, warning about what keeps wrapped executables from working
+    fn detect(wrapper_dir: &Path) -> Self {
+        // Wrapper scripts can't run from a noexec mount, which would make every wrapped executable fail to launch
+        let wrapper_dir_noexec = mount_options_for(wrapper_dir).iter().any(|option| return option == "noexec");
+        if wrapper_dir_noexec {
+            warn!("{} is on a noexec mount, wrapped executables won't be able to launch", wrapper_dir.display());
+        }
+
+        // Wrapping with the NVIDIA backend on a machine without the NVIDIA driver breaks the executable instead
+        let nvidia_driver = nvidia_driver_present();
+        if !nvidia_driver {
+            warn!("The NVIDIA driver doesn't seem to be installed (no /proc/driver/nvidia/version or nvidia-smi), NVIDIA offload won't work");
+        }
+        for (index, gpu) in nvidia_gpus().iter().enumerate() {
+            info!("Found {} at {} (offload provider NVIDIA-G{index})", gpu.name, gpu.bus_id);
+        }
+
+        // Without a file dialog backend picking paths falls back to typing them in
+        let file_dialog_backend = file_dialog_backend();
+        match file_dialog_backend {
+            Some(backend) => info!("Using {backend} for file dialogs"),
+            None => warn!("No file dialog backend available (xdg-desktop-portal or zenity), paths have to be typed in"),
+        }
+
+        // Flatpak apps live in their sandbox, so they're listed separately instead of showing up as processes
+        let flatpak_apps = if flatpak::is_available() {
+            flatpak::installed_apps().unwrap_or_else(|e| {
+                warn!("Failed to list the Flatpak apps: {e}");
+                return Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+
+        let session = SessionType::detect();
+        info!("Running in a {} session", session.label());
+
+        return System {
+            file_dialog_backend,
+            session,
+            installed_helpers: LaunchHelper::ALL.into_iter().filter(|helper| return helper.is_installed()).collect(),
+            installed_prefixes: COMMON_PREFIXES.into_iter().filter(|command| return on_path(command)).collect(),
+            flatpak_apps,
+            wrapper_dir_noexec,
+            nvidia_driver,
+        }
+    }
+}
+
+impl Processes {
+    /// Fetch the initial list of processes with executables
+    fn read(settings: &Settings, nvidia_driver: bool) -> Self {
+        let mut cache = ProcessCache::default();
+        let executables = cache.executables(&Procfs, &settings.scan.system_paths, !settings.wrapping.elevated).unwrap_or_default();
+        let recent = sort_by_last_used(executables.keys().cloned()).into_iter().take(RECENT_COUNT).collect();
+        return Processes {
+            executables,
+            cache,
+            recent,
+            refreshed_at: Instant::now(),
+            window_pids: window_pids().ok(),
+            nvidia_pids: if nvidia_driver { nvidia_pids().ok() } else { None },
+            checked: HashSet::new(),
+        }
+    }
+}
+
+impl Added {
+    /// Read the config, with what's gone missing since the last time dropped from it
+    fn load(config_path: &Path, wrapper_dir: &Path) -> Self {
+        let broken = validate_config(config_path, wrapper_dir, false);
+        let config = Config::read(config_path);
+        migrate_wrapper_names(wrapper_dir, &wrapped_with_settings(&config), &parse_env_lines(PROTON_ENV));
+        let wrapped: Vec<PathBuf> = config.entries.iter().flat_map(|entry| return wrapped_executables(&PathBuf::from(&entry.path))).collect();
+
+        return Added {
+            entries: config.paths(),
+            bind_mounted: config.entries.iter().filter(|entry| return entry.bind_mount).map(|entry| return entry.path.clone()).collect(),
+            launchers: config.entries.iter().filter(|entry| return entry.launcher).map(|entry| return entry.path.clone()).collect(),
+            flatpaks: config.flatpaks,
+            // The master switch is off if any wrapped executable currently has its offload masked
+            offload_enabled: !wrapped.iter().any(|path| return is_offload_masked(path)),
+            broken,
+            // Backup/sync tools may have replaced our symlinks with copies of the wrapper scripts
+            replaced_symlinks: wrapped.into_iter().filter(|path| return is_dereferenced_wrapper(wrapper_dir, path)).collect(),
+            off_nvidia: HashSet::new(),
+            states: HashMap::new(),
+            states_of: (Vec::new(), false),
+            checked: HashSet::new(),
+        }
+    }
+}
+
+impl MyApp {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        let (AppPaths { wrapper_dir, config_path, settings_path }, settings) = AppPaths::load()?;
+
+        // Offer to import wrappers from a manual setup on the first run, then remember we did
+        let first_run = !settings_path.exists();
+        if first_run {
+            settings.save(&settings_path);
+        }
+
+        let system = System::detect(&wrapper_dir);
+        let added = Added::load(&config_path, &wrapper_dir);
+        let window_state = WindowState::path().map(|path| return WindowState::load(&path)).unwrap_or_default();
+
+        return Ok(MyApp {
+            processes: Processes::read(&settings, system.nvidia_driver),
+            added,
+            system,
+            windows: Windows::default(),
+            prompts: Prompts { import: first_run, ..Prompts::default() },
+            selected_executable: window_state.selected.clone().filter(|selected| return Path::new(selected).exists()),
+            window_state,
+            wrapper_dir,
+            config_path,
+            report: None,
+            toasts: Toasts::default(),
+            last_action: None,
+            pending: None,
+            batch: None,
+            test_launch: None,
+            running_hooks: Vec::new(),
+            tray: None,
+            hotkey: None,
+            wrap_strategy: WrapStrategy::InPlaceSymlink,
+            offload_backend: OffloadBackend::Nvidia,
+            dry_run: false,
+            wrapper_env: None,
+            config_watcher: None,
+            config_changed_at: None,
+            launch_helpers: Vec::new(),
+            proton_env: PROTON_ENV.to_string(),
+            exclude_globs: settings.scan.exclude_globs.join("\n"),
+            system_paths: settings.scan.system_paths.join("\n"),
+            settings,
+            settings_path,
+            command_name: String::new(),
+            filter: String::new(),
+        })
+    }
+
+    /// How executables get wrapped right now, with the settings, the Proton environment and launch helpers as they're picked
+    fn wrap_options(&self) -> WrapOptions {
+        return WrapOptions { proton_env: parse_env_lines(&self.proton_env), helpers: self.launch_helpers.clone(), ..self.settings.wrap_options() }
+    }
+
+    /// The wrapper environment recorded for a path in the config, which it can have without being added
+    fn recorded_env(&self, selected: &str) -> WrapperEnv {
+        return Config::read(&self.config_path).env_of(&config_entry(selected).unwrap_or_else(|_| return selected.to_string()))
+    }
+
+    /// Re-wrap an entry whose backup went missing, which is only possible if the original executable is back in its place
+    fn repair_entry(&self, entry: &str) -> Result<(), Box<dyn Error>> {
+        let path = PathBuf::from(entry);
+        if fs::symlink_metadata(&path)?.file_type().is_symlink() {
+            return Err(format!("{entry} is still a symlink but its backup is gone, the original executable can't be recovered").into());
+        }
+        let config = Config::read(&self.config_path);
+        execute(&self.wrapper_dir, &path, config.backend_of(entry), &config.env_of(entry), &self.wrap_options())?;
+        return Ok(())
+    }
+
+    /// Put the original executable back in place of a copied wrapper script, dropping it from the list if it was added on its own
+    fn restore_replaced_symlink(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        revert(&self.wrapper_dir, path)?;
+        let entry = path.display().to_string();
+        if self.added.entries.remove(&entry) {
+            remove_config(&entry, &self.config_path);
+        }
+        return Ok(())
+    }
+
+    /// Add the entries of an exported config, wrapping them the way they were wrapped where they were exported from.
+    /// Entries whose paths don't exist here are skipped and reported, and so is anything adding it by hand would refuse or ask about first.
+    /// Flatpak apps get offloaded with the backend they had if they're installed here
+    fn import_config(&mut self, import_path: &Path) {
+        let exported = match read_exported_config(import_path) {
+            Ok(exported) => exported,
+            Err(e) => {
+                self.report = Some(format!("Failed to import {}: {e}", import_path.display()));
+                return;
+            }
+        };
+
+        let (mut imported, mut missing, mut failed, mut unconfirmed) = (0, Vec::new(), Vec::new(), Vec::new());
+        for entry in exported.entries {
+            if self.added.entries.contains(&entry.path) { continue; }
+            let path = PathBuf::from(&entry.path);
+            if fs::symlink_metadata(&path).is_err() {
+                missing.push(entry.path);
+                continue;
+            }
+            // A shared config could carry anything, so it gets the same checks as adding by hand
+            if let Some(key) = entry.env.invalid_key() {
+                error!("Not importing {}, {key:?} isn't a valid environment variable name", entry.path);
+                failed.push(entry.path);
+                continue;
+            }
+            if let Some(prefix) = system_path_match(&path, &self.settings.scan.system_paths) {
+                error!("Not importing {}, it's under {prefix} which is in the system paths", entry.path);
+                failed.push(entry.path);
+                continue;
+            }
+            if anti_cheat_match(&path, &self.settings.anti_cheat_patterns).is_some() || shared_path_reason(&path).is_some() {
+                unconfirmed.push(entry.path);
+                continue;
+            }
+
+            // Something already wrapped (like when importing on the same machine) only has to be added, wrapping it again would revert it
+            let result = if wrapper_state(&path) != WrapperState::Disabled || is_bind_mounted(&path) {
+                Ok(None)
+            } else if entry.launcher {
+                let extra_env = if is_proton_path(&path) { parse_env_lines(&self.proton_env) } else { Vec::new() };
+                create_launcher(&path, entry.backend, &entry.env, &extra_env, &self.launch_helpers).map(|_| return None)
+            } else if entry.bind_mount {
+                let extra_env = if is_proton_path(&path) { parse_env_lines(&self.proton_env) } else { Vec::new() };
+                bind_mount_wrapper(&self.wrapper_dir, &path, entry.backend, &entry.env, &extra_env, &self.launch_helpers).map(|()| return None)
+            } else {
+                execute(&self.wrapper_dir, &path, entry.backend, &entry.env, &self.wrap_options())
+                    .map(|summary| return summary.resolved)
+            };
+            // A symlink here gets its real file wrapped, which is what the config has to keep track of
+            let selected = match result {
+                Ok(resolved) => resolved.map_or_else(|| return entry.path.clone(), |real_path| return real_path.display().to_string()),
+                Err(e) => {
+                    error!("Failed to import {}: {e}", entry.path);
+                    failed.push(entry.path);
+                    continue;
+                },
+            };
+
+            let record = |added: &mut Entry| (added.backend, added.env, added.bind_mount, added.launcher) = (entry.backend, entry.env.clone(), entry.bind_mount, entry.launcher && !entry.bind_mount);
+            match add_config(&selected, &self.config_path, record) {
+                Ok(added) => {
+                    if entry.bind_mount {
+                        self.added.bind_mounted.insert(added.clone());
+                    } else if entry.launcher {
+                        self.added.launchers.insert(added.clone());
+                    } else if !self.added.offload_enabled {
+                        // Respect the master switch, like for anything else that gets added
+                        for wrapped in wrapped_executables(Path::new(&added)) {
+                            if let Err(e) = set_offload(&self.wrapper_dir, &wrapped, false) {
+                                error!("Failed to switch the NVIDIA offload off for {}: {e}", wrapped.display());
+                            }
+                        }
+                    }
+                    self.added.entries.insert(added);
+                    imported += 1;
+                },
+                Err(e) => {
+                    error!("Failed to add {selected} to the config: {e}");
+                    failed.push(selected);
+                },
+            }
+        }
+
+        for (app_id, offload) in exported.flatpaks {
+            if self.added.flatpaks.contains_key(&app_id) { continue; }
+            if !self.system.flatpak_apps.iter().any(|app| return app.id == app_id) {
+                missing.push(app_id);
+                continue;
+            }
+            match self.offload_flatpak(&app_id, offload.backend) {
+                Ok(()) => imported += 1,
+                Err(e) => {
+                    error!("Failed to import the Flatpak app {app_id}: {e}");
+                    failed.push(app_id);
+                },
+            }
+        }
+
+        let mut report = format!("Imported {imported} entries");
+        if !missing.is_empty() {
+            let _ = write!(report, ", skipped {} that don't exist here ({})", missing.len(), missing.join(", "));
+        }
+        if !failed.is_empty() {
+            let _ = write!(report, ", failed {} ({})", failed.len(), failed.join(", "));
+        }
+        if !unconfirmed.is_empty() {
+            let _ = write!(report, ", left out {} that anti-cheats scan or other users rely on, add those one by one ({})", unconfirmed.len(), unconfirmed.join(", "));
+        }
+        self.report = Some(report);
+    }
+
+    /// Look for wrapped executables that aren't in the config yet, closing the import dialog if there aren't any
+    fn scan_for_wrapped(&mut self) {
+        self.prompts.import_candidates = find_wrapped_symlinks(&common_scan_dirs(), &self.wrapper_dir).into_iter()
+            .filter_map(|path| return path.to_str().map(ToString::to_string))
+            .filter(|path| return !self.added.entries.contains(path))
+            .map(|path| return (path, true))
+            .collect();
+        if self.prompts.import_candidates.is_empty() {
+            self.prompts.import = false;
+            self.report = Some("No untracked wrapped executables found".to_string());
+        }
+    }
+
+    /// Add the checked import candidates to the config
+    fn import_checked(&mut self) {
+        for (path, checked) in std::mem::take(&mut self.prompts.import_candidates) {
+            if !checked { continue; }
+            match add_config(&path, &self.config_path, |_| {}) {
+                Ok(entry) => { self.added.entries.insert(entry); },
+                Err(e) => error!("Failed to import {path}: {e}"),
+            }
+        }
+        self.prompts.import = false;
+    }
+
+    /// Move an entry to a new path, reverting the old location if it's still there and wrapping the new one, returning the new entry
+    fn edit_entry_path(&mut self, old_entry: &str, new_path: &str) -> Result<String, Box<dyn Error>> {
+        let new_entry = config_entry(new_path)?;
+        if new_entry == old_entry { return Ok(new_entry) }
+        if let (Some(prefix), false) = (system_path_match(Path::new(&new_entry), &self.settings.scan.system_paths), is_desktop_entry(Path::new(&new_entry))) {
+            return Err(format!("{new_entry} is in {prefix}, which is on the system path blocklist").into());
+        }
+        if self.added.entries.contains(&new_entry) {
+            return Err(format!("{new_entry} is already added").into());
+        }
+        if self.added.bind_mounted.contains(old_entry) {
+            return Err("Bind mounted entries can't be moved, remove it and add it again at the new location".into());
+        }
+
+        // The old location is either still there to revert, or gone with only its wrapper scripts left behind
+        let old_path = PathBuf::from(old_entry);
+        if fs::symlink_metadata(&old_path).is_ok() {
+            revert(&self.wrapper_dir, &old_path)?;
+        } else {
+            remove_stale_wrapper(&self.wrapper_dir, &old_path);
+        }
+        self.added.entries.remove(old_entry);
+        let (backend, env) = remove_config(old_entry, &self.config_path).map_or_else(|| return (OffloadBackend::Nvidia, WrapperEnv::default()), |old| return (old.backend, old.env));
+
+        // The new location may have been moved there wrapped, which has to be undone before wrapping it again
+        let new_path = PathBuf::from(&new_entry);
+        restore_moved(&new_path)?;
+        let summary = execute(&self.wrapper_dir, &new_path, backend, &env, &self.wrap_options())?;
+        // A symlink gets its real file wrapped, which is what the config has to keep track of
+        let (new_entry, new_path) = match summary.resolved {
+            Some(real_path) => (real_path.display().to_string(), real_path),
+            None => (new_entry, new_path),
+        };
+        if !self.added.offload_enabled {
+            for path in wrapped_executables(&new_path) {
+                set_offload(&self.wrapper_dir, &path, false)?;
+            }
+        }
+        add_config(&new_entry, &self.config_path, |entry| {
+            entry.backend = backend;
+            entry.env = env;
+        })?;
+        self.added.entries.insert(new_entry.clone());
+        return Ok(new_entry)
+    }
+
+    /// Start watching the config for changes made outside the GUI (by hand or through the CLI)
+    fn watch_config(&mut self, ctx: &Context) {
+        let (sender, receiver) = mpsc::channel();
+        let config_path = self.config_path.clone();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|event| return event.paths.contains(&config_path)) {
+                let _ = sender.send(());
+                ctx.request_repaint();
+            }
+        });
+
+        // Editors often replace the file instead of writing to it, so watch the folder rather than the file itself
+        let Some(config_dir) = self.config_path.parent() else { return; };
+        match watcher.and_then(|mut watcher| return watcher.watch(config_dir, RecursiveMode::NonRecursive).map(|()| return watcher)) {
+            Ok(watcher) => self.config_watcher = Some((watcher, receiver)),
+            Err(e) => error!("Failed to watch {} for changes: {e}", self.config_path.display()),
+        }
+    }
+
+    /// Reload the config once it stopped changing for a moment, so a burst of writes only reloads it once
+    fn reload_config_if_changed(&mut self, ctx: &Context) {
+        let Some((_, receiver)) = &self.config_watcher else { return; };
+        if receiver.try_iter().count() > 0 {
+            self.config_changed_at = Some(Instant::now());
+        }
+        let Some(changed_at) = self.config_changed_at else { return; };
+        if changed_at.elapsed() < CONFIG_RELOAD_DEBOUNCE {
+            ctx.request_repaint_after(CONFIG_RELOAD_DEBOUNCE.saturating_sub(changed_at.elapsed()));
+            return;
+        }
+        self.config_changed_at = None;
+
+        // Anything the UI holds on to for entries that are gone is dropped
+        self.added.entries = Config::read(&self.config_path).paths();
+        self.added.checked.retain(|entry| return self.added.entries.contains(entry));
+        self.added.off_nvidia.retain(|entry| return self.added.entries.contains(entry));
+        self.added.broken.retain(|(entry, _)| return self.added.entries.contains(entry));
+        if self.windows.editing_entry.as_ref().is_some_and(|(old_entry, _)| return !self.added.entries.contains(old_entry)) {
+            self.windows.editing_entry = None;
+        }
+    }
+
+    /// Run the `post_wrap` or `post_unwrap` hook on an entry if one is set. A hook can take up to 30 seconds, so it runs on its own thread and `poll_hooks` reports it if it failed
+    fn run_hook(&mut self, wrapped: bool, entry: &str) {
+        let (name, hook) = if wrapped { ("post_wrap", &self.settings.post_wrap) } else { ("post_unwrap", &self.settings.post_unwrap) };
+        let Some(hook) = hook.clone() else { return; };
+        let entry = entry.to_string();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(hook_warning(name, &hook, &entry));
+        });
+        self.running_hooks.push(receiver);
+    }
+
+    /// Report the hooks that failed once they're done, keeping the window repainting until they all are
+    fn poll_hooks(&mut self, ctx: &Context) {
+        let mut warnings = Vec::new();
+        self.running_hooks.retain(|receiver| {
+            return match receiver.try_recv() {
+                Ok(warning) => {
+                    warnings.extend(warning);
+                    false
+                },
+                Err(mpsc::TryRecvError::Empty) => true,
+                Err(mpsc::TryRecvError::Disconnected) => false,
+            }
+        });
+        // Every failed one is in the log, the report only has room for one
+        if let Some(warning) = warnings.pop() {
+            self.report = Some(warning);
+        }
+        if !self.running_hooks.is_empty() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    /// Revert every checked entry in the list of added processes, carrying on past failures
+    fn revert_checked(&mut self) {
+        self.revert_entries(self.added.checked.clone());
+    }
+
+    /// Revert the given entries on their own thread, since there can be many and the ones wrapped as root wait on the password prompt.
+    /// `finish_revert` picks up how it went
+    fn revert_entries(&mut self, entries: HashSet<String>) {
+        if let Some(pending) = &self.pending {
+            self.report = Some(format!("Still busy with {}", pending.selected));
+            return;
+        }
+        let selected = format!("{} entries", entries.len());
+        let (wrapper_dir, bind_mounted, launchers, post_unwrap) = (self.wrapper_dir.clone(), self.added.bind_mounted.clone(), self.added.launchers.clone(), self.settings.post_unwrap.clone());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let results = revert_all(&wrapper_dir, entries, &bind_mounted, &launchers);
+            // The hooks run right here too, so the report can tell how many failed
+            let failed_hooks = post_unwrap.map_or(0, |hook| {
+                return results.iter().filter(|(entry, result)| return result.is_ok() && hook_warning("post_unwrap", &hook, entry).is_some()).count()
+            });
+            let _ = sender.send(Finished::Revert(results, failed_hooks));
+        });
+        self.pending = Some(PendingExecute { selected, backend: self.offload_backend, env: WrapperEnv::default(), receiver });
+    }
+
+    /// Update the config and the list after reverting several entries, reporting how many failed and keeping those checked so they can be retried
+    fn finish_revert(&mut self, results: Vec<(String, Result<(), WrapError>)>, failed_hooks: usize) {
+        let mut reverted = Vec::new();
+        let mut failed = 0;
+        for (entry, result) in results {
+            match result {
+                Ok(()) => {
+                    self.added.checked.remove(&entry);
+                    self.added.entries.remove(&entry);
+                    self.added.bind_mounted.remove(&entry);
+                    self.added.launchers.remove(&entry);
+                    reverted.push(entry);
+                },
+                Err(e) => {
+                    error!("Failed to revert {entry}: {e}");
+                    self.added.checked.insert(entry); // Keep it checked so it can be retried
+                    failed += 1;
+                }
+            }
+        }
+        // All of them are removed in one go, there can be many
+        let removed = Config::update(&self.config_path, |config| {
+            for entry in &reverted {
+                config.remove(entry);
+            }
+        });
+        if let Err(e) = removed {
+            error!("Failed to remove the reverted entries from the config: {e}");
+        }
+        let mut report = format!("Reverted {} entries ({failed} failed)", reverted.len());
+        if failed > 0 {
+            report.push_str(", the failed ones are checked");
+        }
+        if failed_hooks > 0 {
+            let _ = write!(report, ", the post_unwrap hook failed for {failed_hooks} of them");
+        }
+        self.report = Some(report);
+    }
+
+    /// Move the wrapper scripts out of a noexec mount into a directory under the home folder
+    fn relocate_wrapper_dir(&mut self) {
+        let Some(home) = std::env::var_os("HOME") else {
+            self.report = Some("Can't relocate the wrapper scripts, HOME is not set".to_string());
+            return;
+        };
+        let new_dir = PathBuf::from(home).join(".local/lib/NvidiaManager/wrappers");
+        if let Err(e) = fs::create_dir_all(&new_dir) {
+            self.report = Some(format!("Failed to create {}: {e}", new_dir.display()));
+            return;
+        }
+        if mount_options_for(&new_dir).iter().any(|option| return option == "noexec") {
+            self.report = Some(format!("{} is on a noexec mount too, pick a wrapper directory manually in {}", new_dir.display(), self.settings_path.display()));
+            return;
+        }
+
+        let executables: Vec<PathBuf> = self.added.entries.iter()
+            .flat_map(|entry| return wrapped_executables(&PathBuf::from(entry)))
+            .collect();
+        match relocate_wrappers(&self.wrapper_dir, &new_dir, &executables) {
+            Ok(()) => {
+                self.wrapper_dir.clone_from(&new_dir);
+                self.settings.wrapper_dir = Some(new_dir);
+                self.settings.save(&self.settings_path);
+                self.system.wrapper_dir_noexec = false;
+                self.report = Some(format!("Moved the wrapper scripts to {}", self.wrapper_dir.display()));
+            },
+            Err(e) => self.report = Some(format!("Failed to move the wrapper scripts: {e}")),
+        }
+    }
+
+    /// Switch the NVIDIA offload on or off for every added executable at once, keeping the config as is
+    fn set_global_offload(&mut self, enabled: bool) {
+        let mut switched = 0;
+        let mut failed = 0;
+        // Bind mounts and launchers have no symlink to switch
+        for entry in self.added.entries.iter().filter(|entry| return !self.added.bind_mounted.contains(*entry) && !self.added.launchers.contains(*entry)) {
+            for path in wrapped_executables(&PathBuf::from(entry)) {
+                match set_offload(&self.wrapper_dir, &path, enabled) {
+                    Ok(()) => switched += 1,
+                    Err(e) => {
+                        error!("Failed to switch the NVIDIA offload for {}: {e}", path.display());
+                        failed += 1;
+                    }
+                }
+            }
+        }
+        self.added.offload_enabled = enabled;
+        self.report = Some(format!("Turned offload {} for {switched} executables ({failed} failed)", if enabled { "on" } else { "off" }));
+    }
+}
+
+impl MyApp {
+    /// Add or remove the given executable, depending on whether it's already wrapped.
+    /// It's added with the picked backend and strategy and the environment recorded for it
+    fn add_or_remove(&mut self, selected: &str) {
+        let env = self.recorded_env(selected);
+        self.add_or_remove_with(selected, self.offload_backend, self.wrap_strategy, env);
+    }
+
+    /// Add or remove the given executable, adding it with the given backend, strategy and environment
+    fn add_or_remove_with(&mut self, selected: &str, backend: OffloadBackend, strategy: WrapStrategy, env: WrapperEnv) {
+        // One at a time, the list and config are only up to date once the running one is done
+        if let Some(pending) = &self.pending {
+            self.report = Some(format!("Still busy with {}", pending.selected));
+            return;
+        }
+
+        // Only print what would be done, leaving the files and the config alone
+        if self.dry_run {
+            if self.added.bind_mounted.contains(selected) || (strategy == WrapStrategy::BindMount && !is_desktop_entry(Path::new(selected))) {
+                info!("[dry run] Would {} the wrapper of {selected}", if self.added.bind_mounted.contains(selected) { "unmount" } else { "bind mount" });
+            } else if self.added.launchers.contains(selected) || (strategy == WrapStrategy::SeparateLauncher && Path::new(selected).is_file() && !is_desktop_entry(Path::new(selected))) {
+                let launcher = launcher_path(Path::new(selected)).map_or_else(|| return "a launcher".to_string(), |launcher| return launcher.display().to_string());
+                info!("[dry run] Would {} {launcher} for {selected}", if self.added.launchers.contains(selected) { "remove" } else { "write" });
+            } else if self.settings.wrapping.elevated && !self.added.entries.contains(selected) && needs_elevation(Path::new(selected)) {
+                info!("[dry run] Would wrap {selected} as root through pkexec");
+            } else if let Err(e) = execute(&self.wrapper_dir, &PathBuf::from(selected), backend, &env, &WrapOptions { dry_run: true, ..self.wrap_options() }) {
+                self.report = Some(wrap_error_report(&e));
+                return;
+            }
+            self.report = Some(format!("Dry run, nothing was changed. What it would have done to {selected} is in the log"));
+            self.windows.log = true;
+            return;
+        }
+
+        // Bind mounted entries are reverted by unmounting them, new ones only get bind mounted when that strategy is picked (desktop entries never are, they're only text)
+        let bind_mount = strategy == WrapStrategy::BindMount && !self.added.entries.contains(selected) && !is_desktop_entry(Path::new(selected));
+        if self.added.bind_mounted.contains(selected) || bind_mount {
+            self.mount_or_unmount(selected, backend, &env);
+            return;
+        }
+        // Launchers are only for single executables, folders and desktop entries get wrapped like they would otherwise
+        let separate_launcher = strategy == WrapStrategy::SeparateLauncher && !self.added.entries.contains(selected) && Path::new(selected).is_file() && !is_desktop_entry(Path::new(selected));
+        if self.added.launchers.contains(selected) || separate_launcher {
+            self.create_or_remove_launcher(selected, backend, &env);
+            return;
+        }
+        // Executables we can't write to only get wrapped as root when that's turned on, reverting them asks for root by itself
+        if self.settings.wrapping.elevated && !self.added.entries.contains(selected) && needs_elevation(Path::new(selected)) {
+            self.wrap_as_root(selected, backend, env);
+            return;
+        }
+
+        // Wrapping a big folder takes a while, so it runs on its own thread and `poll_pending` picks up the result
+        let (wrapper_dir, path, options, used_env) = (self.wrapper_dir.clone(), PathBuf::from(selected), self.wrap_options(), env.clone());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(Finished::Execute(execute(&wrapper_dir, &path, backend, &used_env, &options))); // Execute the main logic
+        });
+        self.pending = Some(PendingExecute { selected: selected.to_string(), backend, env, receiver });
+    }
+
+    /// Read the running processes again, adding the new ones to the list (the selection is left alone)
+    fn refresh_processes(&mut self) {
+        match self.processes.cache.executables(&Procfs, &self.settings.scan.system_paths, !self.settings.wrapping.elevated) {
+            Ok(executables) => self.processes.executables.extend(executables),
+            Err(e) => error!("Failed to read the running processes: {e}"),
+        }
+        self.processes.recent = sort_by_last_used(self.processes.executables.keys().cloned()).into_iter().take(RECENT_COUNT).collect();
+        self.processes.window_pids = window_pids().ok();
+        self.processes.nvidia_pids = if self.system.nvidia_driver { nvidia_pids().ok() } else { None };
+        self.processes.refreshed_at = Instant::now();
+    }
+
+    /// Refresh the processes every `refresh_interval` seconds, if that's turned on
+    fn auto_refresh_processes(&mut self, ctx: &Context) {
+        if self.settings.refresh_interval == 0 { return; }
+        let interval = Duration::from_secs(self.settings.refresh_interval);
+        if self.processes.refreshed_at.elapsed() >= interval {
+            self.refresh_processes();
+        }
+        ctx.request_repaint_after(interval.saturating_sub(self.processes.refreshed_at.elapsed()));
+    }
+
+    /// Pick up the result of a running Add/Remove once it's done, keeping the window repainting until then
+    fn poll_pending(&mut self, ctx: &Context) {
+        let Some(pending) = &self.pending else { return; };
+        let result = match pending.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => {
+                ctx.request_repaint_after(Duration::from_millis(100));
+                return;
+            },
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.report = Some(format!("Working on {} stopped unexpectedly, check the log", pending.selected));
+                self.pending = None;
+                self.continue_batch();
+                return;
+            },
+        };
+        let Some(PendingExecute { selected, backend, env, .. }) = self.pending.take() else { return; };
+        match result {
+            Finished::Execute(result) => self.finish_add_or_remove(&selected, backend, env, result),
+            Finished::WrapAsRoot(result) => self.finish_wrap_as_root(&selected, backend, env, result),
+            Finished::Revert(results, failed_hooks) => self.finish_revert(results, failed_hooks),
+        }
+        self.continue_batch();
+    }
+
+    /// What launching an added entry runs, which is its launcher for the separate launcher strategy. Folders and .desktop entries have nothing to launch
+    fn launch_path_of(&self, entry: &str) -> Option<PathBuf> {
+        let path = Path::new(entry);
+        if self.added.launchers.contains(entry) {
+            return launcher_path(path)
+        }
+        return Some(path.to_path_buf()).filter(|_| return path.is_file() && !is_desktop_entry(path))
+    }
+
+    /// Launch an added entry on its own thread, `poll_test_launch` picks up how it went
+    fn start_test_launch(&mut self, entry: &str, ctx: &Context) {
+        let Some(path) = self.launch_path_of(entry) else { return; };
+        info!("Test launching {}", path.display());
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(test_launch(&path));
+            ctx.request_repaint();
+        });
+        self.test_launch = Some((entry.to_string(), receiver));
+    }
+
+    fn poll_test_launch(&mut self) {
+        let Some((entry, receiver)) = &self.test_launch else { return; };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err(io::Error::other("it stopped unexpectedly")),
+        };
+        let (report, worked) = match result {
+            Ok(TestLaunch::OnNvidia) => (format!("{entry} is running on the NVIDIA GPU"), true),
+            Ok(TestLaunch::NotOnNvidia) => (format!("{entry} is running, but it didn't show up in nvidia-smi. It may not use the GPU at all, or the wrapper got bypassed"), false),
+            Ok(TestLaunch::Exited(status)) => (format!("{entry} exited ({status}) before it showed up in nvidia-smi, launchers that start the app and exit right away can't be followed"), false),
+            Ok(TestLaunch::Unchecked) => (format!("Launched {entry}, but nvidia-smi isn't available to check which GPU it's on"), true),
+            Err(e) => (format!("Failed to test launch {entry}: {e}"), false),
+        };
+        info!("{report}");
+        if worked {
+            self.toasts.info(report.clone());
+        } else {
+            self.toasts.error(report.clone());
+        }
+        self.report = Some(report);
+        self.test_launch = None;
+    }
+
+    /// Add every checked process that's not added yet. The ones that would ask for confirmation first are left out and stay checked, to be added one by one
+    fn add_checked(&mut self) {
+        let mut paths: Vec<String> = std::mem::take(&mut self.processes.checked).into_iter()
+            .filter(|path| return !self.added.entries.contains(path))
+            .collect();
+        paths.sort();
+        // They're counted as failed, the log tells why
+        let total = paths.len();
+        paths.retain(|path| {
+            let blocked = self.blocklisted(path);
+            if let Some(prefix) = &blocked {
+                error!("Not adding {path}, it's in {prefix} which is on the system path blocklist");
+            }
+            return blocked.is_none()
+        });
+        let (skipped, queued): (Vec<String>, Vec<String>) = paths.into_iter().partition(|path| {
+            return anti_cheat_match(Path::new(path), &self.settings.anti_cheat_patterns).is_some() || shared_path_reason(Path::new(path)).is_some()
+        });
+        self.batch = Some(BatchAdd { total: total - skipped.len(), queued: queued.into(), added: 0, skipped });
+        self.continue_batch();
+    }
+
+    /// Start the next process of an Add Checked once the one before is done, and report how it went after the last one
+    fn continue_batch(&mut self) {
+        while self.pending.is_none() {
+            let Some(batch) = &mut self.batch else { return; };
+            let Some(next) = batch.queued.pop_front() else { break; };
+            self.add_or_remove(&next);
+        }
+        if self.pending.is_some() { return; }
+
+        let Some(batch) = self.batch.take() else { return; };
+        let failed = batch.total.saturating_sub(batch.added);
+        let mut report = format!("Added {} of {} checked processes ({failed} failed)", batch.added, batch.total);
+        if !batch.skipped.is_empty() {
+            let _ = write!(report, ", {} would change something anti-cheats scan or other users rely on and are still checked, add those one by one", batch.skipped.len());
+        }
+        if failed > 0 {
+            self.toasts.error(report.clone());
+        } else {
+            self.toasts.info(report.clone());
+        }
+        self.report = Some(report);
+        self.processes.checked.extend(batch.skipped);
+    }
+
+    /// Count an entry of a running Add Checked as added
+    fn count_batch_added(&mut self) {
+        if let Some(batch) = &mut self.batch {
+            batch.added += 1;
+        }
+    }
+
+    /// Update the config and the list after `execute` is done, which only happens when it worked
+    fn finish_add_or_remove(&mut self, selected: &str, backend: OffloadBackend, env: WrapperEnv, result: Result<ExecuteSummary, WrapError>) {
+        match result {
+            Ok(summary) => {
+                // Folders are done executable by executable, some of which may have failed
+                let is_dir = PathBuf::from(selected).is_dir();
+                if is_dir {
+                    self.report = Some(format!("{selected}: {}", summary.describe()));
+                    self.windows.folder_results = Some(FolderResults::of(selected, &summary));
+                }
+                match (summary.reverted, is_dir) {
+                    (true, false) => self.toasts.info(format!("Reverted {selected}")),
+                    (true, true) => self.toasts.info(format!("Reverted {} executables in {selected}", summary.succeeded)),
+                    (false, false) if summary.succeeded > 0 => self.toasts.info(format!("Enabled GPU offload for {selected} ({})", backend.label())),
+                    (false, true) if summary.succeeded > 0 => self.toasts.info(format!("Enabled GPU offload for {} executables in {selected} ({})", summary.succeeded, backend.label())),
+                    _ => {},
+                }
+                if !summary.failed.is_empty() {
+                    self.toasts.error(format!("{} executables in {selected} failed, see the log", summary.failed.len()));
+                }
+                if summary.reverted { // If we reverted our changes then remove it from the list and config file, otherwise add it
+                    // Whatever failed to revert is still wrapped, so it stays in the list to try again
+                    if !summary.failed.is_empty() { return; }
+                    self.added.entries.remove(selected);
+                    let removed = remove_config(selected, &self.config_path);
+                    self.last_action = Some(LastAction::removed(selected, removed));
+                    self.run_hook(false, selected);
+                    return;
+                }
+                if summary.succeeded == 0 { return; } // Nothing in the folder could be wrapped
+                self.count_batch_added();
+                // A symlink gets its real file wrapped, which is what the config has to keep track of
+                let resolved = summary.resolved.map(|real_path| return real_path.display().to_string());
+                if let Some(real_path) = &resolved {
+                    self.report = Some(format!("{selected} is a symlink, wrapped {real_path} instead"));
+                }
+                let selected = resolved.as_deref().unwrap_or(selected);
+                match add_config(selected, &self.config_path, |entry| (entry.backend, entry.env) = (backend, env.clone())) {
+                    Ok(entry) => {
+                        self.last_action = Some(LastAction { entry: entry.clone(), wrapped: true, backend, env, strategy: WrapStrategy::InPlaceSymlink });
+                        self.added.entries.insert(entry);
+                    },
+                    Err(e) => error!("Failed to add {selected} to the config: {e}"),
+                }
+
+                // Respect the master switch for newly added executables
+                if !self.added.offload_enabled {
+                    for path in wrapped_executables(&PathBuf::from(selected)) {
+                        if let Err(e) = set_offload(&self.wrapper_dir, &path, false) {
+                            error!("Failed to switch the NVIDIA offload off for {}: {e}", path.display());
+                        }
+                    }
+                }
+
+                self.run_hook(true, selected);
+            },
+            Err(e) => { // If there's an error, log it
+                error!("Failed to execute the wrapper script for {selected}: {e:?}");
+                if matches!(e, WrapError::BackupMismatch(_)) {
+                    self.prompts.backup_mismatch = Some(selected.to_string());
+                }
+                self.report = Some(wrap_error_report(&e));
+                self.toasts.error(format!("{selected}: {}", wrap_error_report(&e)));
+            }
+        }
+    }
+
+    /// Do the opposite of the last Add/Remove, wrapping again the way it was wrapped before. Undoing an undo redoes it
+    fn undo_last_action(&mut self) {
+        let Some(action) = self.last_action.take() else { return; };
+        if action.wrapped != self.added.entries.contains(&action.entry) {
+            self.report = Some(format!("{} changed since, there's nothing to undo", action.entry));
+            return;
+        }
+
+        self.add_or_remove_with(&action.entry, action.backend, action.strategy, action.env);
+    }
+
+    /// Revert an entry whose backup doesn't match the wrapped original anymore, after the user said to
+    fn revert_mismatched(&mut self, entry: &str) {
+        if let Err(e) = revert_unverified(&self.wrapper_dir, Path::new(entry)) {
+            self.report = Some(wrap_error_report(&e));
+            return;
+        }
+        self.added.entries.remove(entry);
+        remove_config(entry, &self.config_path);
+        self.report = None;
+        self.run_hook(false, entry);
+    }
+
+    /// Bind mount the wrapper over the given executable, or unmount it if it's already bind mounted
+    fn mount_or_unmount(&mut self, selected: &str, backend: OffloadBackend, env: &WrapperEnv) {
+        let path = PathBuf::from(selected);
+        if self.added.bind_mounted.contains(selected) {
+            match unmount_wrapper(&path) {
+                Ok(()) => {
+                    self.added.bind_mounted.remove(selected);
+                    self.added.entries.remove(selected);
+                    let removed = remove_config(selected, &self.config_path);
+                    self.last_action = Some(LastAction::removed(selected, removed));
+                    self.toasts.info(format!("Unmounted the wrapper from {selected}"));
+                    self.run_hook(false, selected);
+                },
+                Err(e) => {
+                    self.report = Some(wrap_error_report(&e));
+                    self.toasts.error(format!("{selected}: {}", wrap_error_report(&e)));
+                },
+            }
+            return;
+        }
+
+        let extra_env = if is_proton_path(&path) { parse_env_lines(&self.proton_env) } else { Vec::new() };
+        if let Err(e) = bind_mount_wrapper(&self.wrapper_dir, &path, backend, env, &extra_env, &self.launch_helpers) {
+            self.report = Some(wrap_error_report(&e));
+            self.toasts.error(format!("{selected}: {}", wrap_error_report(&e)));
+            return;
+        }
+        self.toasts.info(format!("Enabled GPU offload for {selected} until the next reboot ({})", backend.label()));
+        self.count_batch_added();
+        let record = |entry: &mut Entry| {
+            entry.bind_mount = true;
+            (entry.backend, entry.env) = (backend, env.clone());
+        };
+        match add_config(selected, &self.config_path, record) {
+            Ok(entry) => {
+                self.last_action = Some(LastAction { entry: entry.clone(), wrapped: true, backend, env: env.clone(), strategy: WrapStrategy::BindMount });
+                self.added.bind_mounted.insert(entry.clone());
+                self.added.entries.insert(entry);
+            },
+            Err(e) => error!("Failed to add {selected} to the config: {e}"),
+        }
+        self.run_hook(true, selected);
+    }
+
+    /// Wrap an executable we can't write to in place, with root's help for just that.
+    /// It waits on the password prompt, so it runs on its own thread like any other Add/Remove and `poll_pending` picks up the result
+    fn wrap_as_root(&mut self, selected: &str, backend: OffloadBackend, env: WrapperEnv) {
+        let path = PathBuf::from(selected);
+        let extra_env = if is_proton_path(&path) { parse_env_lines(&self.proton_env) } else { Vec::new() };
+        let (wrapper_dir, launch_helpers, used_env) = (self.wrapper_dir.clone(), self.launch_helpers.clone(), env.clone());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(Finished::WrapAsRoot(wrap_elevated(&wrapper_dir, &path, backend, &used_env, &extra_env, &launch_helpers)));
+        });
+        self.pending = Some(PendingExecute { selected: selected.to_string(), backend, env, receiver });
+    }
+
+    /// Update the config and the list after `wrap_elevated` is done, which only happens when it worked
+    fn finish_wrap_as_root(&mut self, selected: &str, backend: OffloadBackend, env: WrapperEnv, result: Result<(), WrapError>) {
+        if let Err(e) = result {
+            self.report = Some(wrap_error_report(&e));
+            self.toasts.error(format!("{selected}: {}", wrap_error_report(&e)));
+            return;
+        }
+        self.toasts.info(format!("Enabled GPU offload for {selected} as root ({})", backend.label()));
+        self.count_batch_added();
+        match add_config(selected, &self.config_path, |entry| (entry.backend, entry.env) = (backend, env.clone())) {
+            Ok(entry) => {
+                self.last_action = Some(LastAction { entry: entry.clone(), wrapped: true, backend, env, strategy: WrapStrategy::InPlaceSymlink });
+                self.added.entries.insert(entry);
+            },
+            Err(e) => error!("Failed to add {selected} to the config: {e}"),
+        }
+        self.run_hook(true, selected);
+    }
+
+    /// Write a separate launcher for the given executable, or remove it if it already has one
+    fn create_or_remove_launcher(&mut self, selected: &str, backend: OffloadBackend, env: &WrapperEnv) {
+        let path = PathBuf::from(selected);
+        if self.added.launchers.contains(selected) {
+            match remove_launcher(&path) {
+                Ok(()) => {
+                    self.added.launchers.remove(selected);
+                    self.added.entries.remove(selected);
+                    let removed = remove_config(selected, &self.config_path);
+                    self.last_action = Some(LastAction::removed(selected, removed));
+                    self.toasts.info(format!("Removed the launcher of {selected}"));
+                    self.run_hook(false, selected);
+                },
+                Err(e) => {
+                    self.report = Some(wrap_error_report(&e));
+                    self.toasts.error(format!("{selected}: {}", wrap_error_report(&e)));
+                },
+            }
+            return;
+        }
+
+        let extra_env = if is_proton_path(&path) { parse_env_lines(&self.proton_env) } else { Vec::new() };
+        let launcher = match create_launcher(&path, backend, env, &extra_env, &self.launch_helpers) {
+            Ok(launcher) => launcher,
+            Err(e) => {
+                self.report = Some(wrap_error_report(&e));
+                self.toasts.error(format!("{selected}: {}", wrap_error_report(&e)));
+                return;
+            }
+        };
+        self.toasts.info(format!("Wrote {} with GPU offload for {selected} ({})", launcher.display(), backend.label()));
+        self.count_batch_added();
+        let record = |entry: &mut Entry| {
+            entry.launcher = true;
+            (entry.backend, entry.env) = (backend, env.clone());
+        };
+        match add_config(selected, &self.config_path, record) {
+            Ok(entry) => {
+                self.last_action = Some(LastAction { entry: entry.clone(), wrapped: true, backend, env: env.clone(), strategy: WrapStrategy::SeparateLauncher });
+                self.added.launchers.insert(entry.clone());
+                self.added.entries.insert(entry);
+                self.report = Some(format!("Launch {} to run {selected} on the NVIDIA GPU", launcher.display()));
+            },
+            Err(e) => error!("Failed to add {selected} to the config: {e}"),
+        }
+        self.run_hook(true, selected);
+    }
+
+    /// Add the app of the focused window, for when it just launched on the wrong GPU
+    fn add_focused_app(&mut self) {
+        let exe = match focused_executable() {
+            Ok(exe) => exe,
+            Err(e) => {
+                self.report = Some(format!("Couldn't find the app of the focused window: {e}"));
+                return;
+            }
+        };
+
+        // A wrapped app runs from its backup, so it's already been added
+        if resolve_backup(&exe) != exe || exe.starts_with(&self.wrapper_dir) {
+            self.report = Some(format!("{} is already added", exe.display()));
+            return;
+        }
+        let selected = exe.display().to_string();
+        if self.added.entries.contains(&selected) { return; }
+        self.report = Some(format!("Adding {selected}, it'll use the NVIDIA GPU from its next launch"));
+        self.selected_executable = Some(selected.clone());
+        self.confirm_add_or_remove(selected);
+    }
+
+    /// The entry of the system path blocklist a path to add is in, going by where it really is too. Desktop entries are only text, those are fine anywhere
+    fn blocklisted(&self, selected: &str) -> Option<String> {
+        let path = Path::new(selected);
+        if is_desktop_entry(path) { return None }
+        return system_path_match(path, &self.settings.scan.system_paths)
+            .or_else(|| return path.canonicalize().ok().and_then(|real_path| return system_path_match(&real_path, &self.settings.scan.system_paths)))
+    }
+
+    /// Add or remove the given executable, refusing anything on the system path blocklist and asking first before modifying anything an anti-cheat might scan (reverting is always fine).
+    /// Everything that adds goes through here, so whichever way it got picked the blocklist applies
+    fn confirm_add_or_remove(&mut self, selected: String) {
+        let adding = !self.added.entries.contains(&selected);
+        if let (Some(prefix), true) = (self.blocklisted(&selected), adding) {
+            self.report = Some(format!("{selected} is in {prefix}, which is on the system path blocklist"));
+            self.toasts.error(format!("Not adding {selected}, it's on the system path blocklist"));
+            return;
+        }
+        match anti_cheat_match(&PathBuf::from(&selected), &self.settings.anti_cheat_patterns) {
+            Some(pattern) if adding => self.prompts.anti_cheat = Some((selected, pattern)),
+            _ => self.confirm_shared_path(selected),
+        }
+    }
+
+    /// Add or remove the given executable, asking first before modifying anything other users might rely on
+    fn confirm_shared_path(&mut self, selected: String) {
+        let adding = !self.added.entries.contains(&selected);
+        match shared_path_reason(Path::new(&selected)) {
+            Some(reason) if adding => self.prompts.shared_path = Some((selected, reason)),
+            _ => self.add_or_remove(&selected),
+        }
+    }
+
+    /// Do what the shortcuts of the main buttons are for, before any widget gets to see the keys
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        let (add_remove, refresh, revert_all) = ctx.input_mut(|input| {
+            return (input.consume_shortcut(&ADD_REMOVE_SHORTCUT), input.consume_shortcut(&REFRESH_SHORTCUT), input.consume_shortcut(&REVERT_ALL_SHORTCUT))
+        });
+        if add_remove && self.pending.is_none() {
+            if let Some(selected) = self.selected_executable.clone() {
+                self.confirm_add_or_remove(selected);
+            }
+        }
+        if refresh {
+            self.refresh_processes();
+        }
+        if revert_all && (!self.added.entries.is_empty() || !self.added.flatpaks.is_empty()) {
+            self.prompts.revert_all = true;
+        }
+    }
+
+    /// The top panel
+    fn show_top_panel(&mut self, ctx: &Context) {
+        let mut activated = None;
+        TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            self.show_setup_warnings(ui);
+            activated = self.show_process_picker(ui);
+            self.show_backend_picker(ui);
+            self.show_add_controls(ui);
+            self.show_add_options(ui);
+        });
+
+        // Enter on a process adds it, same as the button
+        if let Some(selected) = activated {
+            self.confirm_add_or_remove(selected);
+        }
+    }
+
+    /// What keeps wrapped executables from working on this machine
+    fn show_setup_warnings(&mut self, ui: &mut egui::Ui) {
+        if self.system.wrapper_dir_noexec {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::RED, format!("{} is mounted noexec, wrapped executables won't launch!", self.wrapper_dir.display()));
+                if ui.button("Relocate").clicked() {
+                    self.relocate_wrapper_dir();
+                }
+            });
+        }
+
+        if !self.system.nvidia_driver {
+            ui.colored_label(egui::Color32::RED, "The NVIDIA driver doesn't seem to be installed, executables wrapped with the NVIDIA backend won't launch properly! Use the AMD/Intel (DRI_PRIME) backend on other hybrid GPUs.");
+        }
+    }
+
+    /// The filter and the list of processes to pick from, returning the one Enter was pressed on
+    fn show_process_picker(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        let mut activated = None;
+        // Narrows down both the processes and the added list
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.text_edit_singleline(&mut self.filter);
+            if !self.filter.is_empty() && ui.small_button("Clear").clicked() {
+                self.filter.clear();
+            }
+            // Daemons and helpers flood the list otherwise, and are risky to wrap
+            if self.processes.window_pids.is_some() && ui.checkbox(&mut self.settings.show_all_processes, "Show all processes, not just apps with a window").changed() {
+                self.settings.save(&self.settings_path);
+            }
+        });
+
+        // Processes are shown as `name (pids) — path`, marked if they're on the NVIDIA GPU already. The filter matches any of it
+        let on_nvidia = |running: &RunningExecutable| return self.processes.nvidia_pids.as_ref().is_some_and(|nvidia_pids| return running.pids.iter().any(|pid| return nvidia_pids.contains(pid)));
+        let label_of = |path: &String| return self.processes.executables.get(path).map_or_else(|| return path.clone(), |running| {
+            return if on_nvidia(running) { format!("{} {NVIDIA_MARK}", running.label(path)) } else { running.label(path) }
+        });
+        let window_pids = self.processes.window_pids.as_ref().filter(|_| return !self.settings.show_all_processes);
+        let shown = |path: &String| return window_pids.is_none() || self.processes.executables.get(path)
+            .is_some_and(|running| return running.pids.iter().any(|pid| return window_pids.is_some_and(|window_pids| return window_pids.contains(pid))));
+        let selected_text = self.selected_executable.as_ref().map_or_else(|| return "Select a process".to_string(), label_of);
+        let mut processes: Vec<(String, String)> = self.processes.executables.iter()
+            .filter(|(path, _)| return shown(path))
+            .map(|(path, _)| return (path.clone(), label_of(path)))
+            .filter(|(_, label)| return matches_filter(label, &self.filter))
+            .collect();
+        processes.sort_by(|(_, label_a), (_, label_b)| return label_a.cmp(label_b));
+        egui::ComboBox::from_label("Processes (WARNING: Be careful with what processes you choose!)")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                // The apps used last are likely the ones that just ran on the wrong GPU, so they go first
+                let recent: Vec<(&String, String)> = self.processes.recent.iter()
+                    .filter(|path| return shown(path))
+                    .map(|path| return (path, label_of(path)))
+                    .filter(|(_, label)| return matches_filter(label, &self.filter))
+                    .collect();
+                let mut rows: Vec<Response> = Vec::new();
+                if !recent.is_empty() {
+                    ui.label("Recently used");
+                    rows.extend(recent.iter().map(|(process, label)| return ui.selectable_value(&mut self.selected_executable, Some((*process).clone()), highlight_filter(label, &self.filter, ui.style()))));
+                    ui.separator();
+                    ui.label("All processes");
+                }
+                rows.extend(processes.iter().map(|(process, label)| return ui.selectable_value(&mut self.selected_executable, Some(process.clone()), highlight_filter(label, &self.filter, ui.style()))));
+                let processes: Vec<&String> = recent.into_iter().map(|(process, _)| return process).chain(processes.iter().map(|(process, _)| return process)).collect();
+                if let Some((focused, enter)) = navigate_list(ui, &rows) {
+                    self.selected_executable = Some(processes[focused].clone());
+                    if enter { activated.clone_from(&self.selected_executable); }
+                }
+            });
+        if self.processes.nvidia_pids.is_some() {
+            let count = self.processes.executables.values().filter(|running| return on_nvidia(running)).count();
+            ui.label(format!("{count} processes marked {NVIDIA_MARK} are on the NVIDIA GPU right now"))
+                .on_hover_text("As listed by nvidia-smi when the processes were last refreshed");
+        }
+        // Otherwise a short list is a mystery
+        let hidden = self.processes.cache.hidden();
+        if hidden > 0 {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("{hidden} processes hidden — run with more privileges to see them"))
+                .on_hover_text("These are your own processes, but their executables can't be read without more privileges (e.g. ones that run setuid or made themselves undumpable, like some anti-cheats)");
+        }
+
+        // Several apps at once, e.g. after installing a bunch of games
+        ui.collapsing("Add several processes at once", |ui| {
+            ScrollArea::vertical().id_source("checked_processes").max_height(150.0).show(ui, |ui| {
+                for (process, label) in &processes {
+                    let mut checked = self.processes.checked.contains(process);
+                    if ui.checkbox(&mut checked, highlight_filter(label, &self.filter, ui.style())).changed() {
+                        if checked {
+                            self.processes.checked.insert(process.clone());
+                        } else {
+                            self.processes.checked.remove(process);
+                        }
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.pending.is_none() && !self.processes.checked.is_empty(), egui::Button::new(format!("Add Checked ({})", self.processes.checked.len()))).clicked() {
+                    self.add_checked();
+                }
+                if ui.add_enabled(!self.processes.checked.is_empty(), egui::Button::new("Uncheck All")).clicked() {
+                    self.processes.checked.clear();
+                }
+            });
+        });
+        return activated
+    }
+
+    /// The backend newly added executables get, and whether it works in this session
+    fn show_backend_picker(&mut self, ui: &mut egui::Ui) {
+        // Which GPU newly added executables get offloaded to
+        egui::ComboBox::from_label("Offload backend")
+            .selected_text(self.offload_backend.label())
+            .show_ui(ui, |ui| {
+                for backend in OffloadBackend::ALL {
+                    ui.selectable_value(&mut self.offload_backend, backend, backend.label());
+                }
+            });
+        // The offload variables don't reach the same apps on X11 and Wayland
+        let custom_env = self.wrapper_env.as_ref().map(|(_, env)| return env.clone()).unwrap_or_default();
+        match session_warning(self.system.session, self.offload_backend, &custom_env) {
+            Some(warning) => { ui.colored_label(egui::Color32::YELLOW, format!("{} session: {warning}", self.system.session.label())); },
+            None => { ui.label(format!("{} session", self.system.session.label())); },
+        }
+    }
+
+    /// Add/Remove and everything next to it
+    fn show_add_controls(&mut self, ui: &mut egui::Ui) {
+        // Apps launched after opening the window only show up once the processes are read again (unless auto-refresh is on in the settings)
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").on_hover_text(ui.ctx().format_shortcut(&REFRESH_SHORTCUT)).clicked() {
+                self.refresh_processes();
+            }
+            if self.settings.refresh_interval > 0 {
+                ui.label(format!("Auto-refreshing every {} s", self.settings.refresh_interval));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.pending.is_none(), egui::Button::new("Add/Remove")).on_hover_text(ui.ctx().format_shortcut(&ADD_REMOVE_SHORTCUT)).clicked() {
+                if let Some(selected) = self.selected_executable.clone() { // If an item is selected, and the button is pressed
+                    self.confirm_add_or_remove(selected);
+                }
+            }
+            if let Some(pending) = &self.pending {
+                ui.spinner();
+                match &self.batch {
+                    Some(batch) => ui.label(format!("Working on {} ({} more to add)", pending.selected, batch.queued.len())),
+                    None => ui.label(format!("Working on {}", pending.selected)),
+                };
+            }
+
+            if ui.button("File Picker").clicked() {
+                self.windows.picker = true;
+            }
+
+            let undo_hint = self.last_action.as_ref().map(|action| return format!("{} {}", if action.wrapped { "Remove" } else { "Add back" }, action.entry));
+            if ui.add_enabled(undo_hint.is_some(), egui::Button::new("Undo")).on_hover_text(undo_hint.unwrap_or_default()).clicked() {
+                self.undo_last_action();
+            }
+
+            // For launchers, without touching the executable at all
+            if ui.add_enabled(self.selected_executable.is_some(), egui::Button::new("Copy as Command")).clicked() {
+                if let Some(selected) = &self.selected_executable {
+                    let path = PathBuf::from(selected);
+                    let extra_env = if is_proton_path(&path) { parse_env_lines(&self.proton_env) } else { Vec::new() };
+                    let env = self.recorded_env(selected);
+                    let command = offload_command(&path, &self.offload_backend.env(&env), &extra_env, &self.launch_helpers);
+                    ui.output_mut(|output| output.copied_text.clone_from(&command));
+                    self.report = Some(format!("Copied {command}"));
+                }
+            }
+
+            ui.checkbox(&mut self.dry_run, "Dry run").on_hover_text("Only print what Add/Remove would change, without changing anything");
+
+            // Master switch to quickly compare with and without offloading
+            let mut offload_enabled = self.added.offload_enabled;
+            if ui.checkbox(&mut offload_enabled, "NVIDIA offload enabled").changed() {
+                self.set_global_offload(offload_enabled);
+            }
+        });
+
+        // Wrap by command name, resolved against PATH
+        ui.horizontal(|ui| {
+            ui.label("Command");
+            ui.text_edit_singleline(&mut self.command_name);
+            if ui.button("Resolve").clicked() {
+                let name = self.command_name.trim();
+                match resolve_command(name) {
+                    Some(path) => self.selected_executable = Some(path.display().to_string()),
+                    None => self.report = Some(format!("Couldn't resolve {name}, it's either not on PATH or ambiguous")),
+                }
+            }
+        });
+
+        if let Some(report) = &self.report {
+            ui.label(report);
+        }
+
+        // How new executables get wrapped
+        ui.horizontal(|ui| {
+            ui.label("Strategy");
+            for strategy in WrapStrategy::ALL {
+                ui.selectable_value(&mut self.wrap_strategy, strategy, strategy.label());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.windows.log, "Show the log");
+            ui.separator();
+            ui.toggle_value(&mut self.windows.settings, "Settings");
+        });
+    }
+
+    /// What the selected executable gets when it's added next
+    fn show_add_options(&mut self, ui: &mut egui::Ui) {
+        // Proton/Wine executables get some extra environment, let the user tweak it before adding
+        if self.selected_executable.as_deref().is_some_and(|selected| return is_proton_path(&PathBuf::from(selected))) {
+            ui.collapsing("Proton/Wine environment (one KEY=VALUE per line)", |ui| {
+                ui.text_edit_multiline(&mut self.proton_env);
+            });
+        }
+
+        // The environment of the selected executable, which its wrapper script gets on top of the backend's own (or instead of it with the custom backend)
+        if let Some(selected) = self.selected_executable.clone() {
+            ui.collapsing("Environment (applies the next time it's added)", |ui| {
+                self.show_wrapper_env_table(ui, &selected);
+            });
+        }
+
+        // Optional NVIDIA variables for video decoding and Vulkan, which not every app wants
+        if let (Some(selected), OffloadBackend::Nvidia) = (self.selected_executable.clone(), self.offload_backend) {
+            ui.collapsing("NVIDIA options (apply the next time it's added)", |ui| {
+                self.show_nvidia_extras(ui, &selected);
+            });
+        }
+
+        // Launch helpers only apply to what's added next, each entry keeps the ones it was added with
+        if !self.system.installed_helpers.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Launch through");
+                for helper in self.system.installed_helpers.clone() {
+                    let mut enabled = self.launch_helpers.contains(&helper);
+                    if ui.checkbox(&mut enabled, helper.command()).changed() {
+                        if enabled {
+                            self.launch_helpers.push(helper);
+                        } else {
+                            self.launch_helpers.retain(|launch_helper| return *launch_helper != helper);
+                        }
+                    }
+                }
+            });
+        }
+
+        // Tools like mangohud that launch the app themselves, kept per entry unlike the launch helpers
+        if let Some(selected) = self.selected_executable.clone() {
+            ui.collapsing("Command prefix (applies the next time it's added)", |ui| {
+                self.show_command_prefix(ui, &selected);
+            });
+        }
+
+        if self.selected_executable.as_deref().is_some_and(|selected| return PathBuf::from(selected).is_dir()) {
+            ui.label("Folders are wrapped whole, what gets skipped in them is up to the settings");
+        }
+    }
+
+    /// The preferences, saved as soon as they're changed
+    fn show_settings_window(&mut self, ctx: &Context) {
+        let mut open = self.windows.settings;
+        Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().id_source("settings").show(ui, |ui| {
+                    self.show_look_settings(ui);
+                    self.show_process_settings(ui);
+                    self.show_wrapping_settings(ui);
+                    self.show_folder_settings(ui);
+                    self.show_logging_settings(ui);
+                });
+            });
+        self.windows.settings = open;
+    }
+
+    /// How the window looks and behaves
+    fn show_look_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Looks");
+        ui.horizontal(|ui| {
+            ui.label("Density");
+            for density in Density::ALL {
+                if ui.selectable_value(&mut self.settings.density, density, density.name()).changed() {
+                    density.apply(ui.ctx());
+                    self.settings.save(&self.settings_path);
+                }
+            }
+        });
+        if ui.checkbox(&mut self.settings.close_to_tray, "Close to the tray instead of quitting (if the desktop has one)").changed() {
+            self.settings.save(&self.settings_path);
+        }
+    }
+
+    /// How the processes are listed and picked
+    fn show_process_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Processes");
+        ui.horizontal(|ui| {
+            let mut auto_refresh = self.settings.refresh_interval > 0;
+            if ui.checkbox(&mut auto_refresh, "Auto-refresh every").changed() {
+                self.settings.refresh_interval = if auto_refresh { 5 } else { 0 };
+                self.settings.save(&self.settings_path);
+            }
+            if auto_refresh && ui.add(egui::DragValue::new(&mut self.settings.refresh_interval).range(1..=3600).suffix(" s")).changed() {
+                self.settings.save(&self.settings_path);
+            }
+        });
+        // A different shortcut takes effect once it's registered again
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut self.settings.global_hotkey, "Add the app of the focused window with").changed() {
+                if !self.settings.global_hotkey {
+                    self.hotkey = None;
+                }
+                self.settings.save(&self.settings_path);
+            }
+            if ui.text_edit_singleline(&mut self.settings.hotkey).on_hover_text("Like ctrl+alt+N, X11 only").lost_focus() {
+                self.hotkey = None;
+                self.settings.save(&self.settings_path);
+            }
+        });
+    }
+
+    /// How executables get wrapped
+    fn show_wrapping_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Wrapping");
+        // Only applies to executables added from now on, the existing backups stay where they are
+        if ui.checkbox(&mut self.settings.wrapping.central_backups, "Keep backups in a central directory instead of next to the originals").changed() {
+            self.settings.save(&self.settings_path);
+        }
+        if ui.checkbox(&mut self.settings.wrapping.igpu_links, "Also add a <name>-igpu symlink that launches on the integrated GPU").changed() {
+            self.settings.save(&self.settings_path);
+        }
+        // Only the wrapping and reverting of those executables runs as root, the rest of the app never does
+        if ui.checkbox(&mut self.settings.wrapping.elevated, "Also list executables I can't write to, and wrap them as root (asks for the password)")
+            .on_hover_text("Their wrapper scripts go to /usr/local/lib/NvidiaManager, owned by root so they can't be changed without it")
+            .changed() {
+            self.settings.save(&self.settings_path);
+            self.refresh_processes();
+        }
+        // Only applies to wrapper scripts written from now on, the existing ones keep their shell
+        ui.horizontal(|ui| {
+            ui.label("Wrapper scripts start with");
+            for shell in WrapperShell::ALL {
+                if ui.selectable_value(&mut self.settings.wrapping.shell, shell, shell.shebang()).changed() {
+                    set_wrapper_shell(shell);
+                    self.settings.save(&self.settings_path);
+                }
+            }
+        });
+        // Executables in these are left out of the list and can't be picked, for distros with their own layout
+        ui.collapsing("Never touch executables in (one folder per line)", |ui| {
+            if ui.text_edit_multiline(&mut self.system_paths).changed() {
+                self.settings.scan.system_paths = self.system_paths.lines().map(str::trim).filter(|prefix| return !prefix.is_empty()).map(ToString::to_string).collect();
+                self.settings.save(&self.settings_path);
+                let system_paths = &self.settings.scan.system_paths;
+                self.processes.executables.retain(|path, _| return system_path_match(Path::new(path), system_paths).is_none());
+            }
+        });
+    }
+
+    /// What wrapping a folder goes through
+    fn show_folder_settings(&mut self, ui: &mut egui::Ui) {
+        // Folders get wrapped whole, let the user skip the installer and redistributable folders inside, and keep it from going through a whole project tree
+        ui.heading("Wrapping folders");
+        ui.horizontal(|ui| {
+            ui.label("Look this many folders deep (0 for all of them)");
+            if ui.add(egui::DragValue::new(&mut self.settings.scan.max_depth).range(0..=64)).changed() {
+                self.settings.save(&self.settings_path);
+            }
+        });
+        if ui.checkbox(&mut self.settings.scan.skip_hidden, "Skip hidden files and folders").changed() {
+            self.settings.save(&self.settings_path);
+        }
+        ui.collapsing("Skipped subfolders (one glob per line, * and ? within a folder name, ** across folders)", |ui| {
+            if ui.text_edit_multiline(&mut self.exclude_globs).changed() {
+                self.settings.scan.exclude_globs = self.exclude_globs.lines().map(str::trim).filter(|glob| return !glob.is_empty()).map(ToString::to_string).collect();
+                self.settings.save(&self.settings_path);
+            }
+        });
+    }
+
+    /// The log and the self-test
+    fn show_logging_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Logging");
+        ui.horizontal(|ui| {
+            ui.label("Log level");
+            for level in [LevelFilter::Error, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug] {
+                if ui.selectable_value(&mut self.settings.log_level, level, level.as_str()).changed() {
+                    logging::set_level(level);
+                    self.settings.save(&self.settings_path);
+                }
+            }
+        });
+        if let Some(log_path) = logging::log_path() {
+            ui.label(format!("Logging to {}", log_path.display()));
+        }
+
+        ui.heading("Diagnostics");
+        if ui.button("Run Self-Test").on_hover_text("Wrap and revert a throwaway executable to check that it works on this system").clicked() {
+            self.windows.self_test = Some(self_test::run(&self.wrapper_dir));
+        }
+    }
+
+    /// The steps of the last self-test and whether they passed
+    fn show_self_test_dialog(&mut self, ctx: &Context) {
+        let Some(checks) = &self.windows.self_test else { return; };
+        let mut open = true;
+        Window::new("Self-Test")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let passed = checks.iter().all(|check| return check.result.is_ok());
+                if passed {
+                    ui.colored_label(egui::Color32::GREEN, "Passed, wrapping and reverting work on this system");
+                } else {
+                    ui.colored_label(egui::Color32::RED, "Failed, please attach the log when reporting it");
+                }
+                for check in checks {
+                    match &check.result {
+                        Ok(()) => { ui.label(format!("✔ {}", check.name)); },
+                        Err(e) => { ui.colored_label(egui::Color32::RED, format!("❌ {}: {e}", check.name)); },
+                    }
+                }
+            });
+        if !open {
+            self.windows.self_test = None;
+        }
+    }
+
+    /// Show what the last Add/Remove of a folder did to each executable in it, so it's clear what changed
+    fn show_folder_results_dialog(&mut self, ctx: &Context) {
+        let Some(results) = &self.windows.folder_results else { return; };
+        let mut open = true;
+        Window::new("Folder Results")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{}: {}", results.folder, results.summary));
+                ScrollArea::vertical().id_source("folder_results").max_height(300.0).show(ui, |ui| {
+                    for (path, outcome) in &results.rows {
+                        match outcome {
+                            FolderOutcome::Changed => { ui.colored_label(egui::Color32::GREEN, format!("✔ {}", path.display())); },
+                            FolderOutcome::Skipped(reason) => { ui.colored_label(ui.visuals().weak_text_color(), format!("⏭ {}: {reason}", path.display())); },
+                            FolderOutcome::Failed(e) => { ui.colored_label(egui::Color32::RED, format!("❌ {}: {e}", path.display())); },
+                        }
+                    }
+                });
+            });
+        if !open {
+            self.windows.folder_results = None;
+        }
+    }
+
+    /// Show the latest log lines at the bottom, if turned on
+    fn show_log_panel(&mut self, ctx: &Context) {
+        if !self.windows.log { return; }
+        TopBottomPanel::bottom("log_panel").resizable(true).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong("Log");
+                if let Some(log_path) = logging::log_path() {
+                    ui.label(format!("(also in {})", log_path.display()));
+                }
+            });
+            ScrollArea::vertical().stick_to_bottom(true).auto_shrink([false, true]).show(ui, |ui| {
+                for (level, line) in logging::recent_lines() {
+                    let color = match level {
+                        Level::Error => egui::Color32::RED,
+                        Level::Warn => egui::Color32::YELLOW,
+                        _ => ui.visuals().text_color(),
+                    };
+                    ui.colored_label(color, line);
+                }
+            });
+        });
+    }
+
+    /// Offload a Flatpak app with the selected backend, or take the offload off it again
+    fn toggle_flatpak(&mut self, app_id: &str) {
+        if let Some(offload) = self.added.flatpaks.get(app_id).cloned() {
+            match flatpak::restore(app_id, &offload.changed) {
+                Ok(()) => {
+                    self.added.flatpaks.remove(app_id);
+                    set_flatpak(&self.config_path, app_id, None);
+                },
+                Err(e) => self.report = Some(format!("Failed to remove the offload from {app_id}: {e}")),
+            }
+            return;
+        }
+
+        if let Err(e) = self.offload_flatpak(app_id, self.offload_backend) {
+            self.report = Some(format!("Failed to offload {app_id}: {e}"));
+        }
+    }
+
+    /// Offload a Flatpak app with the given backend, recording what was changed so it can be undone
+    fn offload_flatpak(&mut self, app_id: &str, backend: OffloadBackend) -> Result<(), Box<dyn Error>> {
+        let changed = flatpak::offload(app_id, &backend.env(&WrapperEnv::default()))?;
+        let offload = FlatpakOffload { backend, changed };
+        set_flatpak(&self.config_path, app_id, Some(offload.clone()));
+        self.added.flatpaks.insert(app_id.to_string(), offload);
+        return Ok(())
+    }
+
+    /// Take the offload off every offloaded Flatpak app, for Revert All
+    fn restore_flatpaks(&mut self) {
+        if self.added.flatpaks.is_empty() { return; }
+        let mut failed = 0;
+        for (app_id, offload) in self.added.flatpaks.clone() {
+            match flatpak::restore(&app_id, &offload.changed) {
+                Ok(()) => {
+                    self.added.flatpaks.remove(&app_id);
+                    set_flatpak(&self.config_path, &app_id, None);
+                },
+                Err(e) => {
+                    error!("Failed to remove the offload from {app_id}: {e}");
+                    failed += 1;
+                },
+            }
+        }
+        if failed > 0 {
+            self.toasts.error(format!("Failed to take the offload off {failed} Flatpak apps, see the log"));
+        } else {
+            self.toasts.info("Took the offload off the Flatpak apps".to_string());
+        }
+    }
+
+    /// Show the installed Flatpak apps, which are offloaded through their overrides rather than wrapped
+    fn show_flatpak_list(&mut self, ui: &mut egui::Ui) {
+        if self.system.flatpak_apps.is_empty() { return; }
+        ui.collapsing(format!("Flatpak apps ({} offloaded)", self.added.flatpaks.len()), |ui| {
+            let mut toggled = None;
+            ScrollArea::vertical().id_source("flatpak_list").max_height(160.0).show(ui, |ui| {
+                for app in self.system.flatpak_apps.iter().filter(|app| return matches_filter(&app.name, &self.filter) || matches_filter(&app.id, &self.filter)) {
+                    let mut offloaded = self.added.flatpaks.contains_key(&app.id);
+                    if ui.checkbox(&mut offloaded, format!("{} ({})", app.name, app.id)).changed() {
+                        toggled = Some(app.id.clone());
+                    }
+                }
+            });
+            if let Some(app_id) = toggled {
+                self.toggle_flatpak(&app_id);
+            }
+        });
+    }
+
+    /// Show the list of added processes
+    fn show_added_list(&mut self, ctx: &Context) {
+        CentralPanel::default().show(ctx, |ui| {
+            self.show_flatpak_list(ui);
+            ui.heading("Added Processes That Use NVIDIA GPU");
+            self.show_added_toolbar(ui);
+            self.show_added_rows(ui);
+        });
+    }
+
+    /// What can be done to the whole list, or the selected entry in it
+    fn show_added_toolbar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.pending.is_none() && !self.added.checked.is_empty(), egui::Button::new("Revert Checked")).clicked() {
+                self.revert_checked();
+            }
+            if ui.add_enabled(self.pending.is_none() && (!self.added.entries.is_empty() || !self.added.flatpaks.is_empty()), egui::Button::new("Revert All")).on_hover_text(ui.ctx().format_shortcut(&REVERT_ALL_SHORTCUT)).clicked() {
+                self.prompts.revert_all = true;
+            }
+
+            // Carry the list over to another machine or a reinstall
+            let has_dialogs = self.system.file_dialog_backend.is_some();
+            if ui.add_enabled(has_dialogs && (!self.added.entries.is_empty() || !self.added.flatpaks.is_empty()), egui::Button::new("Export")).on_disabled_hover_text("Needs xdg-desktop-portal or zenity for the file dialog").clicked() {
+                if let Some(export_path) = FileDialog::new().add_filter("TOML", &["toml"]).set_file_name("nvidia_manager_export.toml").save_file() {
+                    self.report = Some(match export_config(&self.config_path, &export_path) {
+                        Ok(count) => format!("Exported {count} entries to {}", export_path.display()),
+                        Err(e) => format!("Failed to export to {}: {e}", export_path.display()),
+                    });
+                }
+            }
+            if ui.add_enabled(has_dialogs, egui::Button::new("Import")).on_disabled_hover_text("Needs xdg-desktop-portal or zenity for the file dialog").clicked() {
+                if let Some(import_path) = FileDialog::new().add_filter("TOML", &["toml"]).pick_file() {
+                    self.import_config(&import_path);
+                }
+            }
+
+            // For when an app moved, e.g. with a relocated Steam library
+            let selected_entry = self.selected_executable.clone().filter(|selected| return self.added.entries.contains(selected));
+            let launchable = selected_entry.clone().filter(|entry| return self.launch_path_of(entry).is_some());
+            if ui.add_enabled(selected_entry.is_some(), egui::Button::new("Edit Path")).clicked() {
+                self.windows.editing_entry = selected_entry.map(|entry| return (entry.clone(), entry));
+            }
+
+            // See if it really ends up on the NVIDIA GPU without having to go and launch it
+            if let Some((entry, _)) = &self.test_launch {
+                ui.spinner();
+                ui.label(format!("Test launching {entry}"));
+            } else if ui.add_enabled(launchable.is_some(), egui::Button::new("Test Launch")).on_hover_text("Launch the selected entry and check with nvidia-smi that it runs on the NVIDIA GPU").on_disabled_hover_text("Folders and .desktop entries can't be launched from here").clicked() {
+                if let Some(entry) = launchable {
+                    self.start_test_launch(&entry, ui.ctx());
+                }
+            }
+
+            // Added apps that are running but not on the NVIDIA GPU, e.g. launched through a path that bypassed the symlink
+            if ui.button("Check Running").on_hover_text("Compare the running added processes against nvidia-smi").clicked() {
+                match entries_off_nvidia(&self.added.entries) {
+                    Ok(off_nvidia) => {
+                        self.report = Some(if off_nvidia.is_empty() {
+                            "Every running added process is on the NVIDIA GPU".to_string()
+                        } else {
+                            format!("{} running added processes aren't on the NVIDIA GPU", off_nvidia.len())
+                        });
+                        self.added.off_nvidia = off_nvidia;
+                    },
+                    Err(e) => self.report = Some(format!("Couldn't check which processes are on the NVIDIA GPU: {e}")),
+                }
+            }
+        });
+    }
+
+    /// The added entries that match the filter, with a way to repair the broken ones
+    fn show_added_rows(&mut self, ui: &mut egui::Ui) {
+        let mut items: Vec<String> = self.added.entries.iter().filter(|item| return matches_filter(item, &self.filter)).cloned().collect();
+        items.sort();
+        self.refresh_wrapper_states();
+        let mut opened = None;
+        let mut find_broken = false;
+        ScrollArea::vertical().show(ui, |ui| {
+            let rows: Vec<Response> = items.iter().map(|item| return self.show_added_row(ui, item, &mut opened, &mut find_broken)).collect();
+
+            // Enter on an added process removes it, same as the button
+            if let Some((focused, enter)) = navigate_list(ui, &rows) {
+                self.selected_executable = Some(items[focused].clone());
+                if enter { self.confirm_add_or_remove(items[focused].clone()); }
+            }
+        });
+        if let Some(report) = opened {
+            self.report = Some(report);
         }
-        
-        // Read the config file, split on newlines, and remove empty lines
-        validate_config(&config_path);
-        let config = read_config(&config_path);
+        // Bind mounts and launchers that went missing are already cleaned up by this, the rest shows up in the Broken Entries window
+        if find_broken {
+            self.added.broken = validate_config(&self.config_path, &self.wrapper_dir, false);
+            if self.added.broken.is_empty() {
+                self.report = Some("Couldn't tell what broke, check the log or remove and add it again".to_string());
+            }
+        }
+    }
 
-        // Fetch the initial list of processes with executables
-        let executables = get_executable_paths().unwrap_or_default();
+    /// One added entry with its state and context menu, noting what went wrong in `opened` and whether it asked to be repaired in `find_broken`
+    fn show_added_row(&mut self, ui: &mut egui::Ui, item: &str, opened: &mut Option<String>, find_broken: &mut bool) -> Response {
+        return ui.horizontal(|ui| {
+            let mut checked = self.added.checked.contains(item);
+            if ui.checkbox(&mut checked, "").changed() {
+                if checked {
+                    self.added.checked.insert(item.to_string());
+                } else {
+                    self.added.checked.remove(item);
+                }
+            }
+            let state = self.added.states.get(item).copied().unwrap_or(WrapperState::Broken);
+            let color = match state {
+                WrapperState::Enabled => egui::Color32::GREEN,
+                WrapperState::Disabled => ui.visuals().weak_text_color(),
+                WrapperState::Broken => egui::Color32::RED,
+            };
+            ui.colored_label(color, "⏺").on_hover_text(state.label());
+            let response = ui.selectable_value(&mut self.selected_executable, Some(item.to_string()), highlight_filter(item, &self.filter, ui.style()));
+            // For looking at it on disk
+            response.context_menu(|ui| {
+                if ui.button("Copy Path").clicked() {
+                    ui.output_mut(|output| output.copied_text = item.to_string());
+                    ui.close_menu();
+                }
+                if ui.button("Open Containing Folder").clicked() {
+                    let folder = Path::new(item).parent().unwrap_or(Path::new("/"));
+                    if let Err(e) = open_folder(folder) {
+                        *opened = Some(format!("Failed to open {}: {e}", folder.display()));
+                    }
+                    ui.close_menu();
+                }
+                let script = wrapper_script_of(&self.wrapper_dir, &self.added.launchers, item);
+                if ui.add_enabled(script.is_some(), egui::Button::new("Reveal Wrapper Script")).on_disabled_hover_text("Folders and desktop entries don't have one of their own").clicked() {
+                    if let Some(script) = script {
+                        if let Err(e) = reveal_in_file_manager(&script) {
+                            *opened = Some(format!("Failed to show {}: {e}", script.display()));
+                        }
+                    }
+                    ui.close_menu();
+                }
+            });
+            if self.added.off_nvidia.contains(item) {
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ Not on NVIDIA")
+                    .on_hover_text("Running right now but not on the NVIDIA GPU, it was likely launched through a path that bypasses the wrapper");
+            }
+            if state == WrapperState::Broken && ui.small_button("Repair").on_hover_text("Look at what's left of it and offer a way out").clicked() {
+                *find_broken = true;
+            }
+            return response
+        }).inner
+    }
 
-        return MyApp {
-            executables,
-            selected_executable: None,
-            modified_executables: config,
-            wrapper_dir,
-            config_path,
-            show_picker_dialog: false
+    /// Read the wrapper environment of an executable from the config, unless it's the one already loaded. Returns its entry
+    fn load_wrapper_env(&mut self, selected: &str) -> String {
+        let entry = config_entry(selected).unwrap_or_else(|_| return selected.to_string());
+        if !self.wrapper_env.as_ref().is_some_and(|(env_entry, _)| return *env_entry == entry) {
+            self.wrapper_env = Some((entry.clone(), Config::read(&self.config_path).env_of(&entry)));
         }
+        return entry
     }
-}
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
-        // The top panel
-        TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            egui::ComboBox::from_label("Processes (WARNING: Be careful with what processes you choose!)")
-                .selected_text(self.selected_executable.as_deref().unwrap_or("Select a process"))
+    /// Checkboxes for the variables the NVIDIA backend can set on top for an executable, and which NVIDIA GPU it goes to if there's more than one, saved to the config as they're changed
+    fn show_nvidia_extras(&mut self, ui: &mut egui::Ui, selected: &str) {
+        let entry = self.load_wrapper_env(selected);
+        let Some((_, env)) = &mut self.wrapper_env else { return; };
+
+        let mut changed = false;
+        for extra in NvidiaExtra::ALL {
+            let mut enabled = env.extras.contains(&extra);
+            if ui.add_enabled(extra.is_available(), egui::Checkbox::new(&mut enabled, extra.label())).on_disabled_hover_text("The NVIDIA driver files it points at aren't installed").changed() {
+                if enabled {
+                    env.extras.push(extra);
+                } else {
+                    env.extras.retain(|existing| return *existing != extra);
+                }
+                changed = true;
+            }
+        }
+
+        // Only worth picking with more than one, the offload provider goes by their order
+        let gpus = nvidia_gpus();
+        if gpus.len() > 1 {
+            let label_of = |bus_id: &Option<String>| return bus_id.as_ref().map_or_else(
+                || return "Whichever the driver picks".to_string(),
+                |bus_id| return gpus.iter().find(|gpu| return gpu.bus_id == *bus_id).map_or_else(|| return format!("{bus_id} (not found)"), |gpu| return format!("{} ({bus_id})", gpu.name)),
+            );
+            egui::ComboBox::from_label("NVIDIA GPU")
+                .selected_text(label_of(&env.nvidia_gpu))
                 .show_ui(ui, |ui| {
-                    for process in &self.executables {
-                        ui.selectable_value(&mut self.selected_executable, Some(process.clone()), process);
+                    for bus_id in std::iter::once(None).chain(gpus.iter().map(|gpu| return Some(gpu.bus_id.clone()))) {
+                        let label = label_of(&bus_id);
+                        changed |= ui.selectable_value(&mut env.nvidia_gpu, bus_id, label).changed();
                     }
                 });
+        }
 
-            ui.horizontal(|ui| {
-                if ui.button("Add/Remove").clicked() {
-                    if let Some(selected) = &self.selected_executable { // If an item is selected, and the button is pressed
-                        match execute(&self.wrapper_dir, &PathBuf::from(selected)) { // Execute the main logic
-                            Ok(reverted) => {
-                                if reverted { // If we reverted our changes then remove it from the list and config file, otherwise add it
-                                    self.modified_executables.remove(selected);
-                                    remove_config(selected, &self.config_path);
-                                    return;
+        if changed {
+            write_wrapper_env(&self.config_path, &entry, env);
+        }
+    }
+
+    /// The command an executable gets launched through, typed in or picked from the common ones that are installed, saved to the config as it's edited
+    fn show_command_prefix(&mut self, ui: &mut egui::Ui, selected: &str) {
+        let entry = self.load_wrapper_env(selected);
+        let Some((_, env)) = &mut self.wrapper_env else { return; };
+
+        let mut prefix = env.command_prefix.clone().unwrap_or_default();
+        let mut changed = ui.text_edit_singleline(&mut prefix).on_hover_text("Split into words on spaces, each word is quoted in the wrapper script").changed();
+        ui.horizontal(|ui| {
+            for command in &self.system.installed_prefixes {
+                if ui.add_enabled(!env.prefix_words().contains(command), egui::Button::new(*command).small()).clicked() {
+                    prefix = format!("{prefix} {command}").trim_start().to_string();
+                    changed = true;
+                }
+            }
+        });
+
+        // Only a warning, it may get installed before the app is launched
+        if let Some(command) = prefix.split_whitespace().next().filter(|command| return !on_path(command) && !Path::new(command).is_file()) {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("{command} isn't installed (not on PATH), the app won't launch without it"));
+        }
+
+        if changed {
+            env.command_prefix = Some(prefix.trim().to_string()).filter(|prefix| return !prefix.is_empty());
+            write_wrapper_env(&self.config_path, &entry, env);
+        }
+    }
+
+    /// Editable table of the wrapper environment of an executable, saved to the config as it's edited
+    fn show_wrapper_env_table(&mut self, ui: &mut egui::Ui, selected: &str) {
+        let entry = self.load_wrapper_env(selected);
+        let Some((_, env)) = &mut self.wrapper_env else { return; };
+
+        let mut changed = false;
+        let mut removed = None;
+        egui::Grid::new("wrapper_env").striped(true).show(ui, |ui| {
+            for (index, (key, var)) in env.vars.iter_mut().enumerate() {
+                changed |= ui.text_edit_singleline(key).changed();
+                changed |= ui.text_edit_singleline(var).changed();
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(index) = removed {
+            env.vars.remove(index);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Variable").clicked() {
+                env.vars.push((String::new(), String::new()));
+            }
+            if ui.button("Reset to Defaults").clicked() {
+                env.vars = WrapperEnv::default().vars;
+                changed = true;
+            }
+        });
+
+        // Half typed rows aren't saved, an empty name would end up in the wrapper script otherwise
+        if let Some(key) = env.invalid_key() {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("\"{key}\" isn't a valid variable name, it won't be saved"));
+        } else if changed {
+            write_wrapper_env(&self.config_path, &entry, env);
+        }
+    }
+
+    /// Check what's on disk for every entry again, but only once the list or the master switch changed since walking folders isn't free
+    fn refresh_wrapper_states(&mut self) {
+        let mut entries: Vec<String> = self.added.entries.iter().cloned().collect();
+        entries.sort();
+        if self.added.states_of == (entries.clone(), self.added.offload_enabled) { return; }
+
+        self.added.states = entries.iter()
+            .map(|entry| {
+                // Bind mounts leave the executable alone, they're only there while mounted
+                let state = if self.added.bind_mounted.contains(entry) {
+                    if is_bind_mounted(Path::new(entry)) { WrapperState::Enabled } else { WrapperState::Broken }
+                } else if self.added.launchers.contains(entry) {
+                    let has_launcher = launcher_path(Path::new(entry)).is_some_and(|launcher| return is_launcher_of(&launcher, Path::new(entry)));
+                    if has_launcher { WrapperState::Enabled } else { WrapperState::Broken }
+                } else {
+                    wrapper_state(Path::new(entry))
+                };
+                return (entry.clone(), state)
+            })
+            .collect();
+        self.added.states_of = (entries, self.added.offload_enabled);
+    }
+
+    /// Entries that got broken outside of Nvidia Manager, the ones that can't launch anymore first
+    fn show_broken_entries_dialog(&mut self, ctx: &Context) {
+        if self.added.broken.is_empty() { return; }
+        Window::new("Broken Entries")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let mut resolved = Vec::new();
+                let entries_in = |wanted: BrokenEntry| -> Vec<String> {
+                    return self.added.broken.iter().filter(|(_, state)| return *state == wanted).map(|(entry, _)| return entry.clone()).collect()
+                };
+                let (missing_wrapper, orphaned, reverted) = (entries_in(BrokenEntry::MissingWrapper), entries_in(BrokenEntry::OrphanedSymlink), entries_in(BrokenEntry::Reverted));
+
+                if !missing_wrapper.is_empty() {
+                    ui.colored_label(egui::Color32::RED, "The wrapper scripts of these are gone (was the wrapper folder deleted?), so they can't launch. Their originals are still safe in the backups.");
+                    ui.label("Repair puts the original back and wraps it again the way it was, Restore Original puts it back and removes it from the list.");
+                    for entry in missing_wrapper {
+                        ui.horizontal(|ui| {
+                            ui.label(&entry);
+                            if ui.button("Repair").clicked() {
+                                match restore_missing_wrapper(&self.wrapper_dir, Path::new(&entry)).map_err(Into::into).and_then(|()| return self.repair_entry(&entry)) {
+                                    Ok(()) => resolved.push(entry.clone()),
+                                    Err(e) => error!("Failed to repair {entry}: {e}"),
                                 }
-                                self.modified_executables.insert(selected.to_string());
-                                add_config(selected, &self.config_path);
-                            },
-                            Err(e) => { // If there's an error, backtrace and print it
-                                let backtrace = Backtrace::new();
-                                eprintln!("Failed to execute the wrapper script for {selected}: {e}\nBacktrace:\n{backtrace:?}");
-                                return;
                             }
-                        }
+                            if ui.button("Restore Original").clicked() {
+                                match restore_missing_wrapper(&self.wrapper_dir, Path::new(&entry)) {
+                                    Ok(()) => {
+                                        self.added.entries.remove(&entry);
+                                        remove_config(&entry, &self.config_path);
+                                        resolved.push(entry.clone());
+                                    },
+                                    Err(e) => error!("Failed to restore {entry}: {e}"),
+                                }
+                            }
+                        });
                     }
                 }
 
-                if ui.button("File Picker").clicked() {
-                    self.show_picker_dialog = true;
+                if !orphaned.is_empty() {
+                    ui.colored_label(egui::Color32::RED, "These are still symlinks to their wrapper scripts but their backups are gone, so they can't launch anymore and the originals can't be recovered.");
+                    ui.label("Clean Up removes the dangling symlinks and their wrapper scripts, then reinstall (or verify the files of) the apps to get them back.");
+                    for entry in orphaned {
+                        ui.horizontal(|ui| {
+                            ui.label(&entry);
+                            if ui.button("Clean Up").clicked() {
+                                match remove_orphaned_symlink(&self.wrapper_dir, Path::new(&entry)) {
+                                    Ok(()) => {
+                                        self.added.entries.remove(&entry);
+                                        remove_config(&entry, &self.config_path);
+                                        resolved.push(entry.clone());
+                                    },
+                                    Err(e) => error!("Failed to clean up {entry}: {e}"),
+                                }
+                            }
+                        });
+                    }
                 }
-            });
-        });
 
-        // Show the list of added processes
-        CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Added Processes That Use NVIDIA GPU");
-            ScrollArea::vertical().show(ui, |ui| {
-                for item in &self.modified_executables {
-                    ui.selectable_value(&mut self.selected_executable, Some(item.clone()), item);
+                if !reverted.is_empty() {
+                    ui.label("The backups of these executables are missing, they may have been reverted or updated outside of Nvidia Manager.");
+                    for entry in reverted {
+                        ui.horizontal(|ui| {
+                            ui.label(&entry);
+                            if ui.button("Repair").clicked() {
+                                match self.repair_entry(&entry) {
+                                    Ok(()) => resolved.push(entry.clone()),
+                                    Err(e) => error!("Failed to repair {entry}: {e}"),
+                                }
+                            }
+                            if ui.button("Remove").clicked() {
+                                self.added.entries.remove(&entry);
+                                remove_config(&entry, &self.config_path);
+                                resolved.push(entry.clone());
+                            }
+                        });
+                    }
+                }
+                if !resolved.is_empty() {
+                    self.added.states_of.0.clear(); // Read the states again, the entry may still be there but isn't broken anymore
                 }
+                self.added.broken.retain(|(entry, _)| return !resolved.contains(entry));
             });
-        });
-        
-        
-        // File Picker
-        if self.show_picker_dialog {
-            Window::new("Pick File or Folder")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.label("Pick a File or Folder");
+    }
+
+    /// Wrapped executables whose symlink got replaced by a copy of the wrapper script, the original is still safe in the backup
+    fn show_replaced_symlinks_dialog(&mut self, ctx: &Context) {
+        if self.added.replaced_symlinks.is_empty() { return; }
+        Window::new("Replaced Symlinks")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("These executables were replaced by a copy of their wrapper script, likely by a backup or sync tool. The originals are still in their backups.");
+                let mut resolved = Vec::new();
+                for path in self.added.replaced_symlinks.clone() {
                     ui.horizontal(|ui| {
-                        if ui.button("Pick File").clicked() {
-                            if let Some(picked_path) = FileDialog::new().pick_file() {
-                                let _ = self.selected_executable.insert(picked_path.display().to_string());
+                        ui.label(path.display().to_string());
+                        if ui.button("Relink").clicked() {
+                            match relink_wrapper(&self.wrapper_dir, &path) {
+                                Ok(()) => resolved.push(path.clone()),
+                                Err(e) => error!("Failed to relink {}: {e}", path.display()),
                             }
-                            self.show_picker_dialog = false;
                         }
-                        if ui.button("Pick Folder").clicked() {
-                            if let Some(picked_path) = FileDialog::new().pick_folder() {
-                                let _ = self.selected_executable.insert(picked_path.display().to_string());
+                        if ui.button("Restore Original").clicked() {
+                            match self.restore_replaced_symlink(&path) {
+                                Ok(()) => resolved.push(path.clone()),
+                                Err(e) => error!("Failed to restore {}: {e}", path.display()),
                             }
-                            self.show_picker_dialog = false;
                         }
                     });
+                }
+                self.added.replaced_symlinks.retain(|path| return !resolved.contains(path));
+            });
+    }
+
+    /// Warning shown before wrapping something that looks protected by anti-cheat
+    fn show_anti_cheat_dialog(&mut self, ctx: &Context) {
+        let Some((selected, pattern)) = self.prompts.anti_cheat.clone() else { return; };
+        Window::new("Anti-Cheat Warning")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(egui::Color32::RED, format!("{selected} looks protected by anti-cheat (matched \"{pattern}\")."));
+                ui.label("Anti-cheat may flag the renamed binary and symlink as tampering and ban your account.");
+                ui.label("Setting __NV_PRIME_RENDER_OFFLOAD=1 __GLX_VENDOR_LIBRARY_NAME=nvidia in the game's launch options or its .desktop file is the safer way.");
+                ui.horizontal(|ui| {
+                    if ui.button("Wrap Anyway").clicked() {
+                        self.prompts.anti_cheat = None;
+                        self.confirm_shared_path(selected.clone());
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.prompts.anti_cheat = None;
+                    }
+                });
+            });
+    }
+
+    /// Warning shown before wrapping something outside the home folder or writable by other users
+    fn show_shared_path_dialog(&mut self, ctx: &Context) {
+        let Some((selected, reason)) = self.prompts.shared_path.clone() else { return; };
+        Window::new("Shared Executable")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(egui::Color32::YELLOW, format!("{reason}."));
+                ui.label("Wrapping it renames the original and puts a symlink in its place, which changes it for every user and program on this system.");
+                ui.label("Package updates may also overwrite the symlink or trip over the backup next to it.");
+                ui.horizontal(|ui| {
+                    if ui.button("Wrap Anyway").clicked() {
+                        self.prompts.shared_path = None;
+                        self.add_or_remove(&selected);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.prompts.shared_path = None;
+                    }
+                });
+            });
+    }
+
+    /// Confirmation before reverting every added entry
+    fn show_revert_all_dialog(&mut self, ctx: &Context) {
+        if !self.prompts.revert_all { return; }
+        Window::new("Revert All")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Revert all {} added entries and remove them from the list?", self.added.entries.len()));
+                if !self.added.flatpaks.is_empty() {
+                    ui.label(format!("The offload is taken off the {} offloaded Flatpak apps too.", self.added.flatpaks.len()));
+                }
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.pending.is_none(), egui::Button::new("Revert All")).clicked() {
+                        self.prompts.revert_all = false;
+                        self.restore_flatpaks();
+                        if !self.added.entries.is_empty() {
+                            self.revert_entries(self.added.entries.clone());
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.prompts.revert_all = false;
+                    }
+                });
+            });
+    }
+
+    /// Reverting was refused since the backup changed since it was wrapped, let the user restore it anyway
+    fn show_backup_mismatch_dialog(&mut self, ctx: &Context) {
+        let Some(entry) = self.prompts.backup_mismatch.clone() else { return; };
+        Window::new("Backup Changed")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(egui::Color32::RED, format!("The backup of {entry} isn't the file that was wrapped anymore."));
+                ui.label("It may have been replaced by an update, or got corrupted. Restoring it puts whatever it is now back in place of the executable.");
+                ui.horizontal(|ui| {
+                    if ui.button("Restore Anyway").clicked() {
+                        self.prompts.backup_mismatch = None;
+                        self.revert_mismatched(&entry);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.prompts.backup_mismatch = None;
+                    }
+                });
+            });
+    }
+
+    /// First run offer to import executables wrapped by a manual setup, confirming each before adding it
+    fn show_import_dialog(&mut self, ctx: &Context) {
+        if !self.prompts.import { return; }
+        Window::new("Import Existing Wrappers")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if self.prompts.import_candidates.is_empty() {
+                    ui.label("Executables wrapped before (by hand or an older setup) can be imported. Scan ~/.local/bin and your Steam libraries for them?");
+                    ui.horizontal(|ui| {
+                        if ui.button("Scan").clicked() {
+                            self.scan_for_wrapped();
+                        }
+                        if ui.button("Skip").clicked() {
+                            self.prompts.import = false;
+                        }
+                    });
+                    return;
+                }
+
+                ui.label("These executables are already wrapped but not added, check the ones to import.");
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (path, checked) in &mut self.prompts.import_candidates {
+                        ui.checkbox(checked, path.as_str());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Import Checked").clicked() {
+                        self.import_checked();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.prompts.import_candidates.clear();
+                        self.prompts.import = false;
+                    }
+                });
+            });
+    }
+
+    /// Editing the path of an added entry
+    fn show_edit_path_dialog(&mut self, ctx: &Context) {
+        let Some((old_entry, mut new_path)) = self.windows.editing_entry.clone() else { return; };
+        Window::new("Edit Path")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("New location of {old_entry}"));
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut new_path);
+                    if self.system.file_dialog_backend.is_none() { return; }
+                    if ui.button("Pick File").clicked() {
+                        if let Some(picked_path) = FileDialog::new().pick_file() {
+                            new_path = picked_path.display().to_string();
+                        }
+                    }
+                    if ui.button("Pick Folder").clicked() {
+                        if let Some(picked_path) = FileDialog::new().pick_folder() {
+                            new_path = picked_path.display().to_string();
+                        }
+                    }
+                });
+                self.windows.editing_entry = Some((old_entry.clone(), new_path.clone()));
+
+                ui.horizontal(|ui| {
+                    if ui.button("Apply").clicked() {
+                        match self.edit_entry_path(&old_entry, &new_path) {
+                            Ok(new_entry) => {
+                                self.report = Some(format!("Moved {old_entry} to {new_entry}"));
+                                self.selected_executable = Some(new_entry);
+                                self.windows.editing_entry = None;
+                            },
+                            Err(e) => self.report = Some(format!("Failed to move {old_entry} to {new_path}: {e}")),
+                        }
+                    }
                     if ui.button("Cancel").clicked() {
-                        self.show_picker_dialog = false;
+                        self.windows.editing_entry = None;
+                    }
+                });
+            });
+    }
+
+    /// Remember where the window is and what's selected for the next launch
+    fn save_window_state(&mut self, ctx: &Context) {
+        self.window_state.capture(ctx);
+        self.window_state.selected.clone_from(&self.selected_executable);
+        if let Some(path) = WindowState::path() {
+            self.window_state.save(&path);
+        }
+    }
+
+    /// Select a picked file or folder, unless it's on the system path blocklist
+    fn select_picked(&mut self, picked_path: &Path) {
+        // A symlink into a system path would have its real file wrapped, so that's checked too.
+        // System desktop entries are fine, they're overridden by a copy rather than modified
+        let real_path = fs::canonicalize(picked_path).unwrap_or_else(|_| return picked_path.to_path_buf());
+        let blocked_by = system_path_match(picked_path, &self.settings.scan.system_paths).or_else(|| return system_path_match(&real_path, &self.settings.scan.system_paths));
+        if let (Some(prefix), false) = (blocked_by, is_desktop_entry(picked_path)) {
+            self.report = Some(format!("{} is in {prefix}, which is on the system path blocklist", picked_path.display()));
+            return;
+        }
+        let _ = self.selected_executable.insert(picked_path.display().to_string());
+    }
+
+    /// File Picker
+    fn show_file_picker(&mut self, ctx: &Context) {
+        if !self.windows.picker { return; }
+        Window::new("Pick File or Folder")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                // Nothing can show a file dialog, so the path has to be typed in instead
+                if self.system.file_dialog_backend.is_none() {
+                    ui.label("No file dialog is available, enter the path of a File or Folder");
+                    let response = ui.text_edit_singleline(&mut self.windows.manual_path);
+                    let submitted = response.lost_focus() && ui.input(|input| return input.key_pressed(Key::Enter));
+                    ui.horizontal(|ui| {
+                        if ui.button("Select").clicked() || submitted {
+                            let path = self.windows.manual_path.trim().to_string();
+                            if Path::new(&path).exists() {
+                                self.select_picked(Path::new(&path));
+                                self.windows.manual_path.clear();
+                                self.windows.picker = false;
+                            } else {
+                                self.report = Some(format!("{path} doesn't exist"));
+                            }
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.windows.manual_path.clear();
+                            self.windows.picker = false;
+                        }
+                    });
+                    return;
+                }
+
+                ui.label("Pick a File or Folder");
+                ui.horizontal(|ui| {
+                    if ui.button("Pick File").clicked() {
+                        if let Some(picked_path) = FileDialog::new().pick_file() {
+                            self.select_picked(&picked_path);
+                        }
+                        self.windows.picker = false;
+                    }
+                    if ui.button("Pick Folder").clicked() {
+                        if let Some(picked_path) = FileDialog::new().pick_folder() {
+                            self.select_picked(&picked_path);
+                        }
+                        self.windows.picker = false;
                     }
                 });
+                if ui.button("Cancel").clicked() {
+                    self.windows.picker = false;
+                }
+            });
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        // Closing hides the window while there's a tray icon to bring it back, otherwise it quits like normal
+        if self.settings.close_to_tray && self.tray.is_none() {
+            self.tray = Some(Tray::spawn(ctx));
+        }
+        // Global hotkeys are intrusive, so they're only registered when asked for
+        if self.settings.global_hotkey && self.hotkey.is_none() {
+            match Hotkey::register(ctx, &self.settings.hotkey) {
+                Ok(hotkey) => self.hotkey = Some(hotkey),
+                Err(e) => {
+                    self.report = Some(format!("Failed to register the {} hotkey: {e}", self.settings.hotkey));
+                    self.settings.global_hotkey = false;
+                }
+            }
+        }
+        if self.hotkey.as_ref().is_some_and(Hotkey::pressed) {
+            self.add_focused_app();
+        }
+        self.handle_shortcuts(ctx);
+
+        // Pick up changes made to the config outside the GUI, and by a finished Add/Remove
+        self.reload_config_if_changed(ctx);
+        self.poll_pending(ctx);
+        self.poll_test_launch();
+        self.poll_hooks(ctx);
+        self.auto_refresh_processes(ctx);
+
+        let hides_on_close = self.settings.close_to_tray && self.tray.as_ref().is_some_and(Tray::hides_on_close);
+        let close_requested = ctx.input(|input| return input.viewport().close_requested());
+        if close_requested {
+            self.save_window_state(ctx);
+        }
+        if close_requested && hides_on_close {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
         }
+
+        self.show_top_panel(ctx);
+        self.show_log_panel(ctx);
+        self.show_settings_window(ctx);
+        self.show_self_test_dialog(ctx);
+        self.show_folder_results_dialog(ctx);
+        self.show_added_list(ctx);
+        self.show_broken_entries_dialog(ctx);
+        self.show_replaced_symlinks_dialog(ctx);
+        self.show_anti_cheat_dialog(ctx);
+        self.show_shared_path_dialog(ctx);
+        self.show_backup_mismatch_dialog(ctx);
+        self.show_revert_all_dialog(ctx);
+        self.show_import_dialog(ctx);
+        self.show_edit_path_dialog(ctx);
+        self.show_file_picker(ctx);
+        self.toasts.show(ctx);
+    }
+}
+
+/// Check if a path contains the filter, ignoring case
+fn matches_filter(path: &str, filter: &str) -> bool {
+    return path.to_lowercase().contains(&filter.to_lowercase())
+}
+
+/// Lay out a path with every match of the filter highlighted
+fn highlight_filter(path: &str, filter: &str, style: &egui::Style) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Button.resolve(style);
+    let normal = egui::TextFormat { font_id: font_id.clone(), color: style.visuals.text_color(), ..Default::default() };
+    let highlighted = egui::TextFormat { font_id, color: style.visuals.strong_text_color(), background: style.visuals.selection.bg_fill, ..Default::default() };
+
+    let mut job = egui::text::LayoutJob::default();
+    let lowercase = path.to_lowercase();
+    // Lowercasing some characters changes their length, the match positions wouldn't line up with the path then
+    if filter.is_empty() || lowercase.len() != path.len() {
+        job.append(path, 0.0, normal);
+        return job
+    }
+
+    let filter = filter.to_lowercase();
+    let mut start = 0;
+    for (index, _) in lowercase.match_indices(&filter) {
+        job.append(&path[start..index], 0.0, normal.clone());
+        job.append(&path[index..index + filter.len()], 0.0, highlighted.clone());
+        start = index + filter.len();
+    }
+    job.append(&path[start..], 0.0, normal);
+    return job
+}
+
+/// The wrapper script an entry launches through, `None` for folders and desktop entries which don't have one of their own
+fn wrapper_script_of(wrapper_dir: &Path, launchers: &HashSet<String>, entry: &str) -> Option<PathBuf> {
+    let path = Path::new(entry);
+    if launchers.contains(entry) {
+        return launcher_path(path)
+    }
+    if path.is_dir() || is_desktop_entry(path) { return None }
+    if is_elevated(path) {
+        return fs::read_link(path).ok()
+    }
+    return Some(wrapper_dir.join(generate_wrapper_name(path))).filter(|script| return script.exists())
+}
+
+/// Run a `post_wrap` or `post_unwrap` hook on an entry, returning a warning if it failed. Hooks can take a while, so this only ever runs off the UI thread
+fn hook_warning(name: &str, hook: &str, entry: &str) -> Option<String> {
+    return run_hook(hook, Path::new(entry)).err().map(|e| {
+        warn!("The {name} hook failed for {entry}: {e}");
+        return format!("Warning: the {name} hook failed for {entry}: {e}")
+    })
+}
+
+/// Explain a wrapping error to the user, along with what they can do about it
+fn wrap_error_report(error: &WrapError) -> String {
+    let advice = match error {
+        WrapError::PathMissing(_) => "It may have been moved or uninstalled, use Edit Path if it moved",
+        WrapError::NotWritable(_) => "Nvidia Manager needs write access to the folder of the executable, try running it as the owner of the files or turn on wrapping as root in the settings",
+        WrapError::UnwrappableSymlink(..) => "The file it points to is a system file or not writable, replacing the symlink itself would break whatever manages it",
+        WrapError::AlreadyWrapped(_) | WrapError::AlreadyExists(_) => "Nothing was changed",
+        WrapError::OwnFile(..) => "Nvidia Manager would break itself, nothing was changed",
+        WrapError::BackupMissing(_) => "The original executable is gone, it may have been updated or reverted outside of Nvidia Manager",
+        WrapError::BackupMismatch(_) => "Nothing was restored, it can still be restored anyway if that's really what you want",
+        WrapError::ScriptSyntax(_) => "The environment set for it broke the wrapper script, check the variables",
+        WrapError::ScriptWontRun(..) => "Nothing was changed. Pick a wrapper shell that is installed in the settings, and make sure the wrapper folder isn't on a noexec filesystem",
+        WrapError::SymlinkFailed(..) | WrapError::UnexpectedState(_) => "The filesystem may not support symlinks properly",
+        WrapError::SymlinksUnsupported(..) => "Nothing was changed. Some FUSE and network mounts can't hold symlinks, try the Bind mount or Separate launcher strategy instead",
+        WrapError::MountPoint(_) => "Nothing was changed. The file is mounted on its own (bind mounted or in a container), wrap it where it's mounted from instead",
+        WrapError::Privileged(_) => "Bind mounting and wrapping as root need root, make sure pkexec (polkit) is installed and the password was entered",
+        WrapError::Record(_) | WrapError::Io(_) => "Check the log for details",
+    };
+    return format!("{} {error}. {advice}", error.icon())
+}
+
+/// Move the keyboard focus between the rows of a list with the arrow keys, stopping at the ends.
+/// Returns the focused row and whether Enter was pressed on it, or `None` if the list doesn't have focus (so text fields keep their keys)
+fn navigate_list(ui: &egui::Ui, rows: &[Response]) -> Option<(usize, bool)> {
+    let focused = rows.iter().position(Response::has_focus)?;
+
+    // The list handles up/down itself instead of egui moving the focus to whatever widget is above/below
+    ui.memory_mut(|memory| memory.set_focus_lock_filter(rows[focused].id, EventFilter { tab: false, horizontal_arrows: false, vertical_arrows: true, escape: false }));
+    let (up, down, enter) = ui.input(|input| return (input.key_pressed(Key::ArrowUp), input.key_pressed(Key::ArrowDown), input.key_pressed(Key::Enter)));
+
+    let target = if up {
+        focused.saturating_sub(1)
+    } else if down {
+        (focused + 1).min(rows.len() - 1)
+    } else {
+        focused
+    };
+    if target != focused {
+        rows[target].request_focus();
+        rows[target].scroll_to_me(None);
     }
+    return Some((target, enter))
 }
 
-fn read_config(config_path: &PathBuf) -> HashSet<String> {
-    return fs::read_to_string(config_path)
-        .unwrap_or_default()
+/// How long the config has to stay unchanged before it's reloaded
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Turn the old plain text config (v0, one path per line) into the TOML one, keeping the old file around as `config.txt.v0`
+fn migrate_config(old_config_path: &Path, config_path: &Path) -> Result<(), Box<dyn Error>> {
+    if config_path.exists() || !old_config_path.exists() { return Ok(()) }
+
+    let paths: HashSet<String> = fs::read_to_string(old_config_path)?
         .lines()
+        .map(str::trim) // Hand-edited configs may have stray whitespace around the paths
         .filter(|line| return !line.is_empty())
         .map(ToString::to_string)
         .collect();
+    info!("Migrating {} entries from {} to {}", paths.len(), old_config_path.display(), config_path.display());
+    let mut config = Config::default();
+    for path in &paths {
+        config.add(path);
+    }
+    config.save(config_path)?;
+    fs::rename(old_config_path, old_config_path.with_extension("txt.v0"))?;
+    return Ok(())
+}
+
+/// Move what the indexes next to the config recorded about the wrapped executables into the config, that's on their entries now.
+/// The backups index matters most, without it the backups kept in the central directory can't be found
+fn migrate_indexes(config_dir: &Path, config_path: &Path) -> Result<(), Box<dyn Error>> {
+    type Migrate = fn(&mut Wrapped, toml::Value);
+    let indexes: [(&str, Migrate); 3] = [
+        ("backups.toml", |wrapped, location| wrapped.backup = location.as_str().map(PathBuf::from)),
+        ("hashes.toml", |wrapped, hash| wrapped.sha256 = hash.as_str().map(ToString::to_string)),
+        ("permissions.toml", |wrapped, permissions| wrapped.permissions = permissions.try_into().ok()),
+    ];
+    for (name, migrate) in indexes {
+        let index_path = config_dir.join(name);
+        if !index_path.exists() { continue }
+
+        let index = fs::read_to_string(&index_path)?.parse::<toml::Table>()?;
+        info!("Migrating {} records from {} to {}", index.len(), index_path.display(), config_path.display());
+        Config::update(config_path, |config| {
+            for (original, recorded) in index {
+                config.record_wrapped(&original, |wrapped| migrate(wrapped, recorded));
+            }
+        })?;
+        fs::remove_file(&index_path)?;
+    }
+    return Ok(())
+}
+
+/// Every wrapped executable of the config, with the backend and environment of the entry it belongs to
+fn wrapped_with_settings(config: &Config) -> Vec<(PathBuf, OffloadBackend, WrapperEnv)> {
+    return config.entries.iter()
+        .flat_map(|entry| {
+            return wrapped_executables(&PathBuf::from(&entry.path)).into_iter().map(|path| return (path, entry.backend, entry.env.clone()))
+        })
+        .collect()
+}
+
+/// Write every entry with its metadata, and the offloaded Flatpak apps with their backend, to a file that can be imported on another machine
+fn export_config(config_path: &Path, export_path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut exported = Config::load(config_path)?.exported();
+    exported.save(export_path)?;
+    return Ok(exported.entries.len() + exported.flatpaks.len())
+}
+
+/// Read a file written by `export_config`, of this version or an older one
+fn read_exported_config(export_path: &Path) -> Result<Config, Box<dyn Error>> {
+    let exported = Config::load(export_path)?;
+    if exported.entries.is_empty() && exported.flatpaks.is_empty() {
+        return Err(format!("{} has no entries, is it an exported Nvidia Manager config?", export_path.display()).into());
+    }
+    return Ok(exported)
 }
 
-fn add_config(text: &str, config_path: &PathBuf) {
-    let mut config = read_config(config_path);
+/// Turn a path into the absolute one stored in the config, so it doesn't depend on the current directory.
+/// It's normalized the same way `execute` does it, so the config and the wrapper names agree on the path
+fn config_entry(text: &str) -> Result<String, Box<dyn Error>> {
+    let absolute = normalize_path(Path::new(text.trim()))?;
 
-    if !config.insert(text.to_string()) {
-        eprintln!("{text} is already in the config file");
+    if fs::symlink_metadata(&absolute).is_err() {
+        return Err(format!("{} does not exist", absolute.display()).into());
     }
+    return absolute.to_str().map(ToString::to_string).ok_or_else(|| return format!("{} is not valid UTF-8", absolute.display()).into())
+}
+
+/// Add a path to the config as an absolute path with what `record` sets on its entry, returning the stored path
+fn add_config(text: &str, config_path: &Path, record: impl FnOnce(&mut Entry)) -> Result<String, Box<dyn Error>> {
+    let path = config_entry(text)?;
+    Config::update(config_path, |config| record(config.add(&path)))?;
+    return Ok(path)
+}
+
+/// Remove a path from the config, returning the entry it had
+fn remove_config(text: &str, config_path: &Path) -> Option<Entry> {
+    return Config::update(config_path, |config| return config.remove(text)).unwrap_or_else(|e| {
+        error!("Failed to remove {text} from the config: {e}");
+        return None
+    })
+}
 
-    let modified_executable: Vec<String> = config.into_iter().collect();
-    fs::write(config_path, modified_executable.join("\n")).expect("Failed to write to config file");
+/// Record the wrapper environment of a path. It stays when the path is removed, so adding it again keeps the customizations
+fn write_wrapper_env(config_path: &Path, entry: &str, env: &WrapperEnv) {
+    if let Err(e) = Config::update(config_path, |config| config.customize(entry).env = env.clone()) {
+        error!("Failed to save the environment of {entry}: {e}");
+    }
 }
 
-fn remove_config(text: &str, config_path: &PathBuf) {
-    let mut config = read_config(config_path);
-    
-    if !config.remove(text) {
-        eprintln!("{text} is not in the config file");
+/// Record (or forget, with `None`) what was changed for an offloaded Flatpak app, so exactly that is undone again
+fn set_flatpak(config_path: &Path, app_id: &str, offload: Option<FlatpakOffload>) {
+    let result = Config::update(config_path, |config| {
+        match offload {
+            Some(offload) => { config.flatpaks.insert(app_id.to_string(), offload); },
+            None => { config.flatpaks.remove(app_id); },
+        }
+    });
+    if let Err(e) = result {
+        error!("Failed to save the offload of {app_id}: {e}");
     }
+}
 
-    let config: Vec<String> = config.into_iter().collect();
-    fs::write(config_path, config.join("\n")).expect("Failed to write to config file");
+/// How an entry got broken outside of Nvidia Manager, going by where its symlink points and whether its backup and wrapper script are still there
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BrokenEntry {
+    /// The backup is gone but the symlink is still there pointing at the wrapper script (or at the backup itself), so the executable can't launch anymore
+    OrphanedSymlink,
+    /// The backup is still there but the wrapper script the symlink points at is gone, so it can't launch until it's repaired
+    MissingWrapper,
+    /// Nothing of ours is left, it was reverted or updated outside of Nvidia Manager
+    Reverted,
 }
 
-fn validate_config(config_path: &PathBuf) {
-    let config = read_config(config_path);
-    for item in config {
-        let path = PathBuf::from(&item);
+/// Find the broken entries: the ones whose wrapper script is gone, and the ones whose backup is gone, telling apart the ones left as a dangling symlink from the ones that were reverted.
+/// With `auto_remove` the reverted ones are dropped from the config, the orphaned symlinks are always kept so they get cleaned up
+fn validate_config(config_path: &Path, wrapper_dir: &Path, auto_remove: bool) -> Vec<(String, BrokenEntry)> {
+    let mut missing = Vec::new();
+    let mut gone = Vec::new();
+    for entry in Config::read(config_path).entries {
+        // Bind mounts don't survive a reboot, which leaves the executable untouched and nothing to repair
+        if entry.bind_mount {
+            if !is_bind_mounted(Path::new(&entry.path)) {
+                info!("{} isn't bind mounted anymore (probably after a reboot), removing it", entry.path);
+                gone.push(entry.path);
+            }
+            continue;
+        }
+        // Same for launchers, the executable was never touched
+        if entry.launcher {
+            if !launcher_path(Path::new(&entry.path)).is_some_and(|launcher| return is_launcher_of(&launcher, Path::new(&entry.path))) {
+                info!("The launcher of {} is gone, removing it", entry.path);
+                gone.push(entry.path);
+            }
+            continue;
+        }
+
+        let path = PathBuf::from(&entry.path);
         if path.is_dir() { continue; }
-        if !path.with_extension("bak").exists() {
-            remove_config(&item, config_path);
+        if has_missing_wrapper(&path) {
+            warn!("The wrapper script of {} is gone, it can't launch until it's repaired", entry.path);
+            missing.push((entry.path, BrokenEntry::MissingWrapper));
+            continue;
+        }
+        // Desktop entries overridden by a copy don't have a backup, the copy is what counts
+        let wrapped = if is_desktop_entry(&path) { is_desktop_entry_offloaded(&path) } else { recorded_backup_path(&path, entry.wrapped.get(&entry.path)).exists() };
+        if wrapped { continue; }
+        if is_orphaned_symlink(wrapper_dir, &path) {
+            warn!("The backup of {} is gone but it's still a symlink to its wrapper, it can't launch anymore", entry.path);
+            missing.push((entry.path, BrokenEntry::OrphanedSymlink));
+        } else if auto_remove {
+            gone.push(entry.path.clone());
+            missing.push((entry.path, BrokenEntry::Reverted));
+        } else {
+            missing.push((entry.path, BrokenEntry::Reverted));
         }
     }
+    if !gone.is_empty() {
+        let removed = Config::update(config_path, |config| {
+            for path in &gone {
+                config.remove(path);
+            }
+        });
+        if let Err(e) = removed {
+            error!("Failed to remove {} from the config: {e}", gone.join(", "));
+        }
+    }
+    return missing
 }
 
-fn main() {
-    // TODO: Check if we need sudo perms or something
-    eframe::run_native(
+fn main() -> ExitCode {
+    logging::init();
+
+    // Any arguments mean we're used from a script or a terminal, so skip the GUI entirely
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
+
+    // Open where it was last closed
+    let window_state = WindowState::path().map(|path| return WindowState::load(&path)).unwrap_or_default();
+    let mut viewport = window_state.apply(ViewportBuilder::default(), vec2(800.0, 600.0));
+    // The icon is only cosmetic, so a bad one shouldn't stop the app from launching
+    match icon_data::from_png_bytes(&include_bytes!("../icons/nvidia_manager.png")[..]) {
+        Ok(icon) => viewport = viewport.with_icon(icon),
+        Err(e) => error!("Failed to decode the app icon, launching without one: {e}"),
+    }
+
+    // Runs as the user, the few things that need root ask for it through pkexec when they happen
+    let result = eframe::run_native(
         "Nvidia Manager",
         NativeOptions {
-            viewport: ViewportBuilder::default()
-                .with_inner_size(vec2(800.0, 600.0))
-                .with_icon(icon_data::from_png_bytes(&include_bytes!("../icons/nvidia_manager.png")[..]).unwrap()),
+            viewport,
             ..Default::default()
         },
-        Box::new(|_cc| return Ok(Box::new(MyApp::new()))),
-    ).expect("Error running the app");
+        Box::new(|cc| {
+            let mut app = MyApp::new().map_err(|e| return e.to_string())?;
+            app.watch_config(&cc.egui_ctx);
+            app.settings.density.apply(&cc.egui_ctx);
+            return Ok(Box::new(app))
+        }),
+    );
+    if let Err(e) = result {
+        error!("Error running the app: {e}");
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CONFIG_VERSION;
+
+    /// A fresh folder for a test to work in
+    fn test_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("nvidia_manager_main_test_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        return folder
+    }
+
+    #[test]
+    fn flatpak_offloads_round_trip_through_the_config() {
+        let folder = test_folder("flatpaks");
+        let config_path = folder.join("config.toml");
+        let offload = FlatpakOffload {
+            backend: OffloadBackend::DriPrime,
+            changed: flatpak::Override { keys: vec!["DRI_PRIME".to_string(), "DXVK_HUD".to_string()], previous: vec![("DXVK_HUD".to_string(), "fps".to_string())] },
+        };
+        set_flatpak(&config_path, "org.example.Game", Some(offload.clone()));
+        let read = Config::read(&config_path).flatpaks;
+        assert_eq!(read["org.example.Game"].backend, OffloadBackend::DriPrime);
+        assert_eq!(read["org.example.Game"].changed, offload.changed);
+        set_flatpak(&config_path, "org.example.Game", None);
+        assert!(Config::read(&config_path).flatpaks.is_empty());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn flatpak_offloads_of_older_versions_are_only_unset() {
+        let folder = test_folder("flatpaks_legacy");
+        let config_path = folder.join("config.toml");
+        fs::write(&config_path, "version = 1\nentries = []\n\n[flatpaks]\n\"org.example.Game\" = [\"__NV_PRIME_RENDER_OFFLOAD\"]\n").unwrap();
+        let read = Config::read(&config_path).flatpaks;
+        assert_eq!(read["org.example.Game"].backend, OffloadBackend::Nvidia);
+        assert_eq!(read["org.example.Game"].changed, flatpak::Override { keys: vec!["__NV_PRIME_RENDER_OFFLOAD".to_string()], previous: Vec::new() });
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_carries_the_flatpak_apps() {
+        let folder = test_folder("export_flatpaks");
+        let (config_path, export_path) = (folder.join("config.toml"), folder.join("export.toml"));
+        fs::write(folder.join("game"), "").unwrap();
+        let entry = add_config(&folder.join("game").display().to_string(), &config_path, |added| added.backend = OffloadBackend::DriPrime).unwrap();
+        let changed = flatpak::Override { keys: vec!["DRI_PRIME".to_string()], previous: Vec::new() };
+        set_flatpak(&config_path, "org.example.Game", Some(FlatpakOffload { backend: OffloadBackend::DriPrime, changed }));
+
+        assert_eq!(export_config(&config_path, &export_path).unwrap(), 2);
+        let exported = read_exported_config(&export_path).unwrap();
+        assert_eq!(exported.entries.iter().map(|exported| return (exported.path.as_str(), exported.backend)).collect::<Vec<_>>(), [(entry.as_str(), OffloadBackend::DriPrime)]);
+        // What was changed in the overrides is only meaningful here
+        assert_eq!(exported.flatpaks.iter().map(|(app_id, offload)| return (app_id.as_str(), offload.backend, offload.changed.keys.len())).collect::<Vec<_>>(), [("org.example.Game", OffloadBackend::DriPrime, 0)]);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn read_config_cleans_up_hand_edited_entries() {
+        let folder = test_folder("read_config");
+        let config_path = folder.join("config.toml");
+        fs::write(&config_path, r#"version = 1
+entries = ["  ", "", "/games/a  ", "/games/a", "\t/games/b", "/games/b", "/games/B", "/games/with space "]
+"#).unwrap();
+        let expected: HashSet<String> = ["/games/a", "/games/b", "/games/B", "/games/with space"].into_iter().map(ToString::to_string).collect();
+        assert_eq!(Config::read(&config_path).paths(), expected);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn relative_paths_are_stored_as_absolute() {
+        let folder = test_folder("relative_paths");
+        let config_path = folder.join("config.toml");
+        // Tests run from the crate's folder
+        let expected = std::env::current_dir().unwrap().canonicalize().unwrap().join("src").join("main.rs");
+        for relative in ["src/main.rs", "./src/main.rs", "src/../src/main.rs", " src/main.rs "] {
+            assert_eq!(add_config(relative, &config_path, |_| {}).unwrap(), expected.to_str().unwrap(), "{relative}");
+        }
+        assert_eq!(Config::read(&config_path).paths(), HashSet::from([expected.to_str().unwrap().to_string()]));
+        assert!(add_config("src/missing.rs", &config_path, |_| {}).is_err());
+        assert!(add_config("missing/main.rs", &config_path, |_| {}).is_err());
+        assert_eq!(Config::read(&config_path).entries.len(), 1);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn plain_text_configs_migrate_to_toml() {
+        let folder = test_folder("migrate_v0");
+        let old_config_path = folder.join("config.txt");
+        let config_path = folder.join("config.toml");
+        fs::write(&old_config_path, "/games/b\n\n  /games/a  \n   \n/games/b\n/games/with \"quotes\" and 'more'\n").unwrap();
+
+        migrate_config(&old_config_path, &config_path).unwrap();
+        let config = Config::load(&config_path).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let entries: Vec<&str> = config.entries.iter().map(|entry| return entry.path.as_str()).collect();
+        assert_eq!(entries, ["/games/a", "/games/b", "/games/with \"quotes\" and 'more'"]);
+        // The old one is kept around, but out of the way so it isn't migrated again
+        assert!(!old_config_path.exists());
+        assert_eq!(fs::read_to_string(folder.join("config.txt.v0")).unwrap().lines().count(), 6);
+
+        // Once there's a TOML config, a plain text one showing up again is left alone
+        fs::write(&old_config_path, "/games/c\n").unwrap();
+        migrate_config(&old_config_path, &config_path).unwrap();
+        assert!(old_config_path.exists() && Config::read(&config_path).entry("/games/c").is_none());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn migrating_without_an_old_config_does_nothing() {
+        let folder = test_folder("migrate_nothing");
+        let config_path = folder.join("config.toml");
+        migrate_config(&folder.join("config.txt"), &config_path).unwrap();
+        assert!(!config_path.exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn undoing_a_removal_adds_back_the_way_it_was_added() {
+        let mut removed = Entry::new("/games/a");
+        removed.bind_mount = true;
+        removed.backend = OffloadBackend::DriPrime;
+        removed.env.set("DXVK_HUD", "fps");
+        let action = LastAction::removed("/games/a", Some(removed.clone()));
+        assert_eq!((action.backend, action.strategy, action.env), (OffloadBackend::DriPrime, WrapStrategy::BindMount, removed.env));
+
+        let action = LastAction::removed("/games/b", None);
+        assert_eq!((action.backend, action.strategy, action.env), (OffloadBackend::Nvidia, WrapStrategy::InPlaceSymlink, WrapperEnv::default()));
+    }
+
+    #[test]
+    fn indexes_are_migrated_onto_the_entries() {
+        let folder = test_folder("migrate_indexes");
+        let config_path = folder.join("config.toml");
+        add_config(folder.to_str().unwrap(), &config_path, |_| {}).unwrap();
+        let original = folder.join("game");
+        fs::write(folder.join("backups.toml"), format!("{:?} = {:?}\n", original.display().to_string(), folder.join("backups/abc").display().to_string())).unwrap();
+        fs::write(folder.join("hashes.toml"), format!("{:?} = \"abc\"\n", original.display().to_string())).unwrap();
+        fs::write(folder.join("permissions.toml"), format!("{:?} = {{ mode = 493, uid = 1000, gid = 1000 }}\n", original.display().to_string())).unwrap();
+
+        migrate_indexes(&folder, &config_path).unwrap();
+        let config = Config::read(&config_path);
+        let wrapped = config.entries[0].wrapped.get(original.to_str().unwrap()).unwrap();
+        assert_eq!(wrapped.backup, Some(folder.join("backups/abc")));
+        assert_eq!(wrapped.sha256.as_deref(), Some("abc"));
+        assert!(wrapped.permissions.is_some());
+        assert!(!folder.join("backups.toml").exists() && !folder.join("hashes.toml").exists() && !folder.join("permissions.toml").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn configs_from_newer_versions_are_refused_and_unknown_fields_kept() {
+        let folder = test_folder("config_version");
+        let config_path = folder.join("config.toml");
+        let newer = format!("version = {}\nentries = []\n", CONFIG_VERSION + 1);
+        fs::write(&config_path, &newer).unwrap();
+        assert!(Config::load(&config_path).is_err());
+        assert!(Config::update(&config_path, |config| return config.add("/games/a").launcher = true).is_err());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), newer);
+
+        // Fields a newer version added without bumping the version survive us writing the config
+        fs::write(&config_path, format!("version = {CONFIG_VERSION}\nfuture_field = \"kept\"\n\n[[entries]]\npath = \"/games/a\"\nfuture_entry_field = 2\n\n[future_table]\nkey = 1\n")).unwrap();
+        Config::update(&config_path, |config| return config.add("/games/b").launcher = true).unwrap();
+        let document = fs::read_to_string(&config_path).unwrap().parse::<toml::Table>().unwrap();
+        assert_eq!(document["future_field"].as_str(), Some("kept"));
+        assert_eq!(document["future_table"]["key"].as_integer(), Some(1));
+        assert_eq!(document["entries"][0]["future_entry_field"].as_integer(), Some(2));
+        assert_eq!(Config::read(&config_path).paths().len(), 2);
+        fs::remove_dir_all(&folder).unwrap();
+    }
 }
@@ -0,0 +1,107 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use log::{error, info};
+
+use crate::internals::{backup_path, execute, generate_wrapper_name, remove_stale_wrapper, revert, wrapper_state, OffloadBackend, WrapOptions, WrapperEnv, WrapperState};
+
+/// What the throwaway executable starts with
+const TEST_SCRIPT: &str = "#!/bin/sh\necho 'Nvidia Manager self-test'\n";
+
+/// The mode the throwaway executable starts with, which has to come back after reverting
+const TEST_MODE: u32 = 0o750;
+
+/// One step of the self-test, with what went wrong if it failed
+pub struct Check {
+    pub name: &'static str,
+    pub result: Result<(), String>,
+}
+
+/// Wrap a throwaway executable in a temporary folder and revert it again, checking every step along the way.
+/// Runs the same code as adding and removing something for real, so a pass means those work on this machine
+pub fn run(wrapper_dir: &Path) -> Vec<Check> {
+    // The process id keeps two runs at once apart
+    let folder = std::env::temp_dir().join(format!("nvidia_manager_self_test_{}", std::process::id()));
+    let contents = format!("{TEST_SCRIPT}# {}\n", std::process::id());
+    // Wrapping goes by the real location, which the temporary folder may be a symlink to
+    let executable = fs::create_dir_all(&folder).and_then(|()| return folder.canonicalize()).unwrap_or_else(|_| return folder.clone()).join("self_test_app");
+
+    let mut checks = Vec::new();
+    let mut check = |name: &'static str, result: Result<(), String>| {
+        let passed = result.is_ok();
+        match &result {
+            Ok(()) => info!("Self-test: {name} passed"),
+            Err(e) => error!("Self-test: {name} failed: {e}"),
+        }
+        checks.push(Check { name, result });
+        return passed
+    };
+
+    let _ = check("Create a test executable", create_executable(&executable, &contents))
+        && check("Wrap it", execute(&wrapper_dir.to_path_buf(), &executable, OffloadBackend::Nvidia, &WrapperEnv::default(), &WrapOptions::default()).map(drop).map_err(|e| return e.to_string()))
+        && check("Check the wrapped executable", verify_wrapped(wrapper_dir, &executable, &contents))
+        && check("Revert it", revert(wrapper_dir, &executable).map_err(|e| return e.to_string()))
+        && check("Check the restored original", verify_restored(wrapper_dir, &executable, &contents));
+
+    // Whatever happened above, nothing may be left behind
+    if backup_path(&executable).exists() {
+        let _ = revert(wrapper_dir, &executable);
+    }
+    remove_stale_wrapper(wrapper_dir, &executable);
+    let _ = fs::remove_dir_all(&folder);
+    return checks
+}
+
+fn create_executable(executable: &Path, contents: &str) -> Result<(), String> {
+    fs::write(executable, contents).map_err(|e| return format!("Failed to write {}: {e}", executable.display()))?;
+    fs::set_permissions(executable, fs::Permissions::from_mode(TEST_MODE)).map_err(|e| return format!("Failed to make {} executable: {e}", executable.display()))?;
+    return Ok(())
+}
+
+/// The executable has to be a symlink to its wrapper script, which launches the untouched backup with the offload environment
+fn verify_wrapped(wrapper_dir: &Path, executable: &Path, contents: &str) -> Result<(), String> {
+    let wrapper_path = wrapper_dir.join(generate_wrapper_name(executable));
+    let destination = fs::read_link(executable).map_err(|e| return format!("{} isn't a symlink: {e}", executable.display()))?;
+    if destination != wrapper_path {
+        return Err(format!("{} points at {} instead of {}", executable.display(), destination.display(), wrapper_path.display()));
+    }
+
+    let backup = backup_path(executable);
+    let backed_up = fs::read_to_string(&backup).map_err(|e| return format!("Failed to read the backup {}: {e}", backup.display()))?;
+    if backed_up != contents {
+        return Err(format!("The backup {} isn't the same as the original", backup.display()));
+    }
+
+    let script = fs::read_to_string(&wrapper_path).map_err(|e| return format!("Failed to read the wrapper script {}: {e}", wrapper_path.display()))?;
+    if !script.contains(&*backup.to_string_lossy()) {
+        return Err(format!("The wrapper script {} doesn't launch the backup {}", wrapper_path.display(), backup.display()));
+    }
+    if let Some((key, _)) = OffloadBackend::Nvidia.env(&WrapperEnv::default()).vars.iter().find(|(key, _)| return !script.contains(key.as_str())) {
+        return Err(format!("The wrapper script {} doesn't set {key}", wrapper_path.display()));
+    }
+
+    if wrapper_state(executable) != WrapperState::Enabled {
+        return Err(format!("{} is {} instead of Enabled", executable.display(), wrapper_state(executable).label()));
+    }
+    return Ok(())
+}
+
+/// The original has to be back byte for byte with its permissions, and nothing of the wrapping may be left
+fn verify_restored(wrapper_dir: &Path, executable: &Path, contents: &str) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(executable).map_err(|e| return format!("{} is gone: {e}", executable.display()))?;
+    if !metadata.is_file() {
+        return Err(format!("{} isn't a regular file again", executable.display()));
+    }
+    if fs::read_to_string(executable).ok().as_deref() != Some(contents) {
+        return Err(format!("{} isn't the same as before it was wrapped", executable.display()));
+    }
+    if metadata.permissions().mode() & 0o7777 != TEST_MODE {
+        return Err(format!("{} has mode {:o} instead of {TEST_MODE:o}", executable.display(), metadata.permissions().mode() & 0o7777));
+    }
+
+    if let Some(leftover) = [backup_path(executable), wrapper_dir.join(generate_wrapper_name(executable))].into_iter().find(|path| return path.exists()) {
+        return Err(format!("{} was left behind", leftover.display()));
+    }
+    return Ok(())
+}
@@ -0,0 +1,242 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::{Context, vec2};
+use log::{error, warn, LevelFilter};
+use toml_edit::{value, Array, Document};
+
+use crate::internals::{ScanOptions, WrapOptions, WrapperShell, DEFAULT_EXCLUDE_GLOBS, DEFAULT_SYSTEM_PATHS};
+
+/// How tightly the lists are packed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Density {
+    Comfortable,
+    Compact,
+}
+
+impl Density {
+    pub const ALL: [Density; 2] = [Density::Comfortable, Density::Compact];
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            Density::Comfortable => "Comfortable",
+            Density::Compact => "Compact",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|density| return density.name() == name)
+    }
+
+    /// Apply the spacing of this density to egui
+    pub fn apply(self, ctx: &Context) {
+        ctx.style_mut(|style| {
+            let (item_spacing, button_padding, row_height) = match self {
+                Density::Comfortable => (vec2(8.0, 3.0), vec2(4.0, 1.0), 18.0),
+                Density::Compact => (vec2(6.0, 1.0), vec2(2.0, 0.0), 14.0),
+            };
+            style.spacing.item_spacing = item_spacing;
+            style.spacing.button_padding = button_padding;
+            style.spacing.interact_size.y = row_height;
+        });
+    }
+}
+
+/// How newly wrapped executables get wrapped
+pub struct WrapSettings {
+    /// Keep the backups in `wrapper_dir/backups` rather than as `.bak` files next to them
+    pub central_backups: bool,
+    /// Add a `<name>-igpu` symlink next to them that launches them on the integrated GPU
+    pub igpu_links: bool,
+    /// Offer executables we can't write to as well, wrapping them in place through `pkexec` instead of failing
+    pub elevated: bool,
+    /// The shell the wrapper scripts run with
+    pub shell: WrapperShell,
+}
+
+impl Default for WrapSettings {
+    fn default() -> Self {
+        return WrapSettings { central_backups: false, igpu_links: false, elevated: false, shell: WrapperShell::EnvBash }
+    }
+}
+
+/// What gets looked at when wrapping a whole folder or listing processes
+pub struct ScanSettings {
+    /// Globs of the subfolders skipped when wrapping a whole folder
+    pub exclude_globs: Vec<String>,
+    /// How many folders deep wrapping a whole folder looks, 0 looks all the way down
+    pub max_depth: usize,
+    /// Skip hidden files and folders when wrapping a whole folder
+    pub skip_hidden: bool,
+    /// Folders whose executables are never listed or wrapped
+    pub system_paths: Vec<String>,
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        return ScanSettings {
+            exclude_globs: DEFAULT_EXCLUDE_GLOBS.iter().map(ToString::to_string).collect(),
+            max_depth: 0,
+            skip_hidden: true,
+            system_paths: DEFAULT_SYSTEM_PATHS.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// User preferences, stored as TOML next to the config file
+pub struct Settings {
+    pub density: Density,
+    /// Where the wrapper scripts live, if moved away from the default data directory
+    pub wrapper_dir: Option<PathBuf>,
+    pub wrapping: WrapSettings,
+    pub scan: ScanSettings,
+    /// Extra anti-cheat file/folder names to warn about on top of the built-in ones, only ever edited by hand
+    pub anti_cheat_patterns: Vec<String>,
+    /// Hide the window to the tray when it's closed, if there's a tray to hide to
+    pub close_to_tray: bool,
+    /// Add the app of the focused window with a global shortcut
+    pub global_hotkey: bool,
+    /// The shortcut for that, like `ctrl+alt+N`
+    pub hotkey: String,
+    /// Re-read the running processes every this many seconds, 0 turns it off
+    pub refresh_interval: u64,
+    /// List every process with a writable executable, not just the ones with a window
+    pub show_all_processes: bool,
+    /// How much gets logged, `NVIDIA_MANAGER_LOG` takes precedence when it's set
+    pub log_level: LevelFilter,
+    /// Command run with the path after something gets wrapped, off unless set by hand
+    pub post_wrap: Option<String>,
+    /// Command run with the path after something gets unwrapped, off unless set by hand
+    pub post_unwrap: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Settings {
+            density: Density::Comfortable,
+            wrapper_dir: None,
+            wrapping: WrapSettings::default(),
+            scan: ScanSettings::default(),
+            anti_cheat_patterns: Vec::new(),
+            close_to_tray: false,
+            global_hotkey: false,
+            hotkey: "ctrl+alt+N".to_string(),
+            refresh_interval: 0,
+            show_all_processes: false,
+            log_level: LevelFilter::Info,
+            post_wrap: None,
+            post_unwrap: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Load the settings, falling back to the defaults for anything missing or invalid
+    pub fn load(settings_path: &Path) -> Self {
+        let mut settings = Settings::default();
+        let Ok(document) = fs::read_to_string(settings_path).unwrap_or_default().parse::<Document>() else {
+            warn!("Failed to parse {}, using the default settings", settings_path.display());
+            return settings;
+        };
+
+        if let Some(density) = document.get("density").and_then(|item| return item.as_str()).and_then(Density::from_name) {
+            settings.density = density;
+        }
+        if let Some(wrapper_dir) = document.get("wrapper_dir").and_then(|item| return item.as_str()) {
+            settings.wrapper_dir = Some(PathBuf::from(wrapper_dir));
+        }
+        if let Some(central_backups) = document.get("central_backups").and_then(|item| return item.as_bool()) {
+            settings.wrapping.central_backups = central_backups;
+        }
+        if let Some(igpu_links) = document.get("igpu_links").and_then(|item| return item.as_bool()) {
+            settings.wrapping.igpu_links = igpu_links;
+        }
+        if let Some(elevated_wrapping) = document.get("elevated_wrapping").and_then(|item| return item.as_bool()) {
+            settings.wrapping.elevated = elevated_wrapping;
+        }
+        if let Some(wrapper_shell) = document.get("wrapper_shell").and_then(|item| return item.as_str()).and_then(WrapperShell::from_name) {
+            settings.wrapping.shell = wrapper_shell;
+        }
+        if let Some(close_to_tray) = document.get("close_to_tray").and_then(|item| return item.as_bool()) {
+            settings.close_to_tray = close_to_tray;
+        }
+        if let Some(global_hotkey) = document.get("global_hotkey").and_then(|item| return item.as_bool()) {
+            settings.global_hotkey = global_hotkey;
+        }
+        if let Some(hotkey) = document.get("hotkey").and_then(|item| return item.as_str()) {
+            settings.hotkey = hotkey.to_string();
+        }
+        if let Some(refresh_interval) = document.get("refresh_interval").and_then(|item| return item.as_integer()).and_then(|seconds| return u64::try_from(seconds).ok()) {
+            settings.refresh_interval = refresh_interval;
+        }
+        if let Some(show_all_processes) = document.get("show_all_processes").and_then(|item| return item.as_bool()) {
+            settings.show_all_processes = show_all_processes;
+        }
+        if let Some(log_level) = document.get("log_level").and_then(|item| return item.as_str()).and_then(|level| return level.parse::<LevelFilter>().ok()) {
+            settings.log_level = log_level;
+        }
+        if let Some(post_wrap) = document.get("post_wrap").and_then(|item| return item.as_str()).filter(|hook| return !hook.trim().is_empty()) {
+            settings.post_wrap = Some(post_wrap.to_string());
+        }
+        if let Some(post_unwrap) = document.get("post_unwrap").and_then(|item| return item.as_str()).filter(|hook| return !hook.trim().is_empty()) {
+            settings.post_unwrap = Some(post_unwrap.to_string());
+        }
+        if let Some(max_depth) = document.get("max_depth").and_then(|item| return item.as_integer()).and_then(|depth| return usize::try_from(depth).ok()) {
+            settings.scan.max_depth = max_depth;
+        }
+        if let Some(skip_hidden) = document.get("skip_hidden").and_then(|item| return item.as_bool()) {
+            settings.scan.skip_hidden = skip_hidden;
+        }
+        if let Some(globs) = document.get("exclude_globs").and_then(|item| return item.as_array()) {
+            settings.scan.exclude_globs = globs.iter().filter_map(|glob| return glob.as_str()).map(ToString::to_string).collect();
+        }
+        if let Some(system_paths) = document.get("system_paths").and_then(|item| return item.as_array()) {
+            settings.scan.system_paths = system_paths.iter().filter_map(|prefix| return prefix.as_str()).map(ToString::to_string).collect();
+        }
+        if let Some(patterns) = document.get("anti_cheat_patterns").and_then(|item| return item.as_array()) {
+            settings.anti_cheat_patterns = patterns.iter().filter_map(|pattern| return pattern.as_str()).map(ToString::to_string).collect();
+        }
+        return settings
+    }
+
+    /// What wrapping a whole folder skips
+    pub fn scan_options(&self) -> ScanOptions {
+        return ScanOptions {
+            exclude_globs: self.scan.exclude_globs.clone(),
+            max_depth: (self.scan.max_depth > 0).then_some(self.scan.max_depth),
+            skip_hidden: self.scan.skip_hidden,
+            system_paths: self.scan.system_paths.clone(),
+        }
+    }
+
+    /// How executables get wrapped, the Proton environment, launch helpers and dry runs are up to whoever wraps them
+    pub fn wrap_options(&self) -> WrapOptions {
+        return WrapOptions { central_backups: self.wrapping.central_backups, igpu_link: self.wrapping.igpu_links, scan: self.scan_options(), ..WrapOptions::default() }
+    }
+
+    /// Save the settings, keeping anything else the user wrote in the file
+    pub fn save(&self, settings_path: &Path) {
+        let mut document = fs::read_to_string(settings_path).unwrap_or_default().parse::<Document>().unwrap_or_default();
+        document["density"] = value(self.density.name());
+        document["central_backups"] = value(self.wrapping.central_backups);
+        document["igpu_links"] = value(self.wrapping.igpu_links);
+        document["elevated_wrapping"] = value(self.wrapping.elevated);
+        document["wrapper_shell"] = value(self.wrapping.shell.name());
+        document["close_to_tray"] = value(self.close_to_tray);
+        document["global_hotkey"] = value(self.global_hotkey);
+        document["hotkey"] = value(self.hotkey.as_str());
+        document["refresh_interval"] = value(i64::try_from(self.refresh_interval).unwrap_or(i64::MAX));
+        document["show_all_processes"] = value(self.show_all_processes);
+        document["log_level"] = value(self.log_level.as_str().to_lowercase());
+        document["max_depth"] = value(i64::try_from(self.scan.max_depth).unwrap_or(i64::MAX));
+        document["skip_hidden"] = value(self.scan.skip_hidden);
+        document["exclude_globs"] = value(self.scan.exclude_globs.iter().map(String::as_str).collect::<Array>());
+        document["system_paths"] = value(self.scan.system_paths.iter().map(String::as_str).collect::<Array>());
+        if let Some(wrapper_dir) = &self.wrapper_dir {
+            document["wrapper_dir"] = value(wrapper_dir.display().to_string());
+        }
+        if let Err(e) = fs::write(settings_path, document.to_string()) {
+            error!("Failed to write the settings to {}: {e}", settings_path.display());
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+use egui::{Align2, Color32, Context, Frame, vec2};
+
+/// How long a confirmation stays up, errors stay until they're dismissed
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// How many are shown at once, the oldest ones make room for new ones
+const MAX_TOASTS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ToastKind {
+    Info,
+    Error,
+}
+
+struct Toast {
+    kind: ToastKind,
+    message: String,
+    shown_at: Instant,
+}
+
+/// Short-lived notifications in the corner of the window, confirming what Add/Remove did or telling what went wrong
+#[derive(Default)]
+pub struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    /// Confirm something that worked, gone after a few seconds
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(ToastKind::Info, message.into());
+    }
+
+    /// Tell about something that failed, shown until dismissed
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(ToastKind::Error, message.into());
+    }
+
+    fn push(&mut self, kind: ToastKind, message: String) {
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.toasts.push(Toast { kind, message, shown_at: Instant::now() });
+    }
+
+    /// Show them stacked in the bottom right corner, newest at the bottom
+    pub fn show(&mut self, ctx: &Context) {
+        self.toasts.retain(|toast| return toast.kind == ToastKind::Error || toast.shown_at.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() { return; }
+
+        let mut dismissed = None;
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(Align2::RIGHT_BOTTOM, vec2(-8.0, -8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                ui.set_max_width(360.0);
+                for (index, toast) in self.toasts.iter().enumerate() {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            match toast.kind {
+                                ToastKind::Info => { ui.label(&toast.message); },
+                                ToastKind::Error => { ui.colored_label(Color32::RED, &toast.message); },
+                            }
+                            if ui.small_button("✖").clicked() {
+                                dismissed = Some(index);
+                            }
+                        });
+                    });
+                }
+            });
+        if let Some(index) = dismissed {
+            self.toasts.remove(index);
+        }
+
+        // Repaint once the next confirmation is due to go away, nothing else might trigger it
+        if let Some(remaining) = self.toasts.iter().filter(|toast| return toast.kind == ToastKind::Info).map(|toast| return TOAST_DURATION.saturating_sub(toast.shown_at.elapsed())).min() {
+            ctx.request_repaint_after(remaining);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use egui::{Context, ViewportCommand};
+use ksni::blocking::TrayMethods;
+use ksni::menu::StandardItem;
+use ksni::MenuItem;
+use log::warn;
+
+/// Tray icon to bring the window back after closing it to the tray, or to actually quit
+pub struct Tray {
+    /// Set once the desktop shows our icon, without it a hidden window could never come back
+    available: Arc<AtomicBool>,
+    /// Set when quitting from the tray, so closing the window isn't turned into hiding it
+    quitting: Arc<AtomicBool>,
+}
+
+impl Tray {
+    /// Start the tray icon on its own thread, without a desktop that shows it closing the window keeps quitting
+    pub fn spawn(ctx: &Context) -> Self {
+        let available = Arc::new(AtomicBool::new(false));
+        let quitting = Arc::new(AtomicBool::new(false));
+        let spawned = TrayIcon {
+            ctx: ctx.clone(),
+            available: Arc::clone(&available),
+            quitting: Arc::clone(&quitting),
+        }.spawn();
+        if let Err(e) = spawned {
+            warn!("Couldn't show a tray icon, closing the window quits: {e}");
+        }
+        return Tray { available, quitting }
+    }
+
+    /// Check if closing the window should hide it to the tray instead
+    pub fn hides_on_close(&self) -> bool {
+        return self.available.load(Ordering::Relaxed) && !self.quitting.load(Ordering::Relaxed)
+    }
+}
+
+/// The `StatusNotifierItem` itself, living on the tray thread
+struct TrayIcon {
+    ctx: Context,
+    available: Arc<AtomicBool>,
+    quitting: Arc<AtomicBool>,
+}
+
+impl TrayIcon {
+    fn show_window(&self) {
+        self.ctx.send_viewport_cmd(ViewportCommand::Visible(true));
+        self.ctx.send_viewport_cmd(ViewportCommand::Focus);
+        self.ctx.request_repaint();
+    }
+}
+
+impl ksni::Tray for TrayIcon {
+    fn id(&self) -> String {
+        return "NvidiaManager".to_string()
+    }
+
+    fn title(&self) -> String {
+        return "Nvidia Manager".to_string()
+    }
+
+    fn icon_name(&self) -> String {
+        return "video-display".to_string()
+    }
+
+    fn watcher_online(&self) {
+        self.available.store(true, Ordering::Relaxed);
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.show_window();
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        return vec![
+            StandardItem {
+                label: "Show".to_string(),
+                activate: Box::new(|tray: &mut Self| { tray.show_window(); }),
+                ..Default::default()
+            }.into(),
+            StandardItem {
+                label: "Quit".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    tray.quitting.store(true, Ordering::Relaxed);
+                    tray.ctx.send_viewport_cmd(ViewportCommand::Close);
+                    tray.ctx.request_repaint();
+                }),
+                ..Default::default()
+            }.into(),
+        ]
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::{pos2, vec2, Context, Pos2, Vec2, ViewportBuilder};
+use log::{error, warn};
+use toml_edit::{value, Document};
+
+/// Smaller than this is most likely a window that got squashed by accident, which isn't worth restoring
+const MIN_SIZE: Vec2 = Vec2 { x: 320.0, y: 240.0 };
+
+/// Where and how big the window was when it was last closed, and what was selected, restored on the next launch
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowState {
+    pub size: Option<Vec2>,
+    /// Not known on Wayland, where the compositor places the window
+    pub position: Option<Pos2>,
+    pub maximized: bool,
+    pub selected: Option<String>,
+}
+
+impl WindowState {
+    /// Where the state is kept, next to the settings
+    pub fn path() -> Option<PathBuf> {
+        return xdg::BaseDirectories::with_prefix("NvidiaManager").ok()?.place_data_file("config/window_state.toml").ok()
+    }
+
+    /// Load the state, anything missing or invalid is left to the defaults
+    #[allow(clippy::cast_possible_truncation)] // Pixels, nowhere near the precision of an f32
+    pub fn load(path: &Path) -> Self {
+        let mut state = WindowState::default();
+        let Ok(content) = fs::read_to_string(path) else { return state };
+        let Ok(document) = content.parse::<Document>() else {
+            warn!("Failed to parse {}, opening the window at the default size", path.display());
+            return state;
+        };
+
+        let number = |key: &str| return document.get(key).and_then(|item| return item.as_float()).map(|number| return number as f32);
+        if let (Some(width), Some(height)) = (number("width"), number("height")) {
+            state.size = Some(vec2(width, height).max(MIN_SIZE));
+        }
+        if let (Some(x), Some(y)) = (number("x"), number("y")) {
+            state.position = Some(pos2(x, y));
+        }
+        state.maximized = document.get("maximized").and_then(|item| return item.as_bool()).unwrap_or(false);
+        state.selected = document.get("selected").and_then(|item| return item.as_str()).map(ToString::to_string);
+        return state
+    }
+
+    /// Save the state, replacing whatever was saved before
+    pub fn save(&self, path: &Path) {
+        let mut document = Document::new();
+        if let Some(size) = self.size {
+            document["width"] = value(f64::from(size.x));
+            document["height"] = value(f64::from(size.y));
+        }
+        if let Some(position) = self.position {
+            document["x"] = value(f64::from(position.x));
+            document["y"] = value(f64::from(position.y));
+        }
+        document["maximized"] = value(self.maximized);
+        if let Some(selected) = &self.selected {
+            document["selected"] = value(selected.as_str());
+        }
+        if let Err(e) = fs::write(path, document.to_string()) {
+            error!("Failed to write the window state to {}: {e}", path.display());
+        }
+    }
+
+    /// Take the current geometry of the window. The size and position while maximized are the screen's, so the ones from before are kept
+    pub fn capture(&mut self, ctx: &Context) {
+        ctx.input(|input| {
+            let viewport = input.viewport();
+            self.maximized = viewport.maximized.unwrap_or(false);
+            if self.maximized { return; }
+            if let Some(inner_rect) = viewport.inner_rect {
+                self.size = Some(inner_rect.size());
+            }
+            if let Some(outer_rect) = viewport.outer_rect {
+                self.position = Some(outer_rect.min);
+            }
+        });
+    }
+
+    /// Open the window the way it was, at the default size if there's nothing saved
+    pub fn apply(&self, viewport: ViewportBuilder, default_size: Vec2) -> ViewportBuilder {
+        let mut viewport = viewport.with_inner_size(self.size.unwrap_or(default_size)).with_maximized(self.maximized);
+        if let Some(position) = self.position {
+            viewport = viewport.with_position(position);
+        }
+        return viewport
+    }
+}